@@ -0,0 +1,46 @@
+//! Times the editor drawing large buffers into a sink, like a pasted or recalled entry of
+//! several kilobytes. Run with `cargo bench`.
+
+use repline::editor::Editor;
+use std::{
+    hint::black_box,
+    io::sink,
+    time::{Duration, Instant},
+};
+
+/// Runs `f` until a second has passed, and prints the mean time it took
+fn bench(name: &str, mut f: impl FnMut()) {
+    let (start, mut runs) = (Instant::now(), 0u32);
+    while start.elapsed() < Duration::from_secs(1) {
+        f();
+        runs += 1;
+    }
+    println!("{name:<24} {:>12.2?}", start.elapsed() / runs);
+}
+
+/// Returns an editor drawing on an 80x24 terminal
+fn editor() -> Editor<'static> {
+    let mut ed = Editor::new("", "> ", ". ");
+    (ed.cols, ed.rows) = (80, 24);
+    ed
+}
+
+fn main() {
+    let entry = "let x = some_function(argument, another_argument);\n".repeat(100);
+    bench("extend 5KB", || {
+        let mut ed = editor();
+        ed.extend(entry.chars(), &mut sink()).unwrap();
+        black_box(ed);
+    });
+    bench("restore 5KB", || {
+        let mut ed = editor();
+        ed.restore(&entry, &mut sink()).unwrap();
+        black_box(ed);
+    });
+    let mut ed = editor();
+    ed.extend(entry.chars(), &mut sink()).unwrap();
+    bench("type after 5KB", || {
+        ed.push('x', &mut sink()).unwrap();
+        ed.pop(&mut sink()).unwrap();
+    });
+}
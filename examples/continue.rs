@@ -6,15 +6,15 @@
 //! 3. Returns a [Result] containing a [Response] or an [Err]
 
 use repline::{Repline, Response, error::Error as RlError};
-use std::error::Error;
+use std::{error::Error, io::Write};
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let mut rl = Repline::with_input(
-        "fn main {\r\n\tprintln(\"Foo!\")\r\n}\r\n".as_bytes(),
+    let mut rl = Repline::with_script(
+        "fn main {<Enter><Tab>println(\"Foo!\")<Enter>}<Enter>",
         "\x1b[33m",
         " .> ",
         " ?> ",
-    );
+    )?;
     while rl.read().is_ok() {}
 
     let mut rl = rl.swap_input(std::io::stdin());
@@ -33,9 +33,30 @@ fn main() -> Result<(), Box<dyn Error>> {
         match f(&line) {
             Ok(Response::Accept) => rl.accept(),
             Ok(Response::Deny) => rl.deny(),
+            Ok(Response::Replace(text)) => {
+                rl.deny();
+                rl.add_history(text);
+            }
+            Ok(Response::Prefill(text)) => {
+                rl.deny();
+                rl.set_text(&text);
+            }
+            Ok(Response::Diagnose(found)) => {
+                rl.deny();
+                rl.set_text(line.strip_suffix('\n').unwrap_or(&line));
+                rl.report_diagnostics(found);
+            }
             Ok(Response::Break) => break,
             Ok(Response::Continue) => continue,
-            Err(e) => print!("\x1b[40G\x1b[A\x1bJ\x1b[91m{e}\x1b[0m\x1b[B"),
+            Err(e) => {
+                let mut stdout = std::io::stdout();
+                if let Some(end) = rl.end_position() {
+                    end.move_to(&mut stdout)?;
+                    write!(stdout, "  \x1b[91m{e}\x1b[0m")?;
+                    end.move_back(&mut stdout)?;
+                }
+                stdout.flush()?;
+            }
         }
     }
     Ok(())
@@ -0,0 +1,43 @@
+/* C bindings for repline. Build the library with the `ffi` feature. */
+#ifndef REPLINE_H
+#define REPLINE_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The line was read successfully */
+#define REPLINE_OK 0
+/* The user pressed Ctrl+C */
+#define REPLINE_CTRL_C 1
+/* The user pressed Ctrl+D */
+#define REPLINE_CTRL_D 2
+/* Reading failed, and no line was returned */
+#define REPLINE_ERROR -1
+
+typedef struct repline repline;
+
+/* Returns NULL if any prompt is NULL or not valid UTF-8 */
+repline *repline_new(const char *color, const char *begin, const char *again);
+void repline_free(repline *rl);
+
+/* Strings returned by repline must be freed with repline_free_string */
+void repline_free_string(char *s);
+
+/* Reads a line, storing a REPLINE_* code in status. Returns NULL on error. */
+char *repline_read(repline *rl, int *status);
+void repline_accept(repline *rl);
+void repline_deny(repline *rl);
+
+void repline_history_add(repline *rl, const char *line);
+size_t repline_history_len(const repline *rl);
+/* Returns NULL if there is no entry at index */
+char *repline_history_get(const repline *rl, size_t index);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* REPLINE_H */
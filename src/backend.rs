@@ -0,0 +1,141 @@
+//! Terminals other than the one repline runs in, like an SSH or telnet session served by the
+//! program, or a serial console.
+//!
+//! A [Repline](crate::Repline) normally draws on its own stdout, puts its own terminal in
+//! raw mode, and asks it for its size. With a [Backend] set, it does all three through the
+//! backend instead, and reads keys from its input as usual:
+//!
+//! ```no_run
+//! use repline::{Repline, backend::Backend};
+//! use std::{io::{Result, Write}, net::TcpStream};
+//!
+//! /// A telnet-ish client, which is assumed to be 80 by 24 and to send keys as they're typed
+//! struct Session(TcpStream);
+//!
+//! impl Write for Session {
+//!     fn write(&mut self, buf: &[u8]) -> Result<usize> {
+//!         self.0.write(buf)
+//!     }
+//!     fn flush(&mut self) -> Result<()> {
+//!         self.0.flush()
+//!     }
+//! }
+//!
+//! impl Backend for Session {
+//!     fn size(&mut self) -> Result<(u16, u16)> {
+//!         Ok((80, 24))
+//!     }
+//! }
+//!
+//! let stream = TcpStream::connect("127.0.0.1:2323")?;
+//! let mut rl = Repline::with_input(stream.try_clone()?, "", "> ", ". ");
+//! rl.set_backend(Session(stream));
+//! let line = rl.read()?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::{
+    cell::{Cell, RefCell},
+    io::{Result, Write},
+    rc::Rc,
+};
+
+/// A terminal to draw on, other than the local one.
+pub trait Backend: Write {
+    /// Gets the width and height of the terminal, in columns and rows
+    fn size(&mut self) -> Result<(u16, u16)>;
+
+    /// Puts the terminal in raw mode, where keys are sent as they're pressed and not echoed,
+    /// or takes it back out.
+    ///
+    /// Does nothing by default, for terminals which are always raw, like an SSH channel
+    /// with a pty, whose client is raw for as long as it's connected.
+    fn set_raw(&mut self, raw: bool) -> Result<()> {
+        let _ = raw;
+        Ok(())
+    }
+}
+
+impl<B: Backend + ?Sized> Backend for &mut B {
+    fn size(&mut self) -> Result<(u16, u16)> {
+        (**self).size()
+    }
+
+    fn set_raw(&mut self, raw: bool) -> Result<()> {
+        (**self).set_raw(raw)
+    }
+}
+
+/// A [Backend] shared between a Repline and what it's drawing, with its raw mode tracked
+/// like the local terminal's
+pub(crate) struct Shared<'a> {
+    backend: RefCell<Box<dyn Backend + 'a>>,
+    guards: Cell<usize>, // the number of Raw guards alive
+    raw: Cell<bool>,     // whether the backend is in raw mode
+}
+
+impl std::fmt::Debug for Shared<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Shared")
+            .field("raw", &self.raw)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> Shared<'a> {
+    pub(crate) fn new(backend: impl Backend + 'a) -> Rc<Self> {
+        let backend = RefCell::new(Box::new(backend) as Box<dyn Backend + 'a>);
+        Rc::new(Self { backend, guards: Cell::new(0), raw: Cell::new(false) })
+    }
+
+    pub(crate) fn size(&self) -> Result<(u16, u16)> {
+        self.backend.borrow_mut().size()
+    }
+
+    pub(crate) fn is_raw(&self) -> bool {
+        self.raw.get()
+    }
+
+    pub(crate) fn set_raw(&self, raw: bool) -> Result<()> {
+        self.backend.borrow_mut().set_raw(raw)?;
+        self.raw.set(raw);
+        Ok(())
+    }
+
+    /// Puts the backend in raw mode, if it isn't already, until the returned guard and every
+    /// other guard alive are dropped, like [raw](crate::raw::raw)
+    pub(crate) fn raw(self: &Rc<Self>) -> Result<Raw<'a>> {
+        if self.guards.get() == 0 || !self.is_raw() {
+            self.set_raw(true)?;
+        }
+        self.guards.set(self.guards.get() + 1);
+        Ok(Raw(self.clone()))
+    }
+}
+
+/// Writes to a [Shared] backend
+pub(crate) struct Writer<'a>(pub(crate) Rc<Shared<'a>>);
+
+impl Write for Writer<'_> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.0.backend.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.0.backend.borrow_mut().flush()
+    }
+}
+
+/// Keeps a [Shared] backend in raw mode while it lives
+pub(crate) struct Raw<'a>(Rc<Shared<'a>>);
+
+impl Drop for Raw<'_> {
+    fn drop(&mut self) {
+        let Self(shared) = self;
+        shared.guards.set(shared.guards.get() - 1);
+        if shared.guards.get() == 0 {
+            // there's nothing to be done about it here
+            let _ = shared.set_raw(false);
+        }
+    }
+}
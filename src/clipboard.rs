@@ -0,0 +1,72 @@
+//! The system clipboard, reached through OSC 52 escape sequences, which the terminal acts on
+//! itself, so they work over SSH too.
+//!
+//! Terminals which don't support OSC 52 ignore it: a copy is lost, and a paste never arrives.
+//! Some only support copying, since reading the clipboard lets any program see it.
+
+use crate::multiplexer::Multiplexer;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Returns the sequence which puts `text` on the clipboard
+pub fn copy(text: &str, multiplexer: Option<Multiplexer>) -> String {
+    wrap(
+        &format!("\x1b]52;c;{}\x07", encode(text.as_bytes())),
+        multiplexer,
+    )
+}
+
+/// Returns the sequence which asks the terminal for the clipboard, which it sends back as
+/// input that [decode_reply] reads
+pub fn request(multiplexer: Option<Multiplexer>) -> String {
+    wrap("\x1b]52;c;?\x07", multiplexer)
+}
+
+/// Reads the clipboard from the body of the terminal's reply, like `52;c;aGk=`, or returns
+/// `None` if it isn't a clipboard reply
+pub fn decode_reply(body: &str) -> Option<String> {
+    let (_, data) = body.strip_prefix("52;")?.split_once(';')?;
+    String::from_utf8(decode(data)?).ok()
+}
+
+/// Wraps `sequence` to be passed through `multiplexer`, if any
+fn wrap(sequence: &str, multiplexer: Option<Multiplexer>) -> String {
+    match multiplexer {
+        Some(multiplexer) => multiplexer.passthrough(sequence),
+        None => sequence.to_string(),
+    }
+}
+
+/// Encodes `bytes` as padded base64
+fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
+
+/// Decodes base64, with or without padding, or returns `None` if it isn't base64
+fn decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut n, mut bits) = (0u32, 0);
+    for c in text.bytes() {
+        n = (n << 6 | BASE64.iter().position(|&b| b == c)? as u32) & 0xffffff;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+        }
+    }
+    Some(out)
+}
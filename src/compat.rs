@@ -0,0 +1,212 @@
+//! A [rustyline](https://docs.rs/rustyline)-shaped interface over [Repline], for porting
+//! programs from rustyline with few changes.
+//!
+//! ```no_run
+//! use repline::compat::{DefaultEditor, ReadlineError};
+//!
+//! let mut rl = DefaultEditor::new()?;
+//! loop {
+//!     match rl.readline(">> ") {
+//!         Ok(line) => {
+//!             rl.add_history_entry(line.as_str())?;
+//!             println!("Line: {line}");
+//!         }
+//!         Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+//!         Err(e) => return Err(e),
+//!     }
+//! }
+//! # Ok::<(), ReadlineError>(())
+//! ```
+//!
+//! Unlike rustyline's, these lines can span multiple lines on screen, and the rest of
+//! repline is available through [repline](Editor::repline).
+
+use crate::{
+    completion::Completer,
+    error::Error,
+    highlight::Highlighter,
+    history::History,
+    repline::{EditorState, Repline},
+};
+use std::{
+    io::{Read, Stdin},
+    path::Path,
+};
+
+/// The result of a line editing operation
+pub type Result<T> = std::result::Result<T, ReadlineError>;
+
+/// How reading a line can fail, like rustyline's errors
+#[derive(Debug)]
+pub enum ReadlineError {
+    /// The user pressed Ctrl+C
+    Interrupted,
+    /// The user pressed Ctrl+D, or the input ended
+    Eof,
+    /// Reading or drawing failed
+    Io(std::io::Error),
+}
+
+impl std::error::Error for ReadlineError {}
+
+impl std::fmt::Display for ReadlineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Interrupted => write!(f, "Interrupted"),
+            Self::Eof => write!(f, "EOF"),
+            Self::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for ReadlineError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<Error> for ReadlineError {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::CtrlC(_) => Self::Interrupted,
+            Error::CtrlD(_) | Error::EndOfInput => Self::Eof,
+            Error::IoFailure(e) | Error::Terminal(e) => Self::Io(e),
+            Error::Interrupted => Self::Io(std::io::ErrorKind::Interrupted.into()),
+            e @ (Error::BadUnicode(_)
+            | Error::BadUtf8(_)
+            | Error::Action(..)
+            | Error::TimedOut(_)) => Self::Io(std::io::Error::other(e.to_string())),
+        }
+    }
+}
+
+/// A line editor reading from stdin, or another [Reader](Read), shaped like rustyline's
+/// `Editor`
+#[derive(Debug)]
+pub struct Editor<R: Read = Stdin> {
+    rl: Repline<'static, R>,
+}
+
+/// Like rustyline's `DefaultEditor`
+pub type DefaultEditor = Editor;
+
+impl Editor {
+    /// Constructs an [Editor] with an empty history
+    pub fn new() -> Result<Self> {
+        Ok(Self { rl: Repline::new("", "", "") })
+    }
+}
+
+impl<R: Read> Editor<R> {
+    /// Constructs an [Editor] with an empty history, which reads keys from `input`
+    pub fn with_input(input: R) -> Self {
+        Self { rl: Repline::with_input(input, "", "", "") }
+    }
+
+    /// Reads a line, shown after `prompt`, returning it without its line break.
+    ///
+    /// Lines aren't added to the history until passed to
+    /// [add_history_entry](Self::add_history_entry).
+    pub fn readline(&mut self, prompt: &str) -> Result<String> {
+        self.rl.set_prompts(prompt.to_owned(), prompt.to_owned());
+        let line = self.rl.read();
+        self.rl.deny();
+        let mut line = line?;
+        if line.ends_with('\n') {
+            line.pop();
+        }
+        Ok(line)
+    }
+
+    /// Reads a line like [readline](Self::readline), starting with `left` before the
+    /// cursor, and `right` after it
+    pub fn readline_with_initial(
+        &mut self,
+        prompt: &str,
+        (left, right): (&str, &str),
+    ) -> Result<String> {
+        let cursor = left.chars().count();
+        let text = format!("{left}{right}");
+        self.rl
+            .restore_state(EditorState { text, cursor, ..Default::default() });
+        self.readline(prompt)
+    }
+
+    /// Adds `line` to the history, returning true
+    pub fn add_history_entry<S: AsRef<str> + Into<String>>(&mut self, line: S) -> Result<bool> {
+        self.rl.add_history(line);
+        Ok(true)
+    }
+
+    /// Reads the history file at `path`, merging its entries into the history
+    pub fn load_history<P: AsRef<Path> + ?Sized>(&mut self, path: &P) -> Result<()> {
+        Ok(self.rl.load_history(path)?)
+    }
+
+    /// Writes the history to the history file at `path`, replacing its contents
+    pub fn save_history<P: AsRef<Path> + ?Sized>(&mut self, path: &P) -> Result<()> {
+        Ok(self.rl.save_history(path)?)
+    }
+
+    /// Appends each line added to the history from now on to the history file at `path`
+    pub fn append_history<P: AsRef<Path> + ?Sized>(&mut self, path: &P) -> Result<()> {
+        Ok(self.rl.append_history(path)?)
+    }
+
+    /// Returns the history
+    pub fn history(&self) -> &History {
+        self.rl.history()
+    }
+
+    /// Sets the [Completer], which completes the word under the cursor when `Tab` is pressed
+    pub fn set_completer(&mut self, completer: impl Completer + 'static) {
+        self.rl.set_completer(completer)
+    }
+
+    /// Sets the [Highlighter], which colors the line as the user types
+    pub fn set_highlighter(&mut self, highlighter: impl Highlighter + 'static) {
+        self.rl.set_highlighter(highlighter)
+    }
+
+    /// Returns the underlying [Repline], for everything else it can do
+    pub fn repline(&mut self) -> &mut Repline<'static, R> {
+        &mut self.rl
+    }
+}
+
+#[cfg(all(test, feature = "screen"))]
+mod tests {
+    use super::{Editor, ReadlineError};
+    use crate::testing::{Replay, Terminal, assert_screen_contains};
+
+    #[test]
+    fn readline_and_history() {
+        let terminal = Terminal::new(20, 8);
+        let keys = Replay::new("one\r\x1b[A!\r\x03\x04");
+        let mut rl = Editor::with_input(keys);
+        rl.repline().set_backend(terminal.clone());
+
+        let line = rl.readline(">> ").unwrap();
+        assert_eq!(line, "one");
+        assert!(rl.add_history_entry(line).unwrap());
+        // the entry is recalled with Up, and typed after
+        assert_eq!(rl.readline(">> ").unwrap(), "one!");
+        assert_eq!(rl.history().len(), 1);
+        assert_screen_contains(&terminal.screen(), ">> one\n>> one!");
+
+        assert!(matches!(
+            rl.readline(">> "),
+            Err(ReadlineError::Interrupted)
+        ));
+        assert!(matches!(rl.readline(">> "), Err(ReadlineError::Eof)));
+    }
+
+    #[test]
+    fn readline_with_initial() {
+        let terminal = Terminal::new(20, 4);
+        let mut rl = Editor::with_input(Replay::new("X\r"));
+        rl.repline().set_backend(terminal.clone());
+        let line = rl.readline_with_initial("$ ", ("ab", "cd")).unwrap();
+        assert_eq!(line, "abXcd");
+    }
+}
@@ -0,0 +1,468 @@
+//! Tab completion: a [Completer] suggests replacements for the word under the cursor.
+
+use crate::history::History;
+use std::{ops::Range, time::SystemTime};
+
+/// Suggests replacements for the word under the cursor when `Tab` is pressed.
+///
+/// Implemented for all `FnMut(&str, usize, Range<usize>) -> Vec<String>`.
+pub trait Completer {
+    /// Returns candidates to replace `word`, the byte range of the word under the cursor.
+    ///
+    /// `cursor` is the byte index of the cursor in `line`, and lies within `word`.
+    fn complete(&mut self, line: &str, cursor: usize, word: Range<usize>) -> Vec<String>;
+
+    /// Like [complete](Completer::complete), for completers which draw on the [History].
+    ///
+    /// This is what's called when `Tab` is pressed, and it calls `complete` by default.
+    fn complete_with_history(
+        &mut self,
+        line: &str,
+        cursor: usize,
+        word: Range<usize>,
+        history: &History,
+    ) -> Vec<String> {
+        let _ = history;
+        self.complete(line, cursor, word)
+    }
+
+    /// How candidates are inserted into the line
+    fn mode(&self) -> CompletionMode {
+        CompletionMode::Complete
+    }
+
+    /// Whether candidates were matched ignoring case, and if so, whose casing is inserted
+    fn case(&self) -> Case {
+        Case::Sensitive
+    }
+
+    /// Returns the byte ranges of `line` which are secret, like the argument after
+    /// `--password`.
+    ///
+    /// Secrets are drawn masked, and lines containing them aren't recorded in the history.
+    fn secrets(&mut self, line: &str) -> Vec<Range<usize>> {
+        let _ = line;
+        vec![]
+    }
+}
+
+impl<F: FnMut(&str, usize, Range<usize>) -> Vec<String>> Completer for F {
+    fn complete(&mut self, line: &str, cursor: usize, word: Range<usize>) -> Vec<String> {
+        self(line, cursor, word)
+    }
+}
+
+/// Completes words from a fixed list, like a language's keywords or a shell's commands
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Words {
+    pub words: Vec<String>,
+    /// How the typed text is matched against the words
+    pub case: Case,
+}
+
+impl Words {
+    pub fn new<S: Into<String>>(words: impl IntoIterator<Item = S>) -> Self {
+        Self { words: words.into_iter().map(Into::into).collect(), case: Case::Sensitive }
+    }
+
+    /// Matches the typed text against the words according to `case`
+    pub fn with_case(self, case: Case) -> Self {
+        Self { case, ..self }
+    }
+}
+
+impl Completer for Words {
+    fn complete(&mut self, line: &str, cursor: usize, word: Range<usize>) -> Vec<String> {
+        let typed = &line[word.start..cursor];
+        self.words
+            .iter()
+            .filter(|w| self.case.matches(w, typed))
+            .cloned()
+            .collect()
+    }
+
+    fn case(&self) -> Case {
+        self.case
+    }
+}
+
+/// Completes the arguments of a command with those it was given before: after `connect `,
+/// suggests the hosts from earlier `connect` lines in the [History], most
+/// [frecent](crate::history::Entry::frecency) first
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct HistoryArgs;
+
+impl Completer for HistoryArgs {
+    fn complete(&mut self, _: &str, _: usize, _: Range<usize>) -> Vec<String> {
+        vec![]
+    }
+
+    fn complete_with_history(
+        &mut self,
+        line: &str,
+        cursor: usize,
+        word: Range<usize>,
+        history: &History,
+    ) -> Vec<String> {
+        let Some(command) = line[..word.start].split_whitespace().next() else {
+            return vec![];
+        };
+        let typed = &line[word.start..cursor];
+        let now = SystemTime::now();
+        let mut scores: Vec<(&str, f64)> = vec![];
+        for entry in history.iter() {
+            let mut words = entry.text.split_whitespace();
+            if words.next() != Some(command) {
+                continue;
+            }
+            for arg in words.filter(|arg| arg.starts_with(typed)) {
+                match scores.iter_mut().find(|(seen, _)| *seen == arg) {
+                    Some((_, score)) => *score += entry.frecency(now),
+                    None => scores.push((arg, entry.frecency(now))),
+                }
+            }
+        }
+        scores.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        scores.into_iter().map(|(arg, _)| arg.to_string()).collect()
+    }
+}
+
+/// Completes words from those already in the line and in the [History], like vim's `Ctrl+N`,
+/// for useful completion before a language-aware [Completer] is written. Words are runs of
+/// letters, digits, and `_`, and those nearest the cursor come first, then those of the
+/// newest entries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BufferWords;
+
+impl Completer for BufferWords {
+    fn complete(&mut self, line: &str, cursor: usize, word: Range<usize>) -> Vec<String> {
+        self.complete_with_history(line, cursor, word, &History::new(0))
+    }
+
+    fn complete_with_history(
+        &mut self,
+        line: &str,
+        cursor: usize,
+        word: Range<usize>,
+        history: &History,
+    ) -> Vec<String> {
+        let typed = &line[word.start..cursor];
+        if typed.is_empty() {
+            return vec![];
+        }
+        let before = identifiers(&line[..word.start]).rev();
+        let after = identifiers(&line[word.end..]);
+        let entries = history
+            .iter()
+            .rev()
+            .flat_map(|entry| identifiers(&entry.text).rev());
+        let mut found: Vec<String> = vec![];
+        for ident in before.chain(after).chain(entries) {
+            let extends = ident.len() > typed.len() && ident.starts_with(typed);
+            if extends && !found.iter().any(|f| f == ident) {
+                found.push(ident.to_string());
+            }
+        }
+        found
+    }
+}
+
+/// Returns the runs of letters, digits, and `_` in `text`
+fn identifiers(text: &str) -> impl DoubleEndedIterator<Item = &str> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|ident| !ident.is_empty())
+}
+
+/// Wraps a [Completer], inserting its candidates according to the given [CompletionMode]
+#[derive(Clone, Copy, Debug)]
+pub struct WithMode<C: Completer>(pub C, pub CompletionMode);
+
+impl<C: Completer> Completer for WithMode<C> {
+    fn complete(&mut self, line: &str, cursor: usize, word: Range<usize>) -> Vec<String> {
+        self.0.complete(line, cursor, word)
+    }
+
+    fn complete_with_history(
+        &mut self,
+        line: &str,
+        cursor: usize,
+        word: Range<usize>,
+        history: &History,
+    ) -> Vec<String> {
+        self.0.complete_with_history(line, cursor, word, history)
+    }
+
+    fn mode(&self) -> CompletionMode {
+        self.1
+    }
+
+    fn case(&self) -> Case {
+        self.0.case()
+    }
+
+    fn secrets(&mut self, line: &str) -> Vec<Range<usize>> {
+        self.0.secrets(line)
+    }
+}
+
+/// Wraps a [Completer] which matches candidates according to the given [Case]
+#[derive(Clone, Copy, Debug)]
+pub struct WithCase<C: Completer>(pub C, pub Case);
+
+impl<C: Completer> Completer for WithCase<C> {
+    fn complete(&mut self, line: &str, cursor: usize, word: Range<usize>) -> Vec<String> {
+        self.0.complete(line, cursor, word)
+    }
+
+    fn complete_with_history(
+        &mut self,
+        line: &str,
+        cursor: usize,
+        word: Range<usize>,
+        history: &History,
+    ) -> Vec<String> {
+        self.0.complete_with_history(line, cursor, word, history)
+    }
+
+    fn mode(&self) -> CompletionMode {
+        self.0.mode()
+    }
+
+    fn case(&self) -> Case {
+        self.1
+    }
+
+    fn secrets(&mut self, line: &str) -> Vec<Range<usize>> {
+        self.0.secrets(line)
+    }
+}
+
+/// How a [Completer]'s candidates are inserted into the line
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CompletionMode {
+    /// Inserts the longest prefix shared by the candidates, and lists them if there are
+    /// several, like readline's `complete`
+    #[default]
+    Complete,
+    /// Replaces the word with each candidate in turn as `Tab` is pressed again, like
+    /// readline's `menu-complete`.
+    ///
+    /// The candidate is underlined until another key settles on it, or `Esc Esc` (or
+    /// `Ctrl+G`) puts back the word as it was typed.
+    Menu,
+    /// Like [Complete](CompletionMode::Complete), but stops after the next `-`, `_`, or `/`,
+    /// so paths and compound words are completed a segment at a time, like fish
+    Partial,
+}
+
+/// How a [Completer] matches the typed text against its candidates, and what's inserted
+/// when they differ in case
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Case {
+    /// Candidates must start with the typed text exactly
+    #[default]
+    Sensitive,
+    /// Candidates start with the typed text ignoring case, which is kept as it was typed,
+    /// like readline's `completion-ignore-case`
+    Insensitive,
+    /// Candidates start with the typed text ignoring case, which is replaced with the
+    /// candidate's casing, so `sel` completes to `SELECT`
+    Canonical,
+}
+
+impl Case {
+    /// Returns true if `candidate` starts with `typed`, ignoring case unless
+    /// [Sensitive](Case::Sensitive)
+    pub fn matches(self, candidate: &str, typed: &str) -> bool {
+        if self == Case::Sensitive {
+            return candidate.starts_with(typed);
+        }
+        let mut candidate = candidate.chars().flat_map(char::to_lowercase);
+        typed
+            .chars()
+            .flat_map(char::to_lowercase)
+            .all(|c| candidate.next() == Some(c))
+    }
+
+    /// Returns true if `a` and `b` are the same character, ignoring case unless
+    /// [Sensitive](Case::Sensitive)
+    fn same(self, a: char, b: char) -> bool {
+        match self {
+            Case::Sensitive => a == b,
+            _ => a == b || a.to_lowercase().eq(b.to_lowercase()),
+        }
+    }
+
+    /// Returns `candidate` with its start replaced by `typed`, if the typed text is kept
+    fn keep_typed(self, typed: &str, candidate: String) -> String {
+        match self {
+            Case::Insensitive => {
+                let rest = candidate.chars().skip(typed.chars().count());
+                typed.chars().chain(rest).collect()
+            }
+            _ => candidate,
+        }
+    }
+}
+
+/// Runs a [Completer] on the word under the cursor
+pub(crate) struct Completion<'a> {
+    completer: Box<dyn Completer + 'a>,
+    pub shown: Vec<String>, // candidates listed on the status line
+    menu: Option<Menu>,     // candidates being cycled through
+}
+
+/// Candidates being cycled through in place of the word as it was typed
+struct Menu {
+    candidates: Vec<String>,
+    index: usize,       // index of the candidate in the buffer
+    span: Range<usize>, // character range of the candidate in the buffer
+    typed: String,      // the word the candidates replace
+}
+
+impl<'a> Completion<'a> {
+    pub fn new(completer: impl Completer + 'a) -> Self {
+        Self { completer: Box::new(completer), shown: vec![], menu: None }
+    }
+
+    /// Returns true if repeated `Tab` presses are cycling through the candidates
+    pub fn cycling(&self) -> bool {
+        self.menu.is_some()
+    }
+
+    /// Stops cycling through candidates, and stops listing them
+    pub fn end(&mut self) {
+        self.shown.clear();
+        self.menu = None;
+    }
+
+    /// Returns the character range of the candidate being previewed while cycling
+    pub fn preview(&self) -> Option<Range<usize>> {
+        self.menu.as_ref().map(|menu| menu.span.clone())
+    }
+
+    /// Stops cycling through candidates, returning the edit which puts back the word as
+    /// it was typed, as a character range and its replacement
+    pub fn revert(&mut self) -> Option<(Range<usize>, String)> {
+        let Menu { span, typed, .. } = self.menu.take()?;
+        self.end();
+        Some((span, typed))
+    }
+
+    /// Returns the character ranges of `buf` which are secret
+    pub fn secrets(&mut self, buf: &[char]) -> Vec<Range<usize>> {
+        let line: String = buf.iter().collect();
+        let chars = |at: usize| line.get(..at).map(|s| s.chars().count());
+        let secrets = self.completer.secrets(&line).into_iter();
+        secrets
+            .filter_map(|span| Some(chars(span.start)?..chars(span.end)?))
+            .collect()
+    }
+
+    /// Returns the edit which completes the word under character `cursor` in `buf`, as a
+    /// character range and its replacement
+    pub fn complete(
+        &mut self,
+        buf: &[char],
+        cursor: usize,
+        history: &History,
+    ) -> Option<(Range<usize>, String)> {
+        if let Some(Menu { candidates, index, span, .. }) = &mut self.menu {
+            *index = (*index + 1) % candidates.len();
+            let replacement = candidates[*index].clone();
+            let edit = (span.clone(), replacement);
+            *span = span.start..span.start + edit.1.chars().count();
+            return Some(edit);
+        }
+
+        let word = word_at(buf, cursor);
+        let line: String = buf.iter().collect();
+        let bytes = |at: usize| buf[..at].iter().map(|c| c.len_utf8()).sum::<usize>();
+        let span = bytes(word.start)..bytes(word.end);
+        let candidates = self
+            .completer
+            .complete_with_history(&line, bytes(cursor), span, history);
+        if candidates.is_empty() {
+            return None;
+        }
+        if candidates.len() > 1 {
+            self.shown = candidates.clone();
+        }
+        let case = self.completer.case();
+        let before: String = buf[word.start..cursor].iter().collect();
+        match self.completer.mode() {
+            CompletionMode::Menu => {
+                let candidates: Vec<_> = candidates
+                    .into_iter()
+                    .map(|c| case.keep_typed(&before, c))
+                    .collect();
+                let first = candidates[0].clone();
+                if candidates.len() > 1 {
+                    let span = word.start..word.start + first.chars().count();
+                    let typed = buf[word.clone()].iter().collect();
+                    self.menu = Some(Menu { candidates, index: 0, span, typed });
+                }
+                Some((word, first))
+            }
+            mode => {
+                let mut prefix = common_prefix(&candidates, case);
+                if mode == CompletionMode::Partial {
+                    prefix = segment(prefix, word.len());
+                }
+                let prefix = case.keep_typed(&before, prefix);
+                let typed: String = buf[word.clone()].iter().collect();
+                let len = prefix.chars().count();
+                // the typed text may be complete, but cased differently
+                let recased = case == Case::Canonical && len == word.len() && prefix != typed;
+                (len > word.len() || recased).then_some((word, prefix))
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Completion<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Completion")
+            .field("shown", &self.shown)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns the character range of the whitespace-delimited word containing `cursor`
+pub(crate) fn word_at(buf: &[char], cursor: usize) -> Range<usize> {
+    let start = buf[..cursor]
+        .iter()
+        .rposition(|c| c.is_whitespace())
+        .map_or(0, |i| i + 1);
+    let end = buf[cursor..].iter().position(|c| c.is_whitespace());
+    start..end.map_or(buf.len(), |i| cursor + i)
+}
+
+/// Returns the longest prefix shared by all `candidates`, in the casing of the first, when
+/// `case` ignores it
+fn common_prefix(candidates: &[String], case: Case) -> String {
+    let Some((first, rest)) = candidates.split_first() else {
+        return String::new();
+    };
+    let mut prefix = first.as_str();
+    for candidate in rest {
+        let shared = prefix
+            .chars()
+            .zip(candidate.chars())
+            .take_while(|&(a, b)| case.same(a, b));
+        prefix = &prefix[..shared.map(|(c, _)| c.len_utf8()).sum()];
+    }
+    prefix.to_string()
+}
+
+/// Truncates `text` after the first `-`, `_`, or `/` following its first `typed` characters
+fn segment(mut text: String, typed: usize) -> String {
+    let boundary = text
+        .char_indices()
+        .skip(typed)
+        .find(|(_, c)| matches!(c, '-' | '_' | '/'));
+    if let Some((at, c)) = boundary {
+        text.truncate(at + c.len_utf8());
+    }
+    text
+}
@@ -0,0 +1,206 @@
+//! Lint-as-you-type: a [Diagnostics] provider reports problems in the buffer, which are
+//! underlined in place and summarized on the status line.
+
+use crate::strings::Strings;
+use crossterm::style::{Attribute, Color, ContentStyle};
+use std::{
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+/// How severe a [Diagnostic] is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Hint,
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// The color used to underline diagnostics of this severity
+    pub fn color(self) -> Color {
+        match self {
+            Severity::Hint => Color::DarkGrey,
+            Severity::Info => Color::Cyan,
+            Severity::Warning => Color::Yellow,
+            Severity::Error => Color::Red,
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(Strings::ENGLISH.severity(*self))
+    }
+}
+
+/// A problem found in the buffer by a [Diagnostics] provider
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    /// The byte range of the buffer this diagnostic applies to
+    pub span: Range<usize>,
+    pub severity: Severity,
+    pub message: String,
+    /// A textual edit which resolves the problem
+    pub fix: Option<Fix>,
+}
+
+/// A quick fix for a [Diagnostic], which replaces a byte range of the buffer
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Fix {
+    /// The byte range of the buffer to replace
+    pub span: Range<usize>,
+    pub replacement: String,
+}
+
+impl Diagnostic {
+    /// Constructs a new [Diagnostic] over the byte range `span`
+    pub fn new(span: Range<usize>, severity: Severity, message: impl Into<String>) -> Self {
+        Self { span, severity, message: message.into(), fix: None }
+    }
+
+    /// Attaches a quick fix, which replaces the byte range `span` with `replacement`
+    pub fn with_fix(self, span: Range<usize>, replacement: impl Into<String>) -> Self {
+        Self { fix: Some(Fix { span, replacement: replacement.into() }), ..self }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { severity, message, .. } = self;
+        write!(f, "{severity}: {message}")
+    }
+}
+
+/// Checks the buffer as the user types, reporting [Diagnostic]s to be drawn in it.
+///
+/// Implemented for all `FnMut(&str) -> Vec<Diagnostic>`.
+pub trait Diagnostics {
+    /// Reports the problems found in `buffer`
+    fn diagnose(&mut self, buffer: &str) -> Vec<Diagnostic>;
+}
+
+impl<F: FnMut(&str) -> Vec<Diagnostic>> Diagnostics for F {
+    fn diagnose(&mut self, buffer: &str) -> Vec<Diagnostic> {
+        self(buffer)
+    }
+}
+
+/// Runs a [Diagnostics] provider whenever the buffer changes, at most once per `delay`
+pub(crate) struct Linter<'a> {
+    provider: Box<dyn Diagnostics + 'a>,
+    pub delay: Duration,
+    last: Option<Instant>, // when the provider last ran
+    text: String,          // the buffer the provider last saw
+    pub found: Vec<Diagnostic>,
+    pub index: usize, // the diagnostic shown on the status line
+}
+
+impl<'a> Linter<'a> {
+    pub fn new(provider: impl Diagnostics + 'a) -> Self {
+        Self {
+            provider: Box::new(provider),
+            delay: Duration::ZERO,
+            last: None,
+            text: String::new(),
+            found: vec![],
+            index: 0,
+        }
+    }
+
+    /// Runs the provider on `text`, if it changed and the delay has elapsed.
+    ///
+    /// Returns true if the diagnostics were updated.
+    pub fn lint(&mut self, text: String) -> bool {
+        if text == self.text || self.last.is_some_and(|last| last.elapsed() < self.delay) {
+            return false;
+        }
+        self.found = self.provider.diagnose(&text);
+        self.found.sort_by_key(|d| d.span.start);
+        self.text = text;
+        self.last = Some(Instant::now());
+        self.index = 0;
+        true
+    }
+
+    /// Takes `found` as the diagnostics of `text`, as if the provider had reported them, until
+    /// the provider next runs
+    pub fn report(&mut self, text: String, mut found: Vec<Diagnostic>) {
+        found.sort_by_key(|d| d.span.start);
+        (self.found, self.text, self.index) = (found, text, 0);
+        self.last = None; // so that the next edit is checked, however soon
+    }
+
+    /// Converts a byte range of the linted text into a character range
+    pub fn chars(&self, span: &Range<usize>) -> Option<Range<usize>> {
+        let chars = |at: usize| self.text.get(..at).map(|s| s.chars().count());
+        Some(chars(span.start)?..chars(span.end)?)
+    }
+
+    /// Converts the found diagnostics into styled character ranges
+    pub fn decorations(&self) -> Vec<(Range<usize>, ContentStyle)> {
+        self.found
+            .iter()
+            .filter_map(|d| {
+                let mut style = ContentStyle::new();
+                style.foreground_color = Some(d.severity.color());
+                style.attributes.set(Attribute::Underlined);
+                Some((self.chars(&d.span)?, style))
+            })
+            .collect()
+    }
+
+    /// Selects the first diagnostic starting after character `cursor`, wrapping around,
+    /// and returns its character range
+    pub fn select_next(&mut self, cursor: usize) -> Option<Range<usize>> {
+        let spans: Vec<_> = self.found.iter().map(|d| self.chars(&d.span)).collect();
+        let index = (spans.iter())
+            .position(|span| span.as_ref().is_some_and(|span| span.start > cursor))
+            .or_else(|| spans.iter().position(Option::is_some))?;
+        self.select(index)
+    }
+
+    /// Selects the last diagnostic starting before character `cursor`, wrapping around,
+    /// and returns its character range
+    pub fn select_prev(&mut self, cursor: usize) -> Option<Range<usize>> {
+        let spans: Vec<_> = self.found.iter().map(|d| self.chars(&d.span)).collect();
+        let index = (spans.iter())
+            .rposition(|span| span.as_ref().is_some_and(|span| span.start < cursor))
+            .or_else(|| spans.iter().rposition(Option::is_some))?;
+        self.select(index)
+    }
+
+    fn select(&mut self, index: usize) -> Option<Range<usize>> {
+        self.index = index;
+        self.chars(&self.found.get(index)?.span)
+    }
+
+    /// Returns the quick fix of the selected diagnostic, as a character range and its
+    /// replacement
+    pub fn fix(&self) -> Option<(Range<usize>, &str)> {
+        let Fix { span, replacement } = self.found.get(self.index)?.fix.as_ref()?;
+        Some((self.chars(span)?, replacement))
+    }
+
+    /// A one-line summary of the selected diagnostic
+    pub fn summary(&self, strings: &Strings) -> Option<String> {
+        let Self { found, index, .. } = self;
+        let Diagnostic { severity, message, .. } = found.get(*index)?;
+        let severity = strings.severity(*severity);
+        Some(match found.len() {
+            1 => format!("{severity}: {message}"),
+            n => format!("{severity}: {message} ({}/{n})", index + 1),
+        })
+    }
+}
+
+impl std::fmt::Debug for Linter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Linter")
+            .field("delay", &self.delay)
+            .field("found", &self.found)
+            .field("index", &self.index)
+            .finish_non_exhaustive()
+    }
+}
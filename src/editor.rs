@@ -1,39 +1,404 @@
 //! The [Editor] is a multi-line buffer of [`char`]s which operates on an ANSI-compatible terminal.
+//!
+//! Text is stored, edited, and drawn in logical order. Right-to-left scripts (like Arabic and
+//! Hebrew) are shown however the terminal chooses to show them: most terminals draw them
+//! left-to-right, where the cursor lines up with the text. Terminals which reorder RTL runs
+//! for display will show the cursor at its logical, rather than visual, column.
+//!
+//! A [Repline](crate::Repline) reads keys and runs commands on its own Editor, but one can
+//! be used directly, to build a key loop of one's own on the same drawing:
+//!
+//! ```no_run
+//! use repline::{editor::Editor, raw::raw};
+//! use std::io::{Read, Write, stdin, stdout};
+//!
+//! let _raw = raw()?;
+//! let (mut ed, mut out) = (Editor::new("", "> ", ". "), stdout());
+//! ed.draw_head(&mut out)?;
+//! out.flush()?;
+//! for byte in stdin().bytes() {
+//!     match byte? {
+//!         b'\r' => break,
+//!         b'\x7f' => drop(ed.pop(&mut out)?),
+//!         byte => ed.push(byte as char, &mut out)?,
+//!     }
+//!     out.flush()?;
+//! }
+//! print!("\r\n{ed}\r\n");
+//! # Ok::<(), repline::Error>(())
+//! ```
 
 use crossterm::{cursor::*, queue, style::*, terminal::*};
-use std::{collections::VecDeque, fmt::Display, io::Write};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::VecDeque,
+    fmt::Display,
+    io::Write,
+    ops::{Range, RangeInclusive},
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-use super::error::ReplResult;
+use super::{
+    error::ReplResult,
+    multiplexer::{self, Multiplexer},
+    theme::Theme,
+};
+
+/// Drawn in place of each character of a secret
+pub const MASK: char = '•';
 
 fn is_newline(c: &char) -> bool {
     *c == '\n'
 }
 
-fn write_chars<'a, W: Write>(
-    c: impl IntoIterator<Item = &'a char>,
+/// Returns the number of columns the terminal advances by when drawing `text`.
+///
+/// Wide characters (like CJK ideographs) and emoji take up two columns, even when built
+/// from several characters. Combining marks (including Hebrew points and Arabic harakat)
+/// and directional formatting characters take up no columns of their own.
+fn width(text: &str) -> u16 {
+    text.width() as u16
+}
+
+/// Returns the number of columns the terminal advances by when drawing `c` on its own
+fn char_width(c: &char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+/// Returns the number of characters in the last grapheme cluster of `chars`, which are
+/// given in reverse
+fn last_grapheme<'c>(chars: impl Iterator<Item = &'c char>) -> usize {
+    // no cluster a terminal can draw is anywhere near this long
+    let text: String = chars
+        .take(32)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+    text.graphemes(true)
+        .next_back()
+        .map_or(0, |g| g.chars().count())
+}
+
+/// Returns the number of characters in the first grapheme cluster of `chars`
+fn first_grapheme<'c>(chars: impl Iterator<Item = &'c char>) -> usize {
+    let text: String = chars.take(32).collect();
+    text.graphemes(true).next().map_or(0, |g| g.chars().count())
+}
+
+/// Returns the number of characters in the word at the start of `chars`, and the whitespace
+/// before it, or 1 if `chars` starts with a line break
+fn word_len(chars: impl Iterator<Item = char>, words: WordChars<'_>) -> usize {
+    let mut chars = chars.peekable();
+    if chars.peek() == Some(&'\n') {
+        return 1;
+    }
+    let mut len = 0;
+    while chars.next_if(|&c| c.is_whitespace() && c != '\n').is_some() {
+        len += 1;
+    }
+    let Some(word) = chars.peek().map(|&c| words.contains(c)) else {
+        return len;
+    };
+    len + chars
+        .take_while(|&c| !c.is_whitespace() && words.contains(c) == word)
+        .count()
+}
+
+/// What counts as part of a word when deleting by words. A run of other characters which
+/// aren't whitespace, like `::` or `->`, is deleted as a word of its own.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum WordChars<'a> {
+    /// Anything but whitespace, like readline's `Ctrl+W`
+    #[default]
+    NonSpace,
+    /// Letters and digits
+    Alphanumeric,
+    /// Letters and digits, and these characters, like `_` for identifiers, or `_-./` for
+    /// paths too
+    With(&'a str),
+}
+
+impl WordChars<'_> {
+    /// Returns true if `c` is part of a word
+    pub fn contains(self, c: char) -> bool {
+        match self {
+            Self::NonSpace => !c.is_whitespace(),
+            Self::Alphanumeric => c.is_alphanumeric(),
+            Self::With(chars) => c.is_alphanumeric() || chars.contains(c),
+        }
+    }
+}
+
+/// Moves the cursor along a line from column `from` to column `to`, counted as if the line
+/// didn't wrap, where it wraps every `wrap` columns
+fn move_along<W: Write>(from: usize, to: usize, wrap: usize, w: &mut W) -> std::io::Result<()> {
+    let (from_row, to_row) = (from / wrap, to / wrap);
+    let column = MoveToColumn((to % wrap) as u16);
+    match from_row.cmp(&to_row) {
+        Ordering::Greater => queue!(w, MoveUp((from_row - to_row) as u16), column),
+        Ordering::Less => queue!(w, MoveDown((to_row - from_row) as u16), column),
+        Ordering::Equal if from > to => queue!(w, MoveLeft((from - to) as u16)),
+        Ordering::Equal if from < to => queue!(w, MoveRight((to - from) as u16)),
+        Ordering::Equal => Ok(()),
+    }
+}
+
+/// Returns the (line, column) of the character at `index`
+fn position(buf: &[char], index: usize) -> (usize, usize) {
+    let head = &buf[..index.min(buf.len())];
+    match head.iter().rposition(is_newline) {
+        Some(nl) => (
+            head.iter().filter(|c| is_newline(c)).count(),
+            index - nl - 1,
+        ),
+        None => (0, index),
+    }
+}
+
+/// Returns the index of the character at (`line`, `col`), clamped to the end of the line
+fn index_of(buf: &[char], line: usize, col: usize) -> usize {
+    let mut start = 0;
+    for (n, text) in buf.split(is_newline).enumerate() {
+        if n == line {
+            return start + col.min(text.len());
+        }
+        start += text.len() + 1;
+    }
+    buf.len()
+}
+
+/// Guesses from `TERM` whether the terminal can save and restore the cursor position.
+///
+/// Terminal multiplexers and the Linux console are known to lose the saved position when
+/// the screen scrolls, or when they redraw for their own purposes.
+fn supports_save_restore() -> bool {
+    if Multiplexer::detect().is_some() {
+        return false;
+    }
+    match std::env::var("TERM") {
+        Ok(term) => !["dumb", "linux"].iter().any(|t| term.starts_with(t)),
+        Err(_) => !cfg!(unix),
+    }
+}
+
+/// Returns the index of the first character whose style differs between the `old` and `new`
+/// styled ranges, if any does
+pub(crate) fn first_restyled<T: PartialEq>(
+    old: &[(Range<usize>, T)],
+    new: &[(Range<usize>, T)],
+) -> Option<usize> {
+    let changed = |a: &[(Range<usize>, T)], b: &[(Range<usize>, T)]| {
+        a.iter()
+            .filter(|span| !b.contains(span))
+            .map(|(range, _)| range.start)
+            .min()
+    };
+    changed(old, new).into_iter().chain(changed(new, old)).min()
+}
+
+/// Approximates an RGB color with the nearest color of the xterm 256-color palette
+fn downsample(color: Color) -> Color {
+    let Color::Rgb { r, g, b } = color else {
+        return color;
+    };
+    let level = |c: u8| (c as u16 * 5 + 127) / 255;
+    let value = match (r, g, b) {
+        // the grayscale ramp runs from 8 to 238 in steps of 10
+        (r, g, b) if r == g && g == b && (8..=238).contains(&r) => 232 + (r as u16 - 3) / 10,
+        _ => 16 + 36 * level(r) + 6 * level(g) + level(b),
+    };
+    Color::AnsiValue(value as u8)
+}
+
+/// Reads the style set by SGR escape sequences, like a prompt color such as `\x1b[1;33m`.
+///
+/// Sequences other than SGR, and parameters it doesn't know, are skipped.
+fn sgr_style(escapes: &str) -> ContentStyle {
+    let mut style = ContentStyle::new();
+    let sequences = escapes
+        .split("\x1b[")
+        .skip(1)
+        .filter_map(|seq| seq.split_once('m'));
+    for (params, _) in sequences {
+        let mut params = params.split(';').map(|p| p.parse::<u8>().unwrap_or(0));
+        while let Some(param) = params.next() {
+            let mut extended = || match (params.next(), params.next()) {
+                (Some(5), Some(n)) => Some(Color::AnsiValue(n)),
+                (Some(2), Some(r)) => Some(Color::Rgb { r, g: params.next()?, b: params.next()? }),
+                _ => None,
+            };
+            match param {
+                0 => style = ContentStyle::new(),
+                1 => style.attributes.set(Attribute::Bold),
+                2 => style.attributes.set(Attribute::Dim),
+                3 => style.attributes.set(Attribute::Italic),
+                4 => style.attributes.set(Attribute::Underlined),
+                7 => style.attributes.set(Attribute::Reverse),
+                30..=37 => style.foreground_color = Some(Color::AnsiValue(param - 30)),
+                90..=97 => style.foreground_color = Some(Color::AnsiValue(param - 90 + 8)),
+                40..=47 => style.background_color = Some(Color::AnsiValue(param - 40)),
+                100..=107 => style.background_color = Some(Color::AnsiValue(param - 100 + 8)),
+                38 => style.foreground_color = extended(),
+                48 => style.background_color = extended(),
+                39 => style.foreground_color = None,
+                49 => style.background_color = None,
+                _ => {}
+            }
+        }
+    }
+    style
+}
+
+fn write_chars<W: Write>(c: impl IntoIterator<Item = char>, w: &mut W) -> std::io::Result<()> {
+    queue!(w, Print(c.into_iter().collect::<String>()))
+}
+
+/// Prints a run of characters in the same style, if there are any, and empties it
+fn print_run<W: Write>(
+    (text, style): &mut (String, ContentStyle),
     w: &mut W,
 ) -> std::io::Result<()> {
-    for c in c {
-        queue!(w, Print(c))?;
+    if text.is_empty() {
+        return Ok(());
     }
+    match *style == ContentStyle::default() {
+        true => queue!(w, Print(&text)),
+        false => queue!(w, PrintStyledContent(style.apply(&text))),
+    }?;
+    text.clear();
     Ok(())
 }
 
+/// The contents of an [Editor], saved so that an in-progress entry can be resumed later,
+/// even in another process
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EditorState {
+    pub text: String,
+    /// The index of the cursor, in characters
+    pub cursor: usize,
+    /// The index of the selection's anchor, in characters
+    pub mark: Option<usize>,
+    /// Whether the selection is a block
+    pub block: bool,
+    /// The states [undo](Editor::undo) goes back to, from the oldest
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub undo: Vec<EditorState>,
+    /// The states [redo](Editor::redo) goes forward to, from the latest undone
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub redo: Vec<EditorState>,
+}
+
+/// The buffer laid out in rows, as it would be drawn, so that it can be drawn by another
+/// program, like a TUI which repline is embedded in
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Frame {
+    /// Each row on screen, in runs of text in the same style
+    pub rows: Vec<Vec<StyledContent<String>>>,
+    /// The row and column of the cursor
+    pub cursor: (usize, usize),
+}
+
 /// A multi-line editor which operates on an un-cleared ANSI terminal.
 #[derive(Clone, Debug)]
 pub struct Editor<'a> {
     head: VecDeque<char>,
     tail: VecDeque<char>,
+    widths: Vec<usize>, // of each line's text, measured again as it's edited
+    line: usize,        // the line the cursor is on
+    mark: Option<usize>,
+    block: bool,
+    annotations: Vec<String>,
+    highlights: Vec<(Range<usize>, ContentStyle)>,
+    decorations: Vec<(Range<usize>, ContentStyle)>,
+    status: Option<String>,
+    secrets: Vec<Range<usize>>,
+    preview: Option<Range<usize>>,
+    prompts: Vec<String>, // drawn in place of begin and again, line by line, if any
+    undo: Vec<EditorState>,
+    redo: Vec<EditorState>,
+    top: usize, // the first line in view, when the buffer is taller than the terminal
+    goal: Option<(usize, usize)>, // the column moving up and down aims for, and where it left
 
-    pub color: &'a str,
-    pub begin: &'a str,
-    pub again: &'a str,
+    /// Prompt before the first line, and any lines of a banner above it
+    pub begin: Cow<'a, str>,
+    /// Prompt before each line after the first
+    pub again: Cow<'a, str>,
+    /// Whether to return to the cursor with DECSC/DECRC after drawing past it, rather than
+    /// by moving back to where the cursor should be
+    pub save_restore: bool,
+    /// Whether 24-bit colors can be drawn, rather than approximated from the 256-color palette
+    pub truecolor: bool,
+    /// Whether the shorter of the begin and again prompts is padded to the width of the
+    /// other, so that every line's text starts in the same column
+    pub align: bool,
+    /// The width of the terminal, where long lines wrap onto another row, or 0 if unknown
+    pub cols: usize,
+    /// The height of the terminal, which a taller buffer is scrolled within, or 0 if unknown
+    pub rows: usize,
+    /// Label for the position of the cursor, like `line 12/40`, shown while scrolled
+    pub line_label: &'a str,
+    /// Text drawn dimmed before the begin prompt, like how long the last command took
+    pub segment: String,
+    /// Text drawn dimmed after the begin prompt while the buffer is empty, like the default
+    /// value of a field
+    pub placeholder: Option<String>,
+    /// What counts as part of a word when deleting by words
+    pub words: WordChars<'a>,
+    /// Styles for the prompts, secondary text, and errors
+    pub theme: Theme,
 }
 
 impl<'a> Editor<'a> {
     /// Constructs a new Editor with the provided prompt color, begin prompt, and again prompt.
-    pub fn new(color: &'a str, begin: &'a str, again: &'a str) -> Self {
-        Self { head: Default::default(), tail: Default::default(), color, begin, again }
+    ///
+    /// The color, escape sequences like `\x1b[33m`, becomes the [theme](Editor::theme)'s
+    /// prompt styles.
+    pub fn new(color: &str, begin: &'a str, again: &'a str) -> Self {
+        let prompt = sgr_style(color);
+        Self {
+            head: Default::default(),
+            tail: Default::default(),
+            widths: vec![0],
+            line: 0,
+            mark: None,
+            block: false,
+            annotations: vec![],
+            highlights: vec![],
+            decorations: vec![],
+            status: None,
+            secrets: vec![],
+            preview: None,
+            prompts: vec![],
+            undo: vec![],
+            redo: vec![],
+            top: 0,
+            goal: None,
+            begin: begin.into(),
+            again: again.into(),
+            save_restore: supports_save_restore(),
+            truecolor: multiplexer::truecolor(Multiplexer::detect()),
+            align: false,
+            cols: 0,
+            rows: 0,
+            line_label: "line",
+            segment: String::new(),
+            placeholder: None,
+            words: WordChars::default(),
+            theme: Theme { prompt, again: prompt, ..Theme::default() },
+        }
+    }
+
+    /// Constructs an empty Editor with this one's theme and display settings, and the
+    /// provided begin and again prompts.
+    pub fn sibling(&self, begin: impl Into<Cow<'a, str>>, again: impl Into<Cow<'a, str>>) -> Self {
+        let Self { save_restore, truecolor, align, cols, rows, line_label, .. } = *self;
+        let Self { words, theme, .. } = *self;
+        let new = Self { begin: begin.into(), again: again.into(), ..Self::new("", "", "") };
+        Self { save_restore, truecolor, align, cols, rows, line_label, words, theme, ..new }
     }
 
     /// Returns an iterator over characters in the editor.
@@ -42,153 +407,1407 @@ impl<'a> Editor<'a> {
         head.iter().chain(tail.iter())
     }
 
-    fn putchar<W: Write>(&self, c: char, w: &mut W) -> ReplResult<()> {
-        let Self { color, again, .. } = self;
-        match c {
-            '\n' => queue!(
-                w,
-                Print('\n'),
-                MoveToColumn(0),
-                Print(color),
-                Print(again),
-                Print(ResetColor)
-            ),
-            c => queue!(w, Print(c)),
-        }?;
+    /// Returns the prompt for the given line, or the last line of it, which the text follows
+    pub fn prompt(&self, line: usize) -> &str {
+        let prompt: &str = match self.prompts.get(line) {
+            Some(prompt) => prompt,
+            None if line == 0 => &self.begin,
+            None => &self.again,
+        };
+        prompt.rsplit('\n').next().unwrap_or(prompt)
+    }
+
+    /// Sets the prompts drawn before each line, in place of the begin and again prompts.
+    /// Lines past the last of them get the begin or again prompt.
+    ///
+    /// The prompts take effect the next time the buffer is drawn.
+    pub fn set_prompts(&mut self, prompts: Vec<String>) {
+        self.prompts = prompts;
+    }
+
+    /// Returns the prompts drawn in place of the begin and again prompts
+    pub fn prompts(&self) -> &[String] {
+        &self.prompts
+    }
+
+    /// Returns the width of the prompt for the given line on screen, including the padding
+    /// which [aligns](Editor::align) it
+    pub fn prompt_width(&self, line: usize) -> usize {
+        let width = |line| match line {
+            0 => self.segment.width() + self.prompt(0).width(),
+            _ => self.prompt(line).width(),
+        };
+        match self.align {
+            true => width(0).max(width(1)),
+            false => width(line),
+        }
+    }
+
+    /// Returns the number of rows the begin prompt takes up above the first line
+    fn banner_rows(&self) -> usize {
+        self.begin.matches('\n').count()
+    }
+
+    /// Prints the lines of the begin prompt above the first line, if it has more than one
+    fn draw_banner<W: Write>(&self, w: &mut W) -> ReplResult<()> {
+        let Some((banner, _)) = self.begin.rsplit_once('\n') else {
+            return Ok(());
+        };
+        for line in banner.split('\n') {
+            self.print_prompt(0, line, w)?;
+            queue!(w, Print('\n'), MoveToColumn(0))?;
+        }
         Ok(())
     }
 
-    pub fn redraw_head<W: Write>(&self, w: &mut W) -> ReplResult<()> {
-        let Self { head, color, begin, .. } = self;
-        match head.iter().copied().filter(is_newline).count() {
+    /// Prints `text` in the style of the prompt for the given line
+    fn print_prompt<W: Write>(&self, line: usize, text: &str, w: &mut W) -> ReplResult<()> {
+        let style = self.prompt_style(line);
+        Ok(queue!(w, PrintStyledContent(style.apply(text)))?)
+    }
+
+    /// Returns the [theme](Editor::theme)'s style for the prompt for the given line
+    fn prompt_style(&self, line: usize) -> ContentStyle {
+        match line {
+            0 => self.theme.prompt,
+            _ => self.theme.again,
+        }
+    }
+
+    /// Styles the prompts with `color`, escape sequences like `\x1b[33m`, in the
+    /// [theme](Editor::theme)
+    pub(crate) fn color_prompts(&mut self, color: &str) {
+        let style = sgr_style(color);
+        (self.theme.prompt, self.theme.again) = (style, style);
+    }
+
+    /// Prints the gutter and prompt for the given line
+    fn draw_prompt<W: Write>(&self, line: usize, w: &mut W) -> ReplResult<()> {
+        let Self { annotations, theme, .. } = self;
+        let width = self.gutter_width();
+        if width > 0 {
+            let note = annotations
+                .get(line)
+                .map(String::as_str)
+                .unwrap_or_default();
+            queue!(w, Print(format_args!("{note:>width$} ")))?;
+        }
+        let prompt = self.prompt(line);
+        let segment = if line == 0 { self.segment.as_str() } else { "" };
+        let pad = self.prompt_width(line) - segment.width() - prompt.width();
+        queue!(w, Print(format_args!("{:pad$}", "")))?;
+        if !segment.is_empty() {
+            queue!(w, PrintStyledContent(theme.hint.apply(segment)))?;
+        }
+        self.print_prompt(line, prompt, w)
+    }
+
+    /// Prints a character which is on the given line
+    fn putchar<W: Write>(&self, c: char, line: usize, w: &mut W) -> ReplResult<()> {
+        match c {
+            '\n' => {
+                queue!(w, Print('\n'), MoveToColumn(0))?;
+                self.draw_prompt(line + 1, w)
+            }
+            c => Ok(queue!(w, Print(c))?),
+        }
+    }
+
+    /// Moves the cursor to the start of the prompt, above the first line if it has more than one,
+    /// or to the start of the first line in view
+    pub fn undraw<W: Write>(&self, w: &mut W) -> ReplResult<()> {
+        self.move_up(self.rows_above(), w)
+    }
+
+    /// Moves the cursor up `rows` rows, to the start of the row
+    fn move_up<W: Write>(&self, rows: usize, w: &mut W) -> ReplResult<()> {
+        match rows {
             0 => queue!(w, MoveToColumn(0)),
-            n => queue!(w, MoveUp(n as u16)),
+            n => queue!(w, MoveUp(n as u16), MoveToColumn(0)),
         }?;
+        Ok(())
+    }
+
+    /// Returns the number of rows drawn above the start of `line`, from the banner or the
+    /// first line in view
+    fn origin(&self, line: usize) -> usize {
+        let banner = if self.top == 0 { self.banner_rows() } else { 0 };
+        self.rows_between(self.top, line) + banner
+    }
 
-        queue!(w, Print(color), Print(begin), Print(ResetColor))?;
-        for c in head {
-            self.putchar(*c, w)?;
+    /// Returns the number of rows the buffer can take up on screen, leaving room for the
+    /// banner, and the status line below
+    fn height(&self) -> usize {
+        match self.rows {
+            0 => usize::MAX,
+            rows => rows.saturating_sub(self.banner_rows() + 1).max(1),
+        }
+    }
+
+    /// Returns the line after the last one in view
+    fn bottom(&self) -> usize {
+        let (wrap, height) = (self.wrap(), self.height());
+        let mut used = 0;
+        for line in self.top..self.lines() {
+            used += self.line_end_column(line) / wrap + 1;
+            if used > height && line > self.top {
+                return line;
+            }
+        }
+        self.lines()
+    }
+
+    /// Returns the number of rows drawn above the cursor's, from the banner or the first line
+    /// in view
+    pub fn rows_above(&self) -> usize {
+        self.origin(self.line()) + self.offset() / self.wrap()
+    }
+
+    /// Returns the index of the character drawn at `row` and `col`, counting rows like
+    /// [rows_above](Self::rows_above), or of the end of the line if `col` is past it.
+    ///
+    /// Returns `None` for the banner, and rows below the buffer in view.
+    pub fn index_at(&self, row: usize, col: usize) -> Option<usize> {
+        let banner = if self.top == 0 { self.banner_rows() } else { 0 };
+        let row = row.checked_sub(banner)? + self.row(self.top);
+        let (wrap, widths) = (self.wrap(), self.line_widths());
+        let buf: Vec<char> = self.iter().copied().collect();
+        let mut index = 0;
+        for (line, text) in buf.split(is_newline).enumerate().take(self.bottom()) {
+            let start = self.row(line);
+            if row > start + widths[line] / wrap {
+                index += text.len() + 1;
+                continue;
+            }
+            // the column clicked, as if the line didn't wrap
+            let target = (row - start) * wrap + col;
+            let mut at = self.width_of(line, []);
+            for (i, c) in text.iter().enumerate() {
+                at += width(c.encode_utf8(&mut [0; 4])) as usize;
+                if at > target {
+                    return Some(index + i);
+                }
+            }
+            return Some(index + text.len());
+        }
+        None
+    }
+
+    /// Returns the number of rows of the buffer in view below the cursor's
+    pub fn rows_below(&self) -> usize {
+        self.rows_between(self.line(), self.bottom())
+            .saturating_sub(self.offset() / self.wrap() + 1)
+    }
+
+    /// Returns true if some lines of the buffer are out of view, because it's taller than
+    /// the terminal
+    pub fn scrolled(&self) -> bool {
+        self.top > 0 || self.bottom() < self.lines()
+    }
+
+    /// Returns the number of lines in the buffer
+    fn lines(&self) -> usize {
+        self.widths.len()
+    }
+
+    /// Moves the view so that the cursor's line is in it, and no further down than needed
+    fn follow_cursor(&mut self) {
+        let line = self.line();
+        self.top = self.top.min(line);
+        while line >= self.bottom() {
+            self.top += 1;
+        }
+        while self.top > 0 {
+            self.top -= 1;
+            if line >= self.bottom() || self.bottom() < self.lines() {
+                self.top += 1;
+                break;
+            }
+        }
+    }
+
+    /// Redraws the lines in view, once the cursor has moved from `origin` rows below the
+    /// start of the view onto a line which may be out of it
+    fn scroll<W: Write>(&mut self, origin: usize, w: &mut W) -> ReplResult<()> {
+        self.move_up(origin, w)?;
+        queue!(w, Clear(ClearType::FromCursorDown))?;
+        self.follow_cursor();
+        self.draw_head(w)?;
+        self.redraw_tail(w)
+    }
+
+    /// Redraws the whole buffer if it's scrolled, lines out of view and all, so that all of
+    /// a submitted entry is left on screen, then redraws everything after the cursor
+    pub fn unscroll<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        if !self.scrolled() {
+            return self.redraw_tail(w);
+        }
+        self.undraw(w)?;
+        queue!(w, Clear(ClearType::FromCursorDown))?;
+        let rows = std::mem::take(&mut self.rows);
+        self.top = 0;
+        self.draw_head(w)?;
+        let drawn = self.redraw_tail(w);
+        self.rows = rows;
+        drawn
+    }
+
+    /// Returns an iterator over each character in the buffer from `start`, which is the
+    /// cursor or before it, with its line and its style
+    fn highlighted(&self, start: usize) -> impl Iterator<Item = (usize, char, ContentStyle)> + '_ {
+        let selection = self.selection().unwrap_or_default();
+        let block = self.block_selection();
+        let mut line = self.line_at(start);
+        let mut col = start - self.line_start(line);
+        let split = self.head.len();
+        let chars = (self.head.range(start.min(split)..))
+            .chain(self.tail.range(start.saturating_sub(split)..));
+        chars.enumerate().map(move |(i, &c)| {
+            let i = start + i;
+            let mut style = ContentStyle::new();
+            let decorations = self.highlights.iter().chain(&self.decorations);
+            for (_, deco) in decorations.filter(|(range, _)| range.contains(&i)) {
+                style.foreground_color = deco.foreground_color.or(style.foreground_color);
+                style.background_color = deco.background_color.or(style.background_color);
+                style.underline_color = deco.underline_color.or(style.underline_color);
+                style.attributes.extend(deco.attributes);
+            }
+            let selected = match &block {
+                Some((lines, cols)) => lines.contains(&line) && cols.contains(&col),
+                None => selection.contains(&i),
+            };
+            if selected {
+                style.attributes.set(Attribute::Reverse);
+            }
+            if self
+                .preview
+                .as_ref()
+                .is_some_and(|preview| preview.contains(&i))
+            {
+                style.attributes.set(Attribute::Underlined);
+            }
+            if !self.truecolor {
+                style.foreground_color = style.foreground_color.map(downsample);
+                style.background_color = style.background_color.map(downsample);
+                style.underline_color = style.underline_color.map(downsample);
+            }
+            let out = (line, self.masked(i, c), style);
+            (line, col) = if is_newline(&c) {
+                (line + 1, 0)
+            } else {
+                (line, col + 1)
+            };
+            out
+        })
+    }
+
+    /// Prints characters from the buffer, moving each line which exactly fills its last row
+    /// onto another, where the cursor would wrap to at the end of the line.
+    ///
+    /// `printed` says whether anything has been printed on the first line yet. Returns
+    /// whether anything was printed on the last.
+    fn draw_run<W: Write>(
+        &self,
+        chars: impl IntoIterator<Item = (usize, char, ContentStyle)>,
+        mut printed: bool,
+        w: &mut W,
+    ) -> ReplResult<bool> {
+        let wrap = self.wrap();
+        // characters in the same style are printed together, up to the end of the line
+        let mut run = (String::new(), ContentStyle::new());
+        for (line, c, style) in chars {
+            if is_newline(&c) {
+                print_run(&mut run, w)?;
+                let width = self.line_end_column(line);
+                if printed && width > 0 && width.is_multiple_of(wrap) {
+                    queue!(w, Print('\n'))?;
+                }
+                self.putchar(c, line, w)?;
+            } else if style == run.1 {
+                run.0.push(c);
+            } else {
+                print_run(&mut run, w)?;
+                run = (c.to_string(), style);
+            }
+            printed = true;
+        }
+        print_run(&mut run, w)?;
+        Ok(printed)
+    }
+
+    /// Moves the cursor onto the next row if drawing up to column `x` left it waiting at the
+    /// edge of the terminal, so that it's where the next character will be drawn
+    fn settle<W: Write>(&self, x: usize, w: &mut W) -> ReplResult<()> {
+        if x > 0 && x.is_multiple_of(self.wrap()) {
+            queue!(w, Print('\n'), MoveToColumn(0))?;
         }
         Ok(())
     }
 
+    /// Lays out the whole buffer in rows, however tall, with the banner above it and the
+    /// status line below it, as it would be drawn where lines wrap every `cols` columns, or
+    /// never if `cols` is 0
+    pub fn render(&self, cols: usize) -> Frame {
+        let wrap = if cols == 0 { usize::MAX } else { cols };
+        let prompt_style = |line| self.prompt_style(line);
+        let plain =
+            |text: &str| -> Vec<_> { text.chars().map(|c| (c, ContentStyle::new())).collect() };
+        let buf: Vec<char> = self.iter().copied().collect();
+        let (cursor_line, cursor_col) = position(&buf, self.cursor());
+
+        let mut rows: Vec<Vec<(char, ContentStyle)>> = vec![];
+        if let Some((banner, _)) = self.begin.rsplit_once('\n') {
+            let style = prompt_style(0);
+            let banner = banner
+                .split('\n')
+                .map(|line| line.chars().map(|c| (c, style)));
+            rows.extend(banner.map(Iterator::collect));
+        }
+        let mut cursor = (0, 0);
+        let mut chars = self.highlighted(0).peekable();
+        for line in 0..self.lines() {
+            let mut cells: Vec<(char, ContentStyle)> = vec![];
+            let gutter = self.gutter_width();
+            if gutter > 0 {
+                let note = self
+                    .annotations
+                    .get(line)
+                    .map(String::as_str)
+                    .unwrap_or_default();
+                cells.extend(plain(&format!("{note:>gutter$} ")));
+            }
+            let prompt = self.prompt(line);
+            let segment = if line == 0 { self.segment.as_str() } else { "" };
+            let pad = self.prompt_width(line) - segment.width() - prompt.width();
+            cells.extend(plain(&" ".repeat(pad)));
+            cells.extend(segment.chars().map(|c| (c, self.theme.hint)));
+            cells.extend(prompt.chars().map(|c| (c, prompt_style(line))));
+            let cursor_cell = (line == cursor_line).then_some(cells.len() + cursor_col);
+            while let Some((_, c, style)) = chars.next_if(|&(l, c, _)| l == line && c != '\n') {
+                cells.push((c, style));
+            }
+            chars.next();
+            if let Some(placeholder) = self.placeholder.as_ref().filter(|_| self.is_empty()) {
+                cells.extend(placeholder.chars().map(|c| (c, self.theme.hint)));
+            }
+
+            // wide characters which don't fit at the end of a row start the next one
+            let (len, mut row, mut used) = (cells.len(), vec![], 0);
+            for (cell, (c, style)) in cells.into_iter().enumerate() {
+                let w = width(c.encode_utf8(&mut [0; 4])) as usize;
+                if used + w > wrap {
+                    rows.push(std::mem::take(&mut row));
+                    used = 0;
+                }
+                if cursor_cell == Some(cell) {
+                    cursor = (rows.len(), used);
+                }
+                row.push((c, style));
+                used += w;
+            }
+            rows.push(row);
+            // a line which exactly fills its last row takes up another, which the cursor
+            // wraps to
+            if used == wrap {
+                rows.push(vec![]);
+            }
+            if cursor_cell == Some(len) {
+                cursor = (rows.len() - 1, used % wrap);
+            }
+        }
+        if let Some(status) = &self.status {
+            rows.push(plain(status));
+        }
+
+        let runs = |row: Vec<(char, ContentStyle)>| {
+            let mut runs: Vec<(String, ContentStyle)> = vec![];
+            for (c, style) in row {
+                match runs.last_mut() {
+                    Some((run, last)) if *last == style => run.push(c),
+                    _ => runs.push((c.to_string(), style)),
+                }
+            }
+            runs.into_iter()
+                .map(|(run, style)| StyledContent::new(style, run))
+                .collect()
+        };
+        Frame { rows: rows.into_iter().map(runs).collect(), cursor }
+    }
+
+    /// Prints the prompt and the characters before the cursor, from the first line in view
+    pub fn draw_head<W: Write>(&self, w: &mut W) -> ReplResult<()> {
+        let top = self.top;
+        if top == 0 {
+            self.draw_banner(w)?;
+        }
+        self.draw_prompt(top, w)?;
+        let start = self.line_start(top);
+        let head = self
+            .highlighted(start)
+            .take(self.head.len().saturating_sub(start));
+        self.draw_run(head, true, w)?;
+        self.settle(self.offset(), w)
+    }
+
+    /// Moves back to the start of the prompt, and prints the prompt and the characters before
+    /// the cursor again
+    pub fn redraw_head<W: Write>(&self, w: &mut W) -> ReplResult<()> {
+        self.undraw(w)?;
+        self.draw_head(w)
+    }
+
+    /// Prints the characters after the cursor, up to the last line in view, and the status
+    /// line below them
     pub fn redraw_tail<W: Write>(&self, w: &mut W) -> ReplResult<()> {
-        let Self { tail, .. } = self;
-        queue!(w, SavePosition, Clear(ClearType::FromCursorDown))?;
-        for c in tail {
-            self.putchar(*c, w)?;
+        self.save_cursor(w)?;
+        queue!(w, Clear(ClearType::FromCursorDown))?;
+        let last = self.bottom() - 1;
+        // the line break after the last line in view would start drawing the next
+        let tail = self.highlighted(self.head.len());
+        let tail = tail.take_while(|&(line, c, _)| line < last || line == last && c != '\n');
+        let printed = self.draw_run(tail, false, w)?;
+        if let Some(placeholder) = self.placeholder.as_ref().filter(|_| self.is_empty()) {
+            // cut short to the row, so it's cleared along with it by the first key typed
+            let mut room = self.wrap() - self.offset() % self.wrap() - 1;
+            let placeholder: String = (placeholder.chars())
+                .take_while(|c| match room.checked_sub(char_width(c)) {
+                    Some(left) => {
+                        room = left;
+                        true
+                    }
+                    None => false,
+                })
+                .collect();
+            queue!(w, PrintStyledContent(self.theme.hint.apply(placeholder)))?;
         }
-        queue!(w, RestorePosition)?;
-        Ok(())
+        // the row the end of the buffer was drawn on, which it may not have wrapped from yet
+        let (width, wrap) = (self.line_end_column(last), self.wrap());
+        let waiting = printed && width > 0 && width.is_multiple_of(wrap);
+        let end = self.rows_between(self.line(), last) + width / wrap - waiting as usize;
+        let mut rows = end.saturating_sub(self.offset() / wrap);
+        if self.scrolled() {
+            let (line, lines) = (self.line() + 1, self.lines());
+            let position = format!("{} {line}/{lines}", self.line_label);
+            let position = PrintStyledContent(self.theme.hint.apply(position));
+            queue!(w, Print('\n'), MoveToColumn(0), position)?;
+            if let Some(status) = &self.status {
+                queue!(w, Print("  "), Print(status))?;
+            }
+            rows += 1;
+        } else if let Some(status) = &self.status {
+            queue!(w, Print('\n'), MoveToColumn(0), Print(status))?;
+            rows += 1;
+        }
+        self.restore_cursor(rows, w)
+    }
+
+    /// Redraws the entire buffer, leaving the cursor in place.
+    pub fn redraw<W: Write>(&self, w: &mut W) -> ReplResult<()> {
+        self.redraw_head(w)?;
+        self.redraw_tail(w)
+    }
+
+    /// Redraws the buffer from the start of the line with the character at `index` onward,
+    /// leaving the lines before it as they're drawn, like after its styles changed from
+    /// there.
+    ///
+    /// The buffer is redrawn from the cursor if `index` is after it, and whole if it's on
+    /// the first line in view.
+    pub fn redraw_from<W: Write>(&self, index: usize, w: &mut W) -> ReplResult<()> {
+        if index >= self.cursor() {
+            return self.redraw_tail(w);
+        }
+        let line = self.line_at(index);
+        if line <= self.top {
+            return self.redraw(w);
+        }
+        let rows = self.rows_between(line, self.line()) + self.offset() / self.wrap();
+        self.move_up(rows, w)?;
+        self.draw_prompt(line, w)?;
+        let start = self.line_start(line);
+        let head = self.highlighted(start).take(self.head.len() - start);
+        self.draw_run(head, true, w)?;
+        self.settle(self.offset(), w)?;
+        self.redraw_tail(w)
+    }
+
+    /// Clears the screen, and redraws the entire buffer at the top of it
+    pub fn clear_screen<W: Write>(&self, w: &mut W) -> ReplResult<()> {
+        queue!(w, MoveTo(0, 0), Clear(ClearType::All))?;
+        self.draw_head(w)?;
+        self.redraw_tail(w)
+    }
+
+    /// Rewrites the buffer using `f`, which returns the new position of the cursor,
+    /// then redraws the entire buffer.
+    pub fn rewrite<W: Write>(
+        &mut self,
+        w: &mut W,
+        f: impl FnOnce(&mut Vec<char>) -> usize,
+    ) -> ReplResult<()> {
+        self.undraw(w)?;
+        let mut buf: Vec<char> = self.iter().copied().collect();
+        let cursor = f(&mut buf).min(buf.len());
+        self.tail = buf.split_off(cursor).into();
+        self.head = buf.into();
+        self.measure();
+        self.follow_cursor();
+        self.draw_head(w)?;
+        self.redraw_tail(w)
+    }
+
+    /// Replaces the contents of the buffer with `text`, redrawing only from the first
+    /// changed character onward.
+    ///
+    /// The cursor keeps its place in the unchanged text around it, and moves to the end of
+    /// the changed text if it was inside it.
+    pub fn replace_all<W: Write>(&mut self, text: &str, w: &mut W) -> ReplResult<()> {
+        let old: Vec<char> = self.iter().copied().collect();
+        let new: Vec<char> = text.chars().collect();
+        let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+        let rest = old.len().min(new.len()) - prefix;
+        let suffix = (old.iter().rev().zip(new.iter().rev()).take(rest))
+            .take_while(|(a, b)| a == b)
+            .count();
+        if prefix == old.len() && old.len() == new.len() {
+            return Ok(());
+        }
+        let cursor = match self.cursor() {
+            cursor if cursor <= prefix => cursor,
+            cursor if cursor >= old.len() - suffix => cursor + new.len() - old.len(),
+            _ => new.len() - suffix,
+        };
+
+        self.unmark(w)?;
+        self.cursor_to(prefix, w)?;
+        self.tail = new[prefix..].iter().copied().collect();
+        self.measure();
+        self.redraw_tail(w)?;
+        self.cursor_to(cursor, w)
+    }
+
+    /// Prints the characters before the cursor on the current line, and the lines of the
+    /// prompt above the first line, if that's where the cursor is.
+    ///
+    /// Meant for starting to draw the buffer on a new line.
+    pub fn start_head<W: Write>(&self, w: &mut W) -> ReplResult<()> {
+        if self.line() == 0 {
+            queue!(w, MoveToColumn(0))?;
+            self.draw_banner(w)?;
+        }
+        self.print_head(w)
     }
 
     /// Prints the characters before the cursor on the current line.
     pub fn print_head<W: Write>(&self, w: &mut W) -> ReplResult<()> {
-        let Self { head, color, begin, again, .. } = self;
+        let Self { head, .. } = self;
         let nl = self.head.iter().rposition(is_newline).map(|n| n + 1);
-        let prompt = if nl.is_some() { again } else { begin };
 
-        queue!(w, MoveToColumn(0), Print(color), Print(prompt), ResetColor)?;
+        queue!(w, MoveToColumn(0))?;
+        self.draw_prompt(self.line(), w)?;
 
-        write_chars(head.iter().skip(nl.unwrap_or(0)), w)?;
-        Ok(())
+        let start = nl.unwrap_or(0);
+        write_chars(
+            head.iter()
+                .enumerate()
+                .skip(start)
+                .map(|(i, &c)| self.masked(i, c)),
+            w,
+        )?;
+        self.settle(self.offset(), w)
     }
 
-    /// Prints the characters after the cursor on the current line.
+    /// Prints the characters after the cursor on the current line, or redraws everything
+    /// after the cursor if the line wraps, or might have before the last edit.
     pub fn print_tail<W: Write>(&self, w: &mut W) -> ReplResult<()> {
+        // leave room for a deleted character as wide as two columns
+        if self.line_end_column(self.line()) + 2 >= self.wrap() {
+            return self.redraw_tail(w);
+        }
         let Self { tail, .. } = self;
-        queue!(w, SavePosition, Clear(ClearType::UntilNewLine))?;
-        write_chars(tail.iter().take_while(|&c| !is_newline(c)), w)?;
-        queue!(w, RestorePosition)?;
-        Ok(())
+        self.save_cursor(w)?;
+        queue!(w, Clear(ClearType::UntilNewLine))?;
+        let line = tail.iter().take_while(|&c| !is_newline(c)).enumerate();
+        write_chars(line.map(|(i, &c)| self.masked(self.head.len() + i, c)), w)?;
+        self.restore_cursor(0, w)
     }
 
+    /// Prints `err` at the cursor, over the rest of its row, and returns to the cursor
     pub fn print_err<W: Write>(&self, err: impl Display, w: &mut W) -> ReplResult<()> {
-        queue!(
-            w,
-            SavePosition,
-            Clear(ClearType::UntilNewLine),
-            Print(err),
-            RestorePosition
-        )?;
+        self.save_cursor(w)?;
+        queue!(w, Clear(ClearType::UntilNewLine), Print(err))?;
+        self.restore_cursor(0, w)
+    }
+
+    /// Remembers the position of the cursor, if [save_restore](Editor::save_restore) is set
+    fn save_cursor<W: Write>(&self, w: &mut W) -> ReplResult<()> {
+        if self.save_restore {
+            queue!(w, SavePosition)?;
+        }
+        Ok(())
+    }
+
+    /// Returns to the cursor after drawing `lines` rows below it
+    fn restore_cursor<W: Write>(&self, lines: usize, w: &mut W) -> ReplResult<()> {
+        let column = MoveToColumn(self.column() as u16);
+        match (self.save_restore, lines) {
+            (true, _) => queue!(w, RestorePosition),
+            (false, 0) => queue!(w, column),
+            (false, n) => queue!(w, MoveUp(n as u16), column),
+        }?;
         Ok(())
     }
 
     /// Writes a character at the cursor, shifting the text around as necessary.
     pub fn push<W: Write>(&mut self, c: char, w: &mut W) -> ReplResult<()> {
+        let (line, origin) = (self.line(), self.rows_above());
         self.head.push_back(c);
+        self.line += is_newline(&c) as usize;
+        self.remeasure(line, 1, 1 + is_newline(&c) as usize);
+        if is_newline(&c) && self.scrolled() || self.line() >= self.bottom() {
+            return self.scroll(origin, w);
+        }
         queue!(w, Clear(ClearType::UntilNewLine))?;
-        self.putchar(c, w)?;
+        // a character typed at the end of a secret is probably part of it
+        let at = self.head.len() - 1;
+        let secret = self.secrets.iter().any(|s| (s.start..=s.end).contains(&at));
+        self.putchar(if secret && !is_newline(&c) { MASK } else { c }, line, w)?;
         match c {
             '\n' => self.redraw_tail(w),
-            _ => self.print_tail(w),
+            _ => {
+                self.settle(self.offset(), w)?;
+                self.print_tail(w)
+            }
         }
     }
 
-    /// Erases a character at the cursor, shifting the text around as necessary.
-    pub fn pop<W: Write>(&mut self, w: &mut W) -> ReplResult<Option<char>> {
-        let c = self.head.pop_back();
-
-        match c {
-            None => return Ok(None),
-            Some('\n') => {
-                queue!(w, MoveToPreviousLine(1))?;
-                self.print_head(w)?;
-                self.redraw_tail(w)?;
-            }
-            Some(_) => {
-                queue!(w, MoveLeft(1), Clear(ClearType::UntilNewLine))?;
-                self.print_tail(w)?;
-            }
+    /// Erases the grapheme cluster before the cursor, like an emoji or a letter with its
+    /// accents, shifting the text around as necessary. Returns the erased text.
+    pub fn pop<W: Write>(&mut self, w: &mut W) -> ReplResult<Option<String>> {
+        let len = last_grapheme(self.head.iter().rev());
+        if len == 0 {
+            return Ok(None);
         }
-
-        Ok(c)
+        let (from, origin) = (self.offset(), self.rows_above());
+        let (line, scrolled) = (self.line(), self.scrolled());
+        let text: String = self.head.drain(self.head.len() - len..).collect();
+        self.line -= text.chars().filter(is_newline).count();
+        self.remeasure(self.line, line - self.line + 1, 1);
+        if text == "\n" && scrolled {
+            self.scroll(origin, w)?;
+        } else if text == "\n" {
+            queue!(
+                w,
+                MoveToPreviousLine((self.offset() / self.wrap() + 1) as u16)
+            )?;
+            self.print_head(w)?;
+            self.redraw_tail(w)?;
+        } else {
+            move_along(from, self.offset(), self.wrap(), w)?;
+            queue!(w, Clear(ClearType::UntilNewLine))?;
+            self.print_tail(w)?;
+        }
+        Ok(Some(text))
     }
 
-    /// Pops the character after the cursor, redrawing if necessary
-    pub fn delete<W: Write>(&mut self, w: &mut W) -> ReplResult<Option<char>> {
-        let c = self.tail.pop_front();
-        match c {
-            Some('\n') => self.redraw_tail(w)?,
+    /// Erases the grapheme cluster after the cursor, redrawing if necessary. Returns the
+    /// erased text.
+    pub fn delete<W: Write>(&mut self, w: &mut W) -> ReplResult<Option<String>> {
+        let len = first_grapheme(self.tail.iter());
+        let scrolled = self.scrolled();
+        let text: String = self.tail.drain(..len).collect();
+        let breaks = text.chars().filter(is_newline).count();
+        self.remeasure(self.line, breaks + 1, 1);
+        match text.as_str() {
+            "\n" if scrolled => self.scroll(self.rows_above(), w)?,
+            "\n" => self.redraw_tail(w)?,
             _ => self.print_tail(w)?,
         }
-        Ok(c)
+        Ok((!text.is_empty()).then_some(text))
     }
 
     /// Writes characters into the editor at the location of the cursor.
+    ///
+    /// More than one character is drawn in one go, rather than one at a time, which would
+    /// lay out the rest of the buffer again after each.
     pub fn extend<T: IntoIterator<Item = char>, W: Write>(
         &mut self,
         iter: T,
         w: &mut W,
     ) -> ReplResult<()> {
-        for c in iter {
-            self.push(c, w)?;
+        let (len, origin) = (self.head.len(), self.rows_above());
+        let line = self.line();
+        self.head.extend(iter);
+        match self.head.len() - len {
+            0 => Ok(()),
+            1 => {
+                let c = self.head.pop_back().expect("a character was just added");
+                self.push(c, w)
+            }
+            _ => {
+                let breaks = self.head.range(len..).filter(|c| is_newline(c)).count();
+                self.line += breaks;
+                self.remeasure(line, 1, breaks + 1);
+                self.scroll(origin, w)
+            }
         }
-        Ok(())
     }
 
     /// Sets the editor to the contents of a string, placing the cursor at the end.
     pub fn restore<W: Write>(&mut self, s: &str, w: &mut W) -> ReplResult<()> {
-        match self.head.iter().copied().filter(is_newline).count() {
-            0 => queue!(w, MoveToColumn(0), Clear(ClearType::FromCursorDown))?,
-            n => queue!(w, MoveUp(n as u16), Clear(ClearType::FromCursorDown))?,
-        };
+        let rows = self.rows_between(self.top, self.line()) + self.offset() / self.wrap();
+        self.move_up(rows, w)?;
+        queue!(w, Clear(ClearType::FromCursorDown))?;
         self.clear();
         self.print_head(w)?;
         self.extend(s.chars(), w)
     }
 
+    /// Saves the buffer, cursor, selection, and what can be undone and redone
+    pub fn snapshot(&self) -> EditorState {
+        let (undo, redo) = (self.undo.clone(), self.redo.clone());
+        EditorState { undo, redo, ..self.state() }
+    }
+
+    /// Replaces the buffer, cursor, selection, and what can be undone and redone with a
+    /// saved [EditorState].
+    ///
+    /// The state takes effect the next time the buffer is drawn.
+    pub fn restore_state(&mut self, mut state: EditorState) {
+        self.undo = std::mem::take(&mut state.undo);
+        self.redo = std::mem::take(&mut state.redo);
+        self.set_state(state);
+    }
+
+    /// Saves the buffer, cursor, and selection, without the undo history, like for a
+    /// [checkpoint](Self::checkpoint)
+    pub(crate) fn state(&self) -> EditorState {
+        let Self { mark, block, .. } = *self;
+        let (text, cursor) = (self.to_string(), self.cursor());
+        EditorState { text, cursor, mark, block, ..Default::default() }
+    }
+
+    /// Replaces the buffer, cursor, and selection with a saved [EditorState], keeping what
+    /// can be undone and redone
+    pub(crate) fn set_state(&mut self, state: EditorState) {
+        let EditorState { text, cursor, mark, block, .. } = state;
+        let mut buf: Vec<char> = text.chars().collect();
+        self.clear();
+        self.tail = buf.split_off(cursor.min(buf.len())).into();
+        self.head = buf.into();
+        self.measure();
+        (self.mark, self.block) = (mark, block && mark.is_some());
+        self.follow_cursor();
+    }
+
+    /// Remembers `before` as a state to [undo](Self::undo) back to, if the buffer has changed
+    /// since, and forgets what was undone.
+    pub fn checkpoint(&mut self, before: EditorState) {
+        if before.text == self.to_string() || self.undo.last() == Some(&before) {
+            return;
+        }
+        self.undo.push(before);
+        self.redo.clear();
+    }
+
+    /// Forgets all the states which could be undone or redone
+    pub fn clear_undo(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Reverts the buffer and cursor to the last [checkpoint](Self::checkpoint).
+    ///
+    /// Returns false if there's nothing to undo.
+    pub fn undo<W: Write>(&mut self, w: &mut W) -> ReplResult<bool> {
+        let Some(state) = self.undo.pop() else {
+            return Ok(false);
+        };
+        self.redo.push(self.state());
+        self.revert(state, w)?;
+        Ok(true)
+    }
+
+    /// Reapplies the last change which was [undone](Self::undo).
+    ///
+    /// Returns false if there's nothing to redo.
+    pub fn redo<W: Write>(&mut self, w: &mut W) -> ReplResult<bool> {
+        let Some(state) = self.redo.pop() else {
+            return Ok(false);
+        };
+        self.undo.push(self.state());
+        self.revert(state, w)?;
+        Ok(true)
+    }
+
+    /// Replaces the buffer and moves the cursor to match a saved state, dropping the selection
+    pub fn revert<W: Write>(&mut self, state: EditorState, w: &mut W) -> ReplResult<()> {
+        self.unmark(w)?;
+        self.replace_all(&state.text, w)?;
+        self.cursor_to(state.cursor.min(self.len()), w)
+    }
+
     /// Clears the editor, removing all characters.
     pub fn clear(&mut self) {
         self.head.clear();
         self.tail.clear();
+        (self.widths, self.line) = (vec![0], 0);
+        self.mark = None;
+        self.block = false;
+        self.annotations.clear();
+        self.highlights.clear();
+        self.decorations.clear();
+        self.status = None;
+        self.secrets.clear();
+        self.preview = None;
+        self.top = 0;
     }
 
-    /// Erases a word from the buffer, where a word is any non-whitespace characters
-    /// preceded by a single whitespace character
-    pub fn erase_word<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
-        while self.pop(w)?.filter(|c| !c.is_whitespace()).is_some() {}
-        Ok(())
+    /// Sets the styles applied to ranges of characters in the buffer.
+    ///
+    /// The styles take effect the next time the buffer is drawn.
+    pub fn set_decorations(&mut self, decorations: Vec<(Range<usize>, ContentStyle)>) {
+        self.decorations = decorations;
+    }
+
+    /// Returns the decorations of the buffer
+    pub fn decorations(&self) -> &[(Range<usize>, ContentStyle)] {
+        &self.decorations
+    }
+
+    /// Sets the syntax highlighting of ranges of characters in the buffer, which is drawn
+    /// beneath the decorations.
+    ///
+    /// The styles take effect the next time the buffer is drawn.
+    pub fn set_highlights(&mut self, highlights: Vec<(Range<usize>, ContentStyle)>) {
+        self.highlights = highlights;
+    }
+
+    /// Returns the syntax highlighting of the buffer
+    pub fn highlights(&self) -> &[(Range<usize>, ContentStyle)] {
+        &self.highlights
+    }
+
+    /// Sets the message shown on the status line below the buffer.
+    ///
+    /// The status line is drawn along with the rest of the buffer after the cursor.
+    pub fn set_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+
+    /// Sets the range of characters which is drawn underlined, as a completion candidate
+    /// which hasn't been settled on yet.
+    ///
+    /// The preview takes effect the next time the buffer is drawn.
+    pub fn set_preview(&mut self, preview: Option<Range<usize>>) {
+        self.preview = preview;
+    }
+
+    /// Sets the ranges of characters which are drawn as [`MASK`], such as passwords.
+    ///
+    /// The masks take effect the next time the buffer is drawn.
+    pub fn set_secrets(&mut self, secrets: Vec<Range<usize>>) {
+        self.secrets = secrets;
+    }
+
+    /// Returns the ranges of characters which are drawn as [`MASK`]
+    pub fn secrets(&self) -> &[Range<usize>] {
+        &self.secrets
+    }
+
+    /// Returns the character drawn in place of character `c` at index `i`
+    fn masked(&self, i: usize, c: char) -> char {
+        match self.secrets.iter().any(|s| s.contains(&i)) && !is_newline(&c) {
+            true => MASK,
+            false => c,
+        }
+    }
+
+    /// Returns the index of the cursor in the buffer
+    pub fn cursor(&self) -> usize {
+        self.head.len()
+    }
+
+    /// Returns the byte index of the cursor in the buffer's text
+    pub fn byte_cursor(&self) -> usize {
+        self.head.iter().map(|c| c.len_utf8()).sum()
+    }
+
+    /// Returns the column the cursor is drawn in, counting the gutter and prompt
+    pub fn column(&self) -> usize {
+        self.offset() % self.wrap()
+    }
+
+    /// Returns the column the cursor would be drawn in if its line didn't wrap
+    fn offset(&self) -> usize {
+        let start = self.head.iter().rposition(is_newline).map_or(0, |n| n + 1);
+        self.width_of(self.line(), self.head.range(start..))
+    }
+
+    /// Returns the screen column just past the end of the buffer's last line, ignoring the
+    /// line break at the end of a submitted buffer
+    pub fn end_column(&self) -> usize {
+        let submitted = self
+            .tail
+            .back()
+            .or(self.head.back())
+            .is_some_and(is_newline);
+        self.line_end_column(self.lines() - 1 - submitted as usize) % self.wrap()
+    }
+
+    /// Returns the column lines wrap at: the width of the terminal, if it's known
+    fn wrap(&self) -> usize {
+        match self.cols {
+            0 => usize::MAX,
+            cols => cols,
+        }
+    }
+
+    /// Returns the width of each line, with its gutter and prompt, as if it didn't wrap
+    fn line_widths(&self) -> Vec<usize> {
+        (0..self.lines())
+            .map(|line| self.line_end_column(line))
+            .collect()
+    }
+
+    /// Returns the row the given line starts on, counting from the first line's.
+    ///
+    /// A line which exactly fills its last row takes up another, which the cursor wraps to.
+    pub fn row(&self, line: usize) -> usize {
+        self.rows_between(0, line)
+    }
+
+    /// Returns the number of rows taken up by the lines from `first` up to `end`
+    fn rows_between(&self, first: usize, end: usize) -> usize {
+        let wrap = self.wrap();
+        (first..end.min(self.lines()))
+            .map(|line| self.line_end_column(line) / wrap + 1)
+            .sum()
+    }
+
+    /// Returns the row the cursor is on, counting from the first line's
+    pub fn cursor_row(&self) -> usize {
+        self.row(self.line()) + self.offset() / self.wrap()
+    }
+
+    /// Returns the column just past the end of the given line, as if it didn't wrap
+    pub fn line_end_column(&self, line: usize) -> usize {
+        self.width_of(line, []) + self.widths.get(line).copied().unwrap_or_default()
+    }
+
+    /// Returns the width of the gutter, the prompt, and the `text` on the given line
+    fn width_of<'c>(&self, line: usize, text: impl IntoIterator<Item = &'c char>) -> usize {
+        let gutter = match self.gutter_width() {
+            0 => 0,
+            n => n + 1,
+        };
+        let text: String = text.into_iter().collect();
+        gutter + self.prompt_width(line) + width(&text) as usize
+    }
+
+    /// Returns the index of the line the cursor is on
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the index of the line with the character at `index`, counting the line breaks
+    /// between it and the cursor
+    fn line_at(&self, index: usize) -> usize {
+        match index.checked_sub(self.head.len()) {
+            None => self.line - self.head.range(index..).filter(|c| is_newline(c)).count(),
+            Some(after) => self.line + self.tail.range(..after).filter(|c| is_newline(c)).count(),
+        }
+    }
+
+    /// Returns the index of the first character of the given line, which is the cursor's or
+    /// one above it
+    fn line_start(&self, line: usize) -> usize {
+        let mut breaks = self
+            .head
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, c)| is_newline(c));
+        breaks.nth(self.line - line).map_or(0, |(i, _)| i + 1)
+    }
+
+    /// Measures every line of the buffer again, after it's been replaced
+    fn measure(&mut self) {
+        self.line = self.head.iter().filter(|c| is_newline(c)).count();
+        self.widths.clear();
+        self.remeasure(0, 0, usize::MAX);
+    }
+
+    /// Measures `count` lines from `first`, which is the cursor's or one above it, in place
+    /// of the widths of the `replaced` lines there before an edit which changed only those
+    fn remeasure(&mut self, first: usize, replaced: usize, count: usize) {
+        let (start, split) = (self.line_start(first), self.head.len());
+        let text = (self.head.range(start.min(split)..))
+            .chain(self.tail.range(start.saturating_sub(split)..));
+        let (mut widths, mut line) = (vec![], String::new());
+        for &c in text {
+            if !is_newline(&c) {
+                line.push(c);
+                continue;
+            }
+            widths.push(width(&line) as usize);
+            if widths.len() == count {
+                break;
+            }
+            line.clear();
+        }
+        if widths.len() < count {
+            widths.push(width(&line) as usize);
+        }
+        self.widths.splice(first..first + replaced, widths);
+    }
+
+    /// Attaches a short annotation to the given line, shown in a gutter before its prompt.
+    ///
+    /// The annotation takes effect the next time the line is drawn.
+    pub fn annotate(&mut self, line: usize, note: impl Into<String>) {
+        if self.annotations.len() <= line {
+            self.annotations.resize(line + 1, String::new());
+        }
+        self.annotations[line] = note.into();
+    }
+
+    /// Removes all line annotations
+    pub fn clear_annotations(&mut self) {
+        self.annotations.clear();
+    }
+
+    /// Returns the width of the annotation gutter, excluding its separator
+    pub fn gutter_width(&self) -> usize {
+        self.annotations
+            .iter()
+            .map(|a| a.chars().count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the range of selected characters, if there is a (non-block) selection
+    pub fn selection(&self) -> Option<Range<usize>> {
+        if self.block {
+            return None;
+        }
+        let (mark, cursor) = (self.mark?.min(self.len()), self.cursor());
+        Some(mark.min(cursor)..mark.max(cursor))
+    }
+
+    /// Returns the lines and columns covered by the block selection, if there is one
+    pub fn block_selection(&self) -> Option<(RangeInclusive<usize>, Range<usize>)> {
+        if !self.block {
+            return None;
+        }
+        let buf: Vec<char> = self.iter().copied().collect();
+        let (mark_line, mark_col) = position(&buf, self.mark?);
+        let (line, col) = position(&buf, self.cursor());
+        Some((
+            mark_line.min(line)..=mark_line.max(line),
+            mark_col.min(col)..mark_col.max(col),
+        ))
+    }
+
+    /// Anchors the selection at the cursor, if it isn't already anchored
+    pub fn mark(&mut self) {
+        if self.block {
+            self.mark = None;
+            self.block = false;
+        }
+        self.mark.get_or_insert(self.head.len());
+    }
+
+    /// Anchors a block (column) selection at the cursor, if it isn't already anchored
+    pub fn mark_block(&mut self) {
+        if !self.block {
+            self.mark = None;
+            self.block = true;
+        }
+        self.mark.get_or_insert(self.head.len());
+    }
+
+    /// Drops the selection, redrawing to remove its highlight
+    pub fn unmark<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        self.block = false;
+        match self.mark.take() {
+            Some(_) => self.redraw(w),
+            None => Ok(()),
+        }
+    }
+
+    /// Selects the given range of characters, placing the cursor at its end
+    pub fn select<W: Write>(&mut self, range: Range<usize>, w: &mut W) -> ReplResult<()> {
+        self.mark = Some(range.start);
+        self.cursor_to(range.end, w)?;
+        self.redraw(w)
+    }
+
+    /// Erases the selected characters, returning them
+    pub fn delete_selection<W: Write>(&mut self, w: &mut W) -> ReplResult<Option<String>> {
+        let Some(range) = self.selection() else {
+            return Ok(None);
+        };
+        self.mark = None;
+        self.erase(range, w).map(Some)
+    }
+
+    /// Applies `f` to the selected columns of each line in the block selection which reaches
+    /// them, then moves the block's mark and cursor to column `col`.
+    ///
+    /// Returns false if there is no block selection.
+    fn edit_block<W: Write>(
+        &mut self,
+        col: usize,
+        w: &mut W,
+        f: impl Fn(&mut Vec<char>, Range<usize>),
+    ) -> ReplResult<bool> {
+        let (Some((lines, cols)), Some(mark)) = (self.block_selection(), self.mark) else {
+            return Ok(false);
+        };
+        let buf: Vec<char> = self.iter().copied().collect();
+        let (mark_line, line) = (position(&buf, mark).0, position(&buf, self.cursor()).0);
+
+        let mut out = vec![];
+        for (n, text) in buf.split(is_newline).enumerate() {
+            let mut text = text.to_vec();
+            if lines.contains(&n) && cols.start <= text.len() {
+                let cols = cols.start..cols.end.min(text.len());
+                f(&mut text, cols);
+            }
+            if n > 0 {
+                out.push('\n');
+            }
+            out.extend(text);
+        }
+
+        self.mark = Some(index_of(&out, mark_line, col));
+        let cursor = index_of(&out, line, col);
+        self.rewrite(w, |buf| {
+            *buf = out;
+            cursor
+        })?;
+        Ok(true)
+    }
+
+    /// Replaces the block selection with `c` on every selected line.
+    ///
+    /// Returns false if there is no block selection.
+    pub fn block_insert<W: Write>(&mut self, c: char, w: &mut W) -> ReplResult<bool> {
+        let Some((_, cols)) = self.block_selection() else {
+            return Ok(false);
+        };
+        self.edit_block(cols.start + 1, w, |text, cols| {
+            text.splice(cols, [c]);
+        })
+    }
+
+    /// Erases the block selection, or the column before it if it's empty, on every
+    /// selected line.
+    ///
+    /// Returns false if there is no block selection.
+    pub fn block_backspace<W: Write>(&mut self, w: &mut W) -> ReplResult<bool> {
+        let Some((_, cols)) = self.block_selection() else {
+            return Ok(false);
+        };
+        match cols.is_empty() {
+            true if cols.start == 0 => Ok(true),
+            true => self.edit_block(cols.start - 1, w, |text, cols| {
+                text.remove(cols.start - 1);
+            }),
+            false => self.edit_block(cols.start, w, |text, cols| {
+                text.drain(cols);
+            }),
+        }
+    }
+
+    /// Erases the block selection, or the column after it if it's empty, on every
+    /// selected line.
+    ///
+    /// Returns false if there is no block selection.
+    pub fn block_delete<W: Write>(&mut self, w: &mut W) -> ReplResult<bool> {
+        let Some((_, cols)) = self.block_selection() else {
+            return Ok(false);
+        };
+        self.edit_block(cols.start, w, |text, cols| {
+            if !cols.is_empty() {
+                text.drain(cols);
+            } else if cols.start < text.len() {
+                text.remove(cols.start);
+            }
+        })
+    }
+
+    /// Wraps the selection in `open` and `close`, keeping the wrapped text selected.
+    ///
+    /// Returns false if there is no selection.
+    pub fn surround<W: Write>(&mut self, open: &str, close: &str, w: &mut W) -> ReplResult<bool> {
+        let Some(Range { start, end }) = self.selection() else {
+            return Ok(false);
+        };
+        let len = open.chars().count();
+        self.mark = Some(start + len);
+        self.rewrite(w, |buf| {
+            buf.splice(end..end, close.chars());
+            buf.splice(start..start, open.chars());
+            end + len
+        })?;
+        Ok(true)
+    }
+
+    /// Finds the innermost `open`..`close` pair enclosing the cursor, returning the
+    /// indices of the opening and closing delimiters
+    pub fn enclosing(&self, open: &str, close: &str) -> Option<(usize, usize)> {
+        if open.is_empty() || close.is_empty() {
+            return None;
+        }
+        let buf: Vec<char> = self.iter().copied().collect();
+        let (open, close): (Vec<char>, Vec<char>) =
+            (open.chars().collect(), close.chars().collect());
+        let at = |i: usize, pat: &[char]| buf[i..].starts_with(pat);
+        let cursor = self.cursor();
+
+        let mut depth = 0;
+        let mut start = None;
+        for i in (0..cursor.saturating_sub(open.len() - 1)).rev() {
+            if at(i, &open) {
+                if depth == 0 {
+                    start = Some(i);
+                    break;
+                }
+                depth -= 1;
+            } else if open != close && i + close.len() <= cursor && at(i, &close) {
+                depth += 1;
+            }
+        }
+        let start = start?;
+
+        let mut depth = 0;
+        for i in cursor.max(start + open.len())..buf.len() {
+            if at(i, &close) {
+                if depth == 0 {
+                    return Some((start, i));
+                }
+                depth -= 1;
+            } else if open != close && at(i, &open) {
+                depth += 1;
+            }
+        }
+        None
+    }
+
+    /// Removes the innermost `open`..`close` pair enclosing the cursor.
+    ///
+    /// Returns false if the cursor isn't enclosed by the pair.
+    pub fn unsurround<W: Write>(&mut self, open: &str, close: &str, w: &mut W) -> ReplResult<bool> {
+        let Some((start, end)) = self.enclosing(open, close) else {
+            return Ok(false);
+        };
+        let (olen, clen) = (open.chars().count(), close.chars().count());
+        let cursor = self.cursor();
+        self.mark = None;
+        self.rewrite(w, |buf| {
+            buf.drain(end..end + clen);
+            buf.drain(start..start + olen);
+            cursor - olen
+        })?;
+        Ok(true)
+    }
+
+    /// Erases the word before the cursor, and the whitespace between them, or the line
+    /// break if the cursor is at the start of a line, returning them. See [WordChars].
+    pub fn erase_word<W: Write>(&mut self, w: &mut W) -> ReplResult<String> {
+        let cursor = self.cursor();
+        let len = word_len(self.head.iter().rev().copied(), self.words);
+        self.erase(cursor - len..cursor, w)
+    }
+
+    /// Erases the word after the cursor, and the whitespace between them, or the line break
+    /// if the cursor is at the end of a line, returning them. See [WordChars].
+    pub fn erase_word_forward<W: Write>(&mut self, w: &mut W) -> ReplResult<String> {
+        let cursor = self.cursor();
+        let len = word_len(self.tail.iter().copied(), self.words);
+        self.erase(cursor..cursor + len, w)
+    }
+
+    /// Rewrites the rest of the word under the cursor, or the next word, with `case`, like
+    /// [str::to_uppercase], and moves the cursor past it. Words are letters and digits.
+    pub fn recase_word<W: Write>(
+        &mut self,
+        case: impl FnOnce(&str) -> String,
+        w: &mut W,
+    ) -> ReplResult<()> {
+        let skip = self
+            .tail
+            .iter()
+            .take_while(|c| !c.is_alphanumeric())
+            .count();
+        let len = self
+            .tail
+            .iter()
+            .skip(skip)
+            .take_while(|c| c.is_alphanumeric())
+            .count();
+        let start = self.cursor() + skip;
+        let word: String = self.tail.range(skip..skip + len).collect();
+        let recased: Vec<char> = case(&word).chars().collect();
+        self.mark = None;
+        self.rewrite(w, |buf| {
+            let end = start + recased.len();
+            buf.splice(start..start + len, recased);
+            end
+        })
+    }
+
+    /// Erases the characters from the cursor to the end of the line, or the line break if
+    /// the cursor is already there, returning them
+    pub fn erase_line_end<W: Write>(&mut self, w: &mut W) -> ReplResult<String> {
+        let cursor = self.cursor();
+        let len = match self.tail.iter().position(is_newline) {
+            Some(0) => 1,
+            Some(n) => n,
+            None => self.tail.len(),
+        };
+        self.erase(cursor..cursor + len, w)
+    }
+
+    /// Erases the characters from the start of the line to the cursor, or the line break if
+    /// the cursor is already there, returning them
+    pub fn erase_line_start<W: Write>(&mut self, w: &mut W) -> ReplResult<String> {
+        let cursor = self.cursor();
+        let len = match self.head.iter().rev().position(is_newline) {
+            Some(0) => 1,
+            Some(n) => n,
+            None => self.head.len(),
+        };
+        self.erase(cursor - len..cursor, w)
+    }
+
+    /// Erases the characters from the cursor to the end of the buffer, line breaks and all,
+    /// returning them
+    pub fn erase_buffer_end<W: Write>(&mut self, w: &mut W) -> ReplResult<String> {
+        let cursor = self.cursor();
+        self.erase(cursor..self.len(), w)
+    }
+
+    /// Erases the characters from the start of the buffer to the cursor, line breaks and all,
+    /// returning them
+    pub fn erase_buffer_start<W: Write>(&mut self, w: &mut W) -> ReplResult<String> {
+        self.erase(0..self.cursor(), w)
+    }
+
+    /// Erases the given range of characters, placing the cursor where they were, and
+    /// returns them
+    pub fn erase<W: Write>(&mut self, range: Range<usize>, w: &mut W) -> ReplResult<String> {
+        if range.is_empty() {
+            return Ok(String::new());
+        }
+        let mut out = String::new();
+        self.rewrite(w, |buf| {
+            out = buf.drain(range.clone()).collect();
+            range.start
+        })?;
+        Ok(out)
     }
 
     /// Returns the number of characters in the buffer
@@ -216,6 +1835,16 @@ impl<'a> Editor<'a> {
         matches!(self.tail.front(), None | Some('\n'))
     }
 
+    /// Returns true if the cursor is on the first line of the buffer
+    pub fn on_first_line(&self) -> bool {
+        !self.head.contains(&'\n')
+    }
+
+    /// Returns true if the cursor is on the last line of the buffer
+    pub fn on_last_line(&self) -> bool {
+        !self.tail.contains(&'\n')
+    }
+
     /// Returns true if the character before the cursor is whitespace
     pub fn at_word_start(&self) -> bool {
         self.head
@@ -253,72 +1882,110 @@ impl<'a> Editor<'a> {
         }
     }
 
-    /// Moves the cursor back `steps` steps
-    pub fn cursor_back<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
-        let Some(c) = self.head.pop_back() else {
-            return Ok(());
-        };
+    /// Returns true if the buffer after the cursor starts with a given pattern
+    pub fn starts_with(&self, iter: impl Iterator<Item = char>) -> bool {
+        let mut tail = self.tail.iter();
+        for a in iter {
+            match tail.next() {
+                Some(b) if a == *b => continue,
+                _ => return false,
+            }
+        }
+        true
+    }
 
-        self.tail.push_front(c);
-        match c {
-            '\n' => {
-                queue!(w, MoveToPreviousLine(1))?;
+    /// Moves the cursor back over a grapheme cluster, like an emoji or a letter with its
+    /// accents
+    pub fn cursor_back<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        let from = self.offset();
+        let len = last_grapheme(self.head.iter().rev());
+        let text: String = self.head.drain(self.head.len() - len..).collect();
+        text.chars().rev().for_each(|c| self.tail.push_front(c));
+        self.line -= text.chars().filter(is_newline).count();
+        match text.as_str() {
+            "" => Ok(()),
+            // the cursor was at the start of the next line
+            "\n" if self.scrolled() => self.scroll(self.origin(self.line() + 1), w),
+            "\n" => {
+                queue!(
+                    w,
+                    MoveToPreviousLine((self.offset() / self.wrap() + 1) as u16)
+                )?;
                 self.print_head(w)
             }
-            _ => queue!(w, MoveLeft(1)).map_err(Into::into),
+            _ => Ok(move_along(from, self.offset(), self.wrap(), w)?),
         }
     }
 
-    /// Moves the cursor forward `steps` steps
+    /// Moves the cursor forward over a grapheme cluster
     pub fn cursor_forward<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
-        let Some(c) = self.tail.pop_front() else {
-            return Ok(());
-        };
-
-        self.head.push_back(c);
-        match c {
-            '\n' => {
+        let from = self.offset();
+        let len = first_grapheme(self.tail.iter());
+        let text: String = self.tail.drain(..len).collect();
+        self.head.extend(text.chars());
+        self.line += text.chars().filter(is_newline).count();
+        match text.as_str() {
+            "" => Ok(()),
+            // the cursor was on the last row of the line before
+            "\n" if self.scrolled() => self.scroll(self.origin(self.line()).saturating_sub(1), w),
+            "\n" => {
                 queue!(w, MoveToNextLine(1))?;
                 self.print_head(w)
             }
-            _ => queue!(w, MoveRight(1)).map_err(Into::into),
+            _ => Ok(move_along(from, self.offset(), self.wrap(), w)?),
         }
     }
 
     /// Moves the cursor up to the previous line, attempting to preserve relative offset
     pub fn cursor_up<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
-        // Calculates length of the current line
-        let mut len = self.head.len();
+        let column = self.goal_column();
         self.cursor_line_start(w)?;
-        len -= self.head.len();
-
         if self.at_start() {
             return Ok(());
         }
-
         self.cursor_back(w)?;
         self.cursor_line_start(w)?;
-
-        while 0 < len && !self.at_line_end() {
-            self.cursor_forward(w)?;
-            len -= 1;
-        }
-
-        Ok(())
+        self.seek_column(column, w)
     }
 
     /// Moves the cursor down to the next line, attempting to preserve relative offset
     pub fn cursor_down<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
-        let mut len = self.head.iter().rev().take_while(|&&c| c != '\n').count();
-
+        let column = self.goal_column();
         self.cursor_line_end(w)?;
+        if self.at_end() {
+            return Ok(());
+        }
         self.cursor_forward(w)?;
+        self.seek_column(column, w)
+    }
 
-        while 0 < len && !self.at_line_end() {
-            self.cursor_forward(w)?;
-            len -= 1;
+    /// Returns the column to move up or down to: the one the last move aimed for, if the
+    /// cursor is where it left it, so that passing through a short line doesn't lose it
+    fn goal_column(&self) -> usize {
+        match self.goal {
+            Some((cursor, column)) if cursor == self.cursor() => column,
+            _ => self
+                .head
+                .iter()
+                .rev()
+                .take_while(|&&c| c != '\n')
+                .map(char_width)
+                .sum(),
         }
+    }
 
+    /// Moves the cursor from the start of a line as near to `column` as the line reaches,
+    /// and remembers it as the goal
+    fn seek_column<W: Write>(&mut self, column: usize, w: &mut W) -> ReplResult<()> {
+        let mut at = 0;
+        while let Some(&c) = self.tail.front().filter(|&&c| c != '\n') {
+            at += char_width(&c);
+            if at > column {
+                break;
+            }
+            self.cursor_forward(w)?;
+        }
+        self.goal = Some((self.cursor(), column));
         Ok(())
     }
 
@@ -358,6 +2025,18 @@ impl<'a> Editor<'a> {
         Ok(())
     }
 
+    /// Moves the cursor to the given index in the buffer, or just past it if it's inside a
+    /// grapheme cluster
+    pub fn cursor_to<W: Write>(&mut self, index: usize, w: &mut W) -> ReplResult<()> {
+        while self.head.len() > index && !self.at_start() {
+            self.cursor_back(w)?
+        }
+        while self.head.len() < index && !self.at_end() {
+            self.cursor_forward(w)?
+        }
+        Ok(())
+    }
+
     /// Moves the cursor to the start of the buffer
     pub fn cursor_start<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
         while !self.at_start() {
@@ -1,45 +1,91 @@
-use crate::iter::chars::BadUnicode;
+use crate::iter::chars::Undecodable;
 
 /// Result type for Repline
 pub type ReplResult<T> = std::result::Result<T, Error>;
-/// Borrowed error (does not implement [Error](std::error::Error)!)
+/// Why a read ended without a line.
+///
+/// More variants may be added, so matches on it need a catch-all arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// User broke with Ctrl+C
     CtrlC(String),
     /// User broke with Ctrl+D
     CtrlD(String),
+    /// User pressed a key bound to [EditCommand::Action](crate::keymap::EditCommand::Action),
+    /// with its tag, and the unfinished line
+    Action(&'static str, String),
+    /// No key arrived in time, with the unfinished line
+    TimedOut(String),
     /// Invalid unicode codepoint
     BadUnicode(u32),
+    /// Input which isn't UTF-8, starting with this byte. See
+    /// [set_lossy_input](crate::Repline::set_lossy_input).
+    BadUtf8(u8),
     /// Error came from [std::io]
     IoFailure(std::io::Error),
     /// End of input
     EndOfInput,
+    /// A read or write was interrupted by a signal before it finished, and can be retried
+    Interrupted,
+    /// The terminal couldn't do what repline needs of it, like entering raw mode, which it
+    /// can't if it isn't a terminal at all
+    Terminal(std::io::Error),
 }
 
-impl std::error::Error for Error {}
+impl Error {
+    /// Returns the unfinished line the read ended with, if it ended with one, like by
+    /// [Ctrl+C](Self::CtrlC) or [Ctrl+D](Self::CtrlD)
+    pub fn into_line(self) -> Option<String> {
+        match self {
+            Error::CtrlC(line) | Error::CtrlD(line) | Error::TimedOut(line) => Some(line),
+            Error::Action(_, line) => Some(line),
+            _ => None,
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::IoFailure(e) | Error::Terminal(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::CtrlC(_) => write!(f, "Ctrl+C"),
             Error::CtrlD(_) => write!(f, "Ctrl+D"),
+            Error::Action(tag, _) => write!(f, "{tag}"),
+            Error::TimedOut(_) => write!(f, "Timed out"),
             Error::BadUnicode(u) => write!(f, "\\u{{{u:x}}} is not a valid unicode codepoint"),
+            Error::BadUtf8(b) => write!(f, "Input isn't UTF-8, from byte {b:#04x}"),
             Error::IoFailure(s) => write!(f, "{s}"),
             Error::EndOfInput => write!(f, "End of input"),
+            Error::Interrupted => write!(f, "Interrupted"),
+            Error::Terminal(e) => write!(f, "Terminal unsupported: {e}"),
         }
     }
 }
 
 impl From<std::io::Error> for Error {
     fn from(value: std::io::Error) -> Self {
-        Self::IoFailure(value)
+        match value.kind() {
+            std::io::ErrorKind::Interrupted => Self::Interrupted,
+            _ => Self::IoFailure(value),
+        }
     }
 }
 
-impl From<BadUnicode> for Error {
-    fn from(value: BadUnicode) -> Self {
-        let BadUnicode(code) = value;
-        Self::BadUnicode(code)
+impl From<Undecodable> for Error {
+    fn from(value: Undecodable) -> Self {
+        match value {
+            Undecodable::Io(e) => e.into(),
+            Undecodable::BadUtf8(byte) => Self::BadUtf8(byte),
+            Undecodable::BadUnicode(code) => Self::BadUnicode(code),
+        }
     }
 }
@@ -0,0 +1,116 @@
+//! Reads keys through crossterm's event reader, instead of decoding raw bytes from stdin.
+//!
+//! Windows consoles don't send escape sequences for keys unless asked to, and send some
+//! keys differently when they do, so there, keys are read as console events instead.
+
+use crate::{
+    keymap::{Key, Modifiers},
+    repline::Focus,
+};
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    read,
+};
+use std::io::Result;
+
+/// Reads the next key, or returns `None` if a `Ctrl+X` prefix isn't followed by a key
+/// which makes sense after it
+pub fn read_key() -> Result<Option<Key>> {
+    let key = loop {
+        if let Some(key) = decode_event(read()?) {
+            break key;
+        }
+    };
+    match key {
+        Key::Ctrl('x') => Ok(ctrl_x(read_key()?)),
+        key => Ok(Some(key)),
+    }
+}
+
+/// Awaits the next key from an [EventStream](crossterm::event::EventStream), like
+/// [read_key], failing with [EndOfInput](crate::Error::EndOfInput) if the stream ends
+#[cfg(feature = "tokio")]
+pub async fn next_key(
+    events: &mut crossterm::event::EventStream,
+) -> crate::error::ReplResult<Option<Key>> {
+    use futures_core::Stream;
+    use std::{future::poll_fn, pin::Pin};
+    let mut next = async || loop {
+        let event = poll_fn(|cx| Pin::new(&mut *events).poll_next(cx)).await;
+        let event = event.ok_or(crate::Error::EndOfInput)??;
+        if let Some(key) = decode_event(event) {
+            return Ok::<_, crate::Error>(key);
+        }
+    };
+    match next().await? {
+        Key::Ctrl('x') => Ok(ctrl_x(Some(next().await?))),
+        key => Ok(Some(key)),
+    }
+}
+
+/// Decodes a key press, focus change, or left click, skipping other events and keys
+/// repline doesn't understand
+fn decode_event(event: Event) -> Option<Key> {
+    match event {
+        Event::Key(event) if event.kind != KeyEventKind::Release => decode(event),
+        Event::FocusGained => Some(Key::Focus(Focus::Gained)),
+        Event::FocusLost => Some(Key::Focus(Focus::Lost)),
+        Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            row,
+            column,
+            ..
+        }) => Some(Key::Click(row, column)),
+        _ => None,
+    }
+}
+
+/// Decodes the key after a `Ctrl+X` prefix, for buffer commands, into the character it sends
+fn ctrl_x(key: Option<Key>) -> Option<Key> {
+    let c = match key? {
+        Key::Char(c) | Key::Alt(c) => c,
+        Key::Ctrl(c) => (c as u8 & 0x1f) as char,
+        Key::Enter => '\r',
+        _ => return None,
+    };
+    Some(Key::CtrlX(c))
+}
+
+/// Decodes a key event into the [Key] the same key sends as raw bytes
+fn decode(KeyEvent { code, modifiers, .. }: KeyEvent) -> Option<Key> {
+    let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+    let alt = modifiers.contains(KeyModifiers::ALT);
+    let chord = Modifiers { shift: modifiers.contains(KeyModifiers::SHIFT), alt, ctrl };
+    let key = match code {
+        KeyCode::Char(c) if ctrl => match c.to_ascii_lowercase() {
+            // Ctrl+H is Backspace in a terminal
+            'h' => Key::Backspace,
+            c @ 'a'..='z' => Key::Ctrl(c),
+            // Ctrl+\, Ctrl+], Ctrl+^, and Ctrl+_, which some terminals send as Ctrl+4..7
+            '4' | '\\' => Key::Ctrl('\\'),
+            '5' | ']' => Key::Ctrl(']'),
+            '6' | '^' => Key::Ctrl('^'),
+            '7' | '_' | '-' => Key::Ctrl('_'),
+            _ => return None,
+        },
+        KeyCode::Char(c) if alt => Key::Alt(c),
+        KeyCode::Char(c) => Key::Char(c),
+        KeyCode::Enter if alt => Key::Alt('\r'),
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Esc => Key::Esc,
+        KeyCode::Tab => Key::Tab,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Delete => Key::Delete(chord),
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::Up => Key::Up(chord),
+        KeyCode::Down => Key::Down(chord),
+        KeyCode::Left => Key::Left(chord),
+        KeyCode::Right => Key::Right(chord),
+        KeyCode::Home => Key::Home(chord),
+        KeyCode::End => Key::End(chord),
+        KeyCode::F(n) => Key::F(n),
+        _ => return None,
+    };
+    Some(key)
+}
@@ -0,0 +1,173 @@
+//! A C ABI for embedding repline in programs written in other languages, like linenoise.
+//!
+//! A [`repline`] is created with [repline_new], and freed with [repline_free]. Strings
+//! returned by repline are owned by the caller, and must be freed with [repline_free_string].
+//! All strings are UTF-8, and functions which receive a null pointer do nothing.
+//!
+//! Build a C library with `cargo rustc --release --features ffi --crate-type staticlib`
+//! (or `cdylib`), and include `include/repline.h`.
+#![allow(non_camel_case_types)]
+
+use crate::{error::Error, repline::Repline};
+use std::{
+    ffi::{CStr, CString, c_char, c_int},
+    io::Stdin,
+    panic::{AssertUnwindSafe, catch_unwind},
+    ptr,
+};
+
+/// The line was read successfully
+pub const REPLINE_OK: c_int = 0;
+/// The user pressed `Ctrl+C`
+pub const REPLINE_CTRL_C: c_int = 1;
+/// The user pressed `Ctrl+D`
+pub const REPLINE_CTRL_D: c_int = 2;
+/// Reading failed, and no line was returned
+pub const REPLINE_ERROR: c_int = -1;
+
+/// A [Repline] reading from stdin, which owns its prompts
+pub struct repline {
+    // declared before the prompts it borrows, so that it's dropped first
+    rl: Repline<'static, Stdin>,
+    _prompts: [Box<str>; 3],
+}
+
+/// Copies a C string, if it's valid UTF-8
+///
+/// # Safety
+/// `s` must be null, or point to a nul-terminated string.
+unsafe fn to_owned(s: *const c_char) -> Option<Box<str>> {
+    if s.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(s) }.to_str().ok().map(Into::into)
+}
+
+/// Gives ownership of `s` to the caller, as a C string
+fn to_c(s: String) -> *mut c_char {
+    CString::new(s).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Constructs a [`repline`] with the given color, begin, and again prompts.
+///
+/// Returns null if any prompt is null or not valid UTF-8.
+///
+/// # Safety
+/// Each prompt must be null, or point to a nul-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn repline_new(
+    color: *const c_char,
+    begin: *const c_char,
+    again: *const c_char,
+) -> *mut repline {
+    let prompts = unsafe { (to_owned(color), to_owned(begin), to_owned(again)) };
+    let (Some(color), Some(begin), Some(again)) = prompts else {
+        return ptr::null_mut();
+    };
+    // The prompts live on the heap until the Repline is dropped, so they outlive it
+    let extend = |s: &str| -> &'static str { unsafe { &*(s as *const str) } };
+    let rl = Repline::new(extend(&color), extend(&begin), extend(&again));
+    Box::into_raw(Box::new(repline { rl, _prompts: [color, begin, again] }))
+}
+
+/// Frees a [`repline`]
+///
+/// # Safety
+/// `rl` must be null, or have come from [repline_new], and must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn repline_free(rl: *mut repline) {
+    if !rl.is_null() {
+        drop(unsafe { Box::from_raw(rl) })
+    }
+}
+
+/// Frees a string returned by repline
+///
+/// # Safety
+/// `s` must be null, or have been returned by repline, and must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn repline_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) })
+    }
+}
+
+/// Reads a line, and stores one of the `REPLINE_*` status codes in `status` (if not null).
+///
+/// On `Ctrl+C` and `Ctrl+D`, the unfinished line is returned. On error, returns null.
+/// The terminal is returned to its previous mode before this returns, even on error.
+///
+/// # Safety
+/// `rl` must be null, or a valid [`repline`]. `status` must be null, or valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn repline_read(rl: *mut repline, status: *mut c_int) -> *mut c_char {
+    let result = match unsafe { rl.as_mut() } {
+        // unwinding drops the raw mode guard, so the terminal is restored on panic too
+        Some(rl) => catch_unwind(AssertUnwindSafe(|| rl.rl.read())).ok(),
+        None => None,
+    };
+    let (code, line) = match result {
+        Some(Ok(line)) => (REPLINE_OK, Some(line)),
+        Some(Err(Error::CtrlC(line))) => (REPLINE_CTRL_C, Some(line)),
+        Some(Err(Error::CtrlD(line))) => (REPLINE_CTRL_D, Some(line)),
+        Some(Err(_)) | None => (REPLINE_ERROR, None),
+    };
+    if let Some(status) = unsafe { status.as_mut() } {
+        *status = code;
+    }
+    line.map_or(ptr::null_mut(), to_c)
+}
+
+/// Adds the last line read to the history, and clears it
+///
+/// # Safety
+/// `rl` must be null, or a valid [`repline`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn repline_accept(rl: *mut repline) {
+    if let Some(rl) = unsafe { rl.as_mut() } {
+        rl.rl.accept()
+    }
+}
+
+/// Clears the last line read, without adding it to the history
+///
+/// # Safety
+/// `rl` must be null, or a valid [`repline`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn repline_deny(rl: *mut repline) {
+    if let Some(rl) = unsafe { rl.as_mut() } {
+        rl.rl.deny()
+    }
+}
+
+/// Adds `line` to the history
+///
+/// # Safety
+/// `rl` must be null, or a valid [`repline`]. `line` must be null, or point to a
+/// nul-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn repline_history_add(rl: *mut repline, line: *const c_char) {
+    if let (Some(rl), Some(line)) = unsafe { (rl.as_mut(), to_owned(line)) } {
+        rl.rl.add_history(line)
+    }
+}
+
+/// Returns the number of lines in the history
+///
+/// # Safety
+/// `rl` must be null, or a valid [`repline`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn repline_history_len(rl: *const repline) -> usize {
+    unsafe { rl.as_ref() }.map_or(0, |rl| rl.rl.history().len())
+}
+
+/// Returns a copy of the history entry at `index`, where the last is the most relevant,
+/// or null if there is no such entry
+///
+/// # Safety
+/// `rl` must be null, or a valid [`repline`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn repline_history_get(rl: *const repline, index: usize) -> *mut c_char {
+    let entry = unsafe { rl.as_ref() }.and_then(|rl| rl.rl.history().get(index));
+    entry.map_or(ptr::null_mut(), |entry| to_c(entry.to_owned()))
+}
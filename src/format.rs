@@ -0,0 +1,45 @@
+//! Format on demand: a [Formatter] rewrites the buffer, like a pretty-printer for JSON or SQL.
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// Rewrites the whole buffer, returning the new text or a message explaining why it can't.
+///
+/// Implemented for all `FnMut(&str) -> Result<String, String>`, and for external commands
+/// ([Command]), which are given the buffer on stdin and print the result on stdout.
+pub trait Formatter {
+    fn format(&mut self, text: &str) -> Result<String, String>;
+}
+
+impl<F: FnMut(&str) -> Result<String, String>> Formatter for F {
+    fn format(&mut self, text: &str) -> Result<String, String> {
+        self(text)
+    }
+}
+
+impl Formatter for Command {
+    /// Runs the command, failing with its stderr if it exits unsuccessfully
+    fn format(&mut self, text: &str) -> Result<String, String> {
+        let mut child = (self
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped()))
+        .spawn()
+        .map_err(|e| e.to_string())?;
+        let stdin = child.stdin.take();
+        // written from another thread, so that a full stdout pipe can't deadlock the two
+        let output = std::thread::scope(|s| {
+            if let Some(mut stdin) = stdin {
+                s.spawn(move || stdin.write_all(text.as_bytes()));
+            }
+            child.wait_with_output()
+        });
+        let output = output.map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        String::from_utf8(output.stdout).map_err(|e| e.to_string())
+    }
+}
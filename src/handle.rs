@@ -0,0 +1,78 @@
+//! A read-only view of a [Repline](crate::Repline) for other threads: a [ReplineHandle]
+//! follows the buffer, the cursor, and the history as they change, so that a background
+//! thread can compute suggestions or gather telemetry while the read goes on.
+//!
+//! ```no_run
+//! # use repline::Repline;
+//! let mut rl = Repline::new("", "> ", ". ");
+//! let handle = rl.handle();
+//! std::thread::spawn(move || loop {
+//!     std::thread::sleep(std::time::Duration::from_secs(1));
+//!     eprintln!("{} bytes typed, {} in history", handle.text().len(), handle.history().len());
+//! });
+//! while rl.read().is_ok() {
+//!     rl.accept();
+//! }
+//! ```
+
+use crate::history::Entry;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// A cheap, cloneable view of a [Repline](crate::Repline)'s state, which can be sent to and
+/// shared between threads.
+///
+/// The buffer and cursor are updated after each key, and the history at the start of each
+/// read. Every method copies what it returns, so that the read is never held up for longer
+/// than the copy takes.
+#[derive(Clone, Debug, Default)]
+pub struct ReplineHandle(Arc<RwLock<View>>);
+
+#[derive(Debug, Default)]
+struct View {
+    text: String,          // the buffer
+    cursor: usize,         // the byte index of the cursor in the buffer
+    history: Arc<[Entry]>, // the history, as of the start of the read
+}
+
+impl ReplineHandle {
+    fn read(&self) -> RwLockReadGuard<'_, View> {
+        self.0.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, View> {
+        self.0.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the text in the buffer
+    pub fn text(&self) -> String {
+        self.read().text.clone()
+    }
+
+    /// Returns the byte index of the cursor in the [text](Self::text)
+    pub fn cursor(&self) -> usize {
+        self.read().cursor
+    }
+
+    /// Returns the text in the buffer and the byte index of the cursor in it, as of the same
+    /// moment
+    pub fn snapshot(&self) -> (String, usize) {
+        let view = self.read();
+        (view.text.clone(), view.cursor)
+    }
+
+    /// Returns the history entries, ordered from least to most relevant
+    pub fn history(&self) -> Arc<[Entry]> {
+        self.read().history.clone()
+    }
+
+    /// Updates the buffer and the cursor
+    pub(crate) fn set_buffer(&self, text: String, cursor: usize) {
+        let mut view = self.write();
+        (view.text, view.cursor) = (text, cursor);
+    }
+
+    /// Updates the history
+    pub(crate) fn set_history(&self, history: Arc<[Entry]>) {
+        self.write().history = history;
+    }
+}
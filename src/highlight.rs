@@ -0,0 +1,55 @@
+//! Syntax highlighting: a [Highlighter] styles spans of the buffer as the user types.
+
+use crossterm::style::ContentStyle;
+use std::ops::Range;
+
+/// Styles spans of the buffer, like a language's keywords, strings, and numbers.
+///
+/// Implemented for all `FnMut(&str) -> Vec<(Range<usize>, ContentStyle)>`.
+pub trait Highlighter {
+    /// Returns the byte ranges of `line` to style, and their styles.
+    ///
+    /// Where spans overlap, the later span's colors and attributes take precedence.
+    /// [Diagnostics](crate::diagnostics::Diagnostics) are drawn over the highlighting.
+    fn highlight(&mut self, line: &str) -> Vec<(Range<usize>, ContentStyle)>;
+}
+
+impl<F: FnMut(&str) -> Vec<(Range<usize>, ContentStyle)>> Highlighter for F {
+    fn highlight(&mut self, line: &str) -> Vec<(Range<usize>, ContentStyle)> {
+        self(line)
+    }
+}
+
+/// Runs a [Highlighter] whenever the buffer changes
+pub(crate) struct Highlight<'a> {
+    highlighter: Box<dyn Highlighter + 'a>,
+    text: Option<String>, // the buffer the highlighter last saw
+}
+
+impl<'a> Highlight<'a> {
+    pub fn new(highlighter: impl Highlighter + 'a) -> Self {
+        Self { highlighter: Box::new(highlighter), text: None }
+    }
+
+    /// Runs the highlighter on `text`, if it changed, and returns its spans as character
+    /// ranges
+    pub fn highlight(&mut self, text: String) -> Option<Vec<(Range<usize>, ContentStyle)>> {
+        if self.text.as_ref() == Some(&text) {
+            return None;
+        }
+        let chars = |at: usize| text.get(..at).map(|s| s.chars().count());
+        let spans = (self.highlighter.highlight(&text).into_iter())
+            .filter_map(|(span, style)| Some((chars(span.start)?..chars(span.end)?, style)))
+            .collect();
+        self.text = Some(text);
+        Some(spans)
+    }
+}
+
+impl std::fmt::Debug for Highlight<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Highlight")
+            .field("text", &self.text)
+            .finish_non_exhaustive()
+    }
+}
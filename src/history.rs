@@ -0,0 +1,517 @@
+//! Previously entered lines, recalled with the arrow keys.
+//!
+//! History files hold one [Entry] per line, as tab-separated fields: the time it was last
+//! used (in seconds since the Unix epoch), its number of uses, followed by `:` and its exit
+//! status if it has one, its context, and its text. Backslashes, tabs, and newlines in the
+//! context and text are escaped with a backslash.
+//!
+//! With the `serde` feature, the history can also be [exported](History::export_json) to and
+//! [imported](History::import_json) from JSON, for auditing, or syncing with other tools.
+
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+/// How [History] orders its entries for recall
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Ranking {
+    /// The most recently used entry is recalled first
+    #[default]
+    Recency,
+    /// Entries are ranked by how often and how recently they were used
+    Frecency,
+}
+
+/// How [History::load] and [History::merge] resolve an entry which is already in the
+/// [History], from the same context and with the same words
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Conflict {
+    /// The entries become one, with the uses of whichever was used more, used at the later
+    /// time of the two
+    #[default]
+    Combine,
+    /// The entry already in the history is kept as it is, and only new entries are added
+    Union,
+    /// Whichever entry was used more recently is kept as it is
+    NewestWins,
+}
+
+/// Which way to search the [History] from the entry being recalled
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+    /// Toward older entries, like `Up`
+    Backward,
+    /// Toward newer entries, like `Down`
+    Forward,
+}
+
+/// A previously entered line
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Entry {
+    pub text: String,
+    /// The number of times this entry was accepted
+    pub uses: usize,
+    /// When this entry was last accepted
+    pub used: SystemTime,
+    /// The context (like a directory or connection) this entry was accepted in
+    pub context: Option<String>,
+    /// The exit status of the command this entry ran, as of its last use, if it was given
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub status: Option<i32>,
+}
+
+/// What's known about a line once it's been run, recorded in its [Entry] by
+/// [accept_with_meta](crate::Repline::accept_with_meta)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Meta {
+    /// The exit status of the command the line ran
+    pub status: Option<i32>,
+}
+
+impl Entry {
+    /// Scores the entry by its number of uses, weighted by how recently it was used
+    pub fn frecency(&self, now: SystemTime) -> f64 {
+        let age = now.duration_since(self.used).unwrap_or_default().as_secs();
+        let weight = match age {
+            0..3600 => 4.0,
+            3600..86400 => 2.0,
+            86400..604800 => 1.0,
+            _ => 0.5,
+        };
+        self.uses as f64 * weight
+    }
+
+    /// Writes the entry as a line of a history file
+    pub fn write_to(&self, mut w: impl Write) -> io::Result<()> {
+        let used = self
+            .used
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        let context = escape(self.context.as_deref().unwrap_or_default());
+        let status = self
+            .status
+            .map(|status| format!(":{status}"))
+            .unwrap_or_default();
+        let (used, uses, text) = (used.as_secs(), self.uses, escape(&self.text));
+        writeln!(w, "{used}\t{uses}{status}\t{context}\t{text}")
+    }
+
+    /// Parses a line of a history file, returning `None` if it's malformed
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(4, '\t');
+        let used = SystemTime::UNIX_EPOCH + Duration::from_secs(fields.next()?.parse().ok()?);
+        let uses = fields.next()?;
+        let (uses, status) = match uses.split_once(':') {
+            Some((uses, status)) => (uses, Some(status.parse().ok()?)),
+            None => (uses, None),
+        };
+        let uses = uses.parse().ok()?;
+        let context = Some(unescape(fields.next()?)).filter(|c| !c.is_empty());
+        Some(Self { text: unescape(fields.next()?), uses, used, context, status })
+    }
+}
+
+/// Previously entered lines, ordered from least to most relevant
+#[derive(Clone, Debug)]
+pub struct History {
+    entries: VecDeque<Entry>,
+    cap: usize,
+    ranking: Ranking,
+    conflict: Conflict,
+    context: Option<String>, // the context new entries are recorded in
+    by_context: bool,        // whether entries from the current context are recalled first
+}
+
+impl History {
+    /// Constructs an empty [History] which holds up to `cap` entries
+    pub fn new(cap: usize) -> Self {
+        Self {
+            entries: Default::default(),
+            cap,
+            ranking: Ranking::Recency,
+            conflict: Conflict::Combine,
+            context: None,
+            by_context: false,
+        }
+    }
+
+    /// Returns the number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns an iterator over the entries, from least to most relevant
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    /// Returns the text of the entry at `index`, where the last entry is the most relevant
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(|e| e.text.as_str())
+    }
+
+    /// Returns the entry at `index`, where the last entry is the most relevant
+    pub fn entry(&self, index: usize) -> Option<&Entry> {
+        self.entries.get(index)
+    }
+
+    /// Removes and returns the entry at `index`
+    pub fn remove(&mut self, index: usize) -> Option<Entry> {
+        self.entries.remove(index)
+    }
+
+    /// Removes every entry `keep` rejects, like those containing a password
+    pub fn retain(&mut self, keep: impl FnMut(&Entry) -> bool) {
+        self.entries.retain(keep)
+    }
+
+    /// Removes every entry
+    pub fn clear(&mut self) {
+        self.entries.clear()
+    }
+
+    /// Replaces the text of the entry at `index`
+    pub(crate) fn replace(&mut self, index: usize, text: String) {
+        if let Some(entry) = self.entries.get_mut(index) {
+            entry.text = text
+        }
+    }
+
+    /// Returns how entries are ordered for recall
+    pub fn ranking(&self) -> Ranking {
+        self.ranking
+    }
+
+    /// Sets how entries are ordered for recall
+    pub fn set_ranking(&mut self, ranking: Ranking) {
+        self.ranking = ranking;
+        self.rank();
+    }
+
+    /// Returns the number of entries the history holds, past which the least relevant are
+    /// dropped
+    pub fn cap(&self) -> usize {
+        self.cap
+    }
+
+    /// Sets the number of entries the history holds, dropping the least relevant entries
+    /// past it
+    pub fn set_cap(&mut self, cap: usize) {
+        self.cap = cap;
+        self.trim();
+    }
+
+    /// Returns how entries being merged into the history are resolved with those in it
+    pub fn conflict(&self) -> Conflict {
+        self.conflict
+    }
+
+    /// Sets how entries being merged into the history are resolved with those in it
+    pub fn set_conflict(&mut self, conflict: Conflict) {
+        self.conflict = conflict
+    }
+
+    /// Sets the context (like a directory or connection) new entries are recorded in
+    pub fn set_context(&mut self, context: Option<String>) {
+        self.context = context;
+        self.rank();
+    }
+
+    /// Returns the context new entries are recorded in
+    pub fn context(&self) -> Option<&str> {
+        self.context.as_deref()
+    }
+
+    /// Returns whether entries from the current context are recalled before all others
+    pub fn by_context(&self) -> bool {
+        self.by_context
+    }
+
+    /// Sets whether entries from the current context are recalled before all others
+    pub fn set_by_context(&mut self, by_context: bool) {
+        self.by_context = by_context;
+        self.rank();
+    }
+
+    /// Records a use of `text`, merging it with any entry from the same context
+    /// containing the same words, and returns a copy of the merged entry
+    pub fn add(&mut self, text: String) -> Entry {
+        self.add_with_meta(text, Meta::default())
+    }
+
+    /// Records a use of `text` like [add](Self::add), with what's known about it once it's
+    /// been run
+    pub fn add_with_meta(&mut self, text: String, meta: Meta) -> Entry {
+        let uses = self.remove_words_of(&text).map_or(0, |e| e.uses);
+        let (context, Meta { status }) = (self.context.clone(), meta);
+        let (text, used) = (trim(text), SystemTime::now());
+        let entry = Entry { text, uses: uses + 1, used, context, status };
+        self.push(entry.clone());
+        self.rank();
+        entry
+    }
+
+    /// Reads the entries of the history file at `path`, [merging](Self::merge) them with the
+    /// current entries. Malformed lines are skipped.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut entries = vec![];
+        for line in BufReader::new(File::open(path)?).lines() {
+            entries.extend(Entry::parse(&line?));
+        }
+        self.merge(entries);
+        Ok(())
+    }
+
+    /// Merges `entries`, like those of another history, with the current entries.
+    ///
+    /// An entry from the same context as one already in the history, with the same words,
+    /// is resolved with it according to the [Conflict] policy. Once merged, only the most
+    /// relevant entries up to the [cap](Self::cap) are kept.
+    pub fn merge(&mut self, entries: impl IntoIterator<Item = Entry>) {
+        for mut entry in entries {
+            let Self { entries, conflict, .. } = self;
+            let same = entries
+                .iter_mut()
+                .find(|e| e.context == entry.context && same_words(&e.text, &entry.text));
+            let Some(old) = same else {
+                entries.push_back(entry);
+                continue;
+            };
+            match conflict {
+                Conflict::Combine => {
+                    entry.uses = entry.uses.max(old.uses);
+                    entry.used = entry.used.max(old.used);
+                    *old = entry;
+                }
+                Conflict::Union => {}
+                Conflict::NewestWins if entry.used > old.used => *old = entry,
+                Conflict::NewestWins => {}
+            }
+        }
+        self.rank();
+        self.trim();
+    }
+
+    /// Replaces every entry with `entries`, given from least to most relevant
+    #[cfg(feature = "serde")]
+    pub(crate) fn set_entries(&mut self, entries: impl IntoIterator<Item = Entry>) {
+        self.entries.clear();
+        for entry in entries {
+            self.push(entry);
+        }
+        self.rank();
+    }
+
+    /// Writes the entries to `w` as a JSON array, from least to most relevant.
+    ///
+    /// Entries which were never used aren't exported, like with [save](Self::save).
+    #[cfg(feature = "serde")]
+    pub fn export_json(&self, w: impl Write) -> io::Result<()> {
+        let entries: Vec<&Entry> = self.entries.iter().filter(|e| e.uses > 0).collect();
+        Ok(serde_json::to_writer(w, &entries)?)
+    }
+
+    /// Reads a JSON array of entries, like one [exported](Self::export_json), from `r`,
+    /// [merging](Self::merge) them with the current entries
+    #[cfg(feature = "serde")]
+    pub fn import_json(&mut self, r: impl io::Read) -> io::Result<()> {
+        let entries: Vec<Entry> = serde_json::from_reader(r)?;
+        self.merge(entries);
+        Ok(())
+    }
+
+    /// Writes the entries to a history file at `path`, replacing its contents.
+    ///
+    /// Entries which were never used aren't saved.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(path)?;
+        // sessions appending to the file wait until it's written
+        locked(&file, true, |file| {
+            file.set_len(0)?;
+            let mut file = BufWriter::new(file);
+            for entry in self.entries.iter().filter(|e| e.uses > 0) {
+                entry.write_to(&mut file)?;
+            }
+            file.flush()
+        })
+    }
+
+    fn push(&mut self, entry: Entry) {
+        self.entries.push_back(entry);
+        self.trim();
+    }
+
+    /// Drops the least relevant entries past the cap
+    fn trim(&mut self) {
+        while self.entries.len() > self.cap {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Removes the entry from the current context containing the same words as `text`,
+    /// if there is one
+    fn remove_words_of(&mut self, text: &str) -> Option<Entry> {
+        let Self { entries, context, .. } = self;
+        let idx = entries
+            .iter()
+            .position(|e| e.context == *context && same_words(&e.text, text))?;
+        entries.remove(idx)
+    }
+
+    /// Orders the entries according to the [Ranking], and the current context
+    fn rank(&mut self) {
+        let Self { entries, ranking, context, by_context, .. } = self;
+        let now = SystemTime::now();
+        let local = |e: &Entry| *by_context && e.context == *context;
+        entries.make_contiguous().sort_by(|a, b| {
+            let order = local(a).cmp(&local(b));
+            order.then_with(|| match ranking {
+                Ranking::Recency => a.used.cmp(&b.used),
+                Ranking::Frecency => (a.frecency(now), a.used)
+                    .partial_cmp(&(b.frecency(now), b.used))
+                    .unwrap_or(std::cmp::Ordering::Equal),
+            })
+        });
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(200)
+    }
+}
+
+/// A history file which entries are appended to as they're used, and which other sessions
+/// may be appending to at the same time.
+///
+/// Each entry is appended whole, while holding an advisory lock on the file, so that the
+/// entries of concurrent sessions don't interleave.
+#[derive(Debug)]
+pub(crate) struct HistoryFile {
+    file: File,
+    read: u64, // the length of the file as of the last reload, or append if nothing came between
+}
+
+impl HistoryFile {
+    /// Opens the history file at `path`, creating it if needed. Entries already in the file
+    /// aren't [reloaded](Self::reload).
+    pub(crate) fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        let read = file.metadata()?.len();
+        Ok(Self { file, read })
+    }
+
+    /// Appends `entry` to the file
+    pub(crate) fn append(&mut self, entry: &Entry) -> io::Result<()> {
+        let mut line = vec![];
+        entry.write_to(&mut line)?;
+        let Self { file, read } = self;
+        locked(file, true, |mut file| {
+            let len = file.metadata()?.len();
+            file.write_all(&line)?;
+            // only this session's entry is new, so there's nothing to reload
+            if len == *read {
+                *read = len + line.len() as u64;
+            }
+            Ok(())
+        })
+    }
+
+    /// Reads the entries appended to the file since it was last reloaded, by this session or
+    /// any other. Malformed lines are skipped.
+    pub(crate) fn reload(&mut self) -> io::Result<Vec<Entry>> {
+        let Self { file, read } = self;
+        let mut text = String::new();
+        locked(file, false, |mut file| {
+            // another session saved over the file, so all of it is new
+            if file.metadata()?.len() < *read {
+                *read = 0;
+            }
+            file.seek(SeekFrom::Start(*read))?;
+            *read += file.read_to_string(&mut text)? as u64;
+            Ok(())
+        })?;
+        Ok(text.lines().filter_map(Entry::parse).collect())
+    }
+}
+
+/// Runs `f` while holding an advisory lock on `file`, which is `exclusive` for writing
+fn locked<T>(
+    file: &File,
+    exclusive: bool,
+    f: impl FnOnce(&File) -> io::Result<T>,
+) -> io::Result<T> {
+    match exclusive {
+        true => file.lock()?,
+        false => file.lock_shared()?,
+    }
+    let out = f(file);
+    file.unlock()?;
+    out
+}
+
+/// Escapes backslashes, tabs, and newlines, so that `text` fits in a field of a history file
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Reverses [escape]
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('t') => out.push('\t'),
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some(c) => out.push(c),
+                None => out.push('\\'),
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Removes trailing whitespace from an entry
+fn trim(mut text: String) -> String {
+    text.truncate(text.trim_end().len());
+    text
+}
+
+/// Returns true if `a` and `b` contain the same words, ignoring differences in whitespace
+fn same_words(a: &str, b: &str) -> bool {
+    a.split_whitespace().eq(b.split_whitespace())
+}
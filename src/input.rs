@@ -0,0 +1,62 @@
+//! Sources of input, which can be read one after another.
+//!
+//! A [Repline](crate::Repline) reads keys from any [Read]er. To read a startup script before
+//! falling through to the terminal, chain them with [then](InputSource::then):
+//!
+//! ```no_run
+//! use repline::{Repline, input::InputSource};
+//! use std::{fs::File, io::stdin};
+//!
+//! let input = File::open("startup.txt")?.then(stdin());
+//! let mut rl = Repline::with_input(input, "", "> ", ". ");
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Mid-session, a source can be read before the rest of the input, like for a `:source file`
+//! command, with [push_source](crate::Repline::push_source).
+
+use std::io::{Read, Result};
+
+/// Something keys can be read from, like a file, a script, or the terminal.
+///
+/// Every [Read]er is one.
+pub trait InputSource: Read {
+    /// Reads all of `self`, then `next`. `self` is dropped once it runs out.
+    fn then<S: Read>(self, next: S) -> Chain<Self, S>
+    where Self: Sized {
+        Chain { first: Some(self), next }
+    }
+}
+
+impl<R: Read> InputSource for R {}
+
+/// Reads one source, then another. See [InputSource::then].
+#[derive(Debug)]
+pub struct Chain<A, B> {
+    first: Option<A>,
+    next: B,
+}
+
+impl<A, B> Chain<A, B> {
+    /// Whether the first source is still being read
+    pub fn in_first(&self) -> bool {
+        self.first.is_some()
+    }
+
+    /// Gets the source read once the first runs out
+    pub fn next_mut(&mut self) -> &mut B {
+        &mut self.next
+    }
+}
+
+impl<A: Read, B: Read> Read for Chain<A, B> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if let Some(first) = &mut self.first {
+            match first.read(buf)? {
+                0 if !buf.is_empty() => self.first = None,
+                read => return Ok(read),
+            }
+        }
+        self.next.read(buf)
+    }
+}
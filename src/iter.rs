@@ -1,74 +1,91 @@
 //! Shmancy iterator adapters
 
 pub use chars::Chars;
-pub use flatten::Flatten;
 
 pub mod chars {
-    //! Converts an <code>[Iterator]<Item = [u8]></code> into an
-    //! <code>[Iterator]<Item = [Result]<[char], [BadUnicode]>></code>
+    //! Converts an <code>[Iterator]<Item = [io::Result]<[u8]>></code>, like the
+    //! [Bytes](std::io::Bytes) of a reader, into an
+    //! <code>[Iterator]<Item = [Result]<[char], [Undecodable]>></code>
 
-    /// Invalid unicode codepoint found when iterating over [Chars]
-    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-    pub struct BadUnicode(pub u32);
+    use std::io;
 
-    impl std::error::Error for BadUnicode {}
+    /// Why [Chars] couldn't decode a character
+    #[derive(Debug)]
+    pub enum Undecodable {
+        /// Reading the bytes failed
+        Io(io::Error),
+        /// A byte sequence which isn't UTF-8, starting with this byte
+        BadUtf8(u8),
+        /// Invalid unicode codepoint
+        BadUnicode(u32),
+    }
+
+    impl std::error::Error for Undecodable {}
 
-    impl std::fmt::Display for BadUnicode {
+    impl std::fmt::Display for Undecodable {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            let Self(code) = self;
-            write!(f, "Bad unicode: {code}")
+            match self {
+                Self::Io(e) => write!(f, "{e}"),
+                Self::BadUtf8(byte) => write!(f, "Bad UTF-8: {byte:#04x}"),
+                Self::BadUnicode(code) => write!(f, "Bad unicode: {code}"),
+            }
         }
     }
 
-    /// Converts an <code>[Iterator]<Item = [u8]></code> into an
-    /// <code>[Iterator]<Item = [char]></code>
+    /// Converts an <code>[Iterator]<Item = [io::Result]<[u8]>></code> into an
+    /// <code>[Iterator]<Item = [char]></code>, passing on errors rather than ending at them
     #[derive(Clone, Debug)]
-    pub struct Chars<I: Iterator<Item = u8>>(pub I);
+    pub struct Chars<I: Iterator<Item = io::Result<u8>>> {
+        bytes: I,
+        held: Option<u8>, // a byte read past the end of a malformed sequence, to decode next
+    }
 
-    impl<I: Iterator<Item = u8>> Iterator for Chars<I> {
-        type Item = Result<char, BadUnicode>;
+    impl<I: Iterator<Item = io::Result<u8>>> Chars<I> {
+        /// Decodes `bytes`
+        pub fn new(bytes: I) -> Self {
+            Self { bytes, held: None }
+        }
+
+        /// Gets the next byte, or `None` at the end of input
+        fn byte(&mut self) -> Option<Result<u8, Undecodable>> {
+            match self.held.take() {
+                Some(byte) => Some(Ok(byte)),
+                None => Some(self.bytes.next()?.map_err(Undecodable::Io)),
+            }
+        }
+    }
+
+    impl<I: Iterator<Item = io::Result<u8>>> Iterator for Chars<I> {
+        type Item = Result<char, Undecodable>;
 
         fn next(&mut self) -> Option<Self::Item> {
-            let Self(bytes) = self;
-            let start = bytes.next()? as u32;
+            let lead = match self.byte()? {
+                Ok(byte) => byte,
+                Err(e) => return Some(Err(e)),
+            };
+            let start = lead as u32;
             let (mut out, count) = match start {
                 start if start & 0x80 == 0x00 => (start, 0), // ASCII valid range
                 start if start & 0xe0 == 0xc0 => (start & 0x1f, 1), // 1 continuation byte
                 start if start & 0xf0 == 0xe0 => (start & 0x0f, 2), // 2 continuation bytes
                 start if start & 0xf8 == 0xf0 => (start & 0x07, 3), // 3 continuation bytes
-                _ => return None,
+                _ => return Some(Err(Undecodable::BadUtf8(lead))),
             };
             for _ in 0..count {
-                let cont = bytes.next()? as u32;
+                let cont = match self.byte() {
+                    Some(Ok(byte)) => byte,
+                    Some(Err(e)) => return Some(Err(e)),
+                    // cut off by the end of input
+                    None => return Some(Err(Undecodable::BadUtf8(lead))),
+                };
                 if cont & 0xc0 != 0x80 {
-                    return None;
+                    // it may start the next character, like an escape
+                    self.held = Some(cont);
+                    return Some(Err(Undecodable::BadUtf8(lead)));
                 }
-                out = (out << 6) | (cont & 0x3f);
+                out = (out << 6) | (cont as u32 & 0x3f);
             }
-            Some(char::from_u32(out).ok_or(BadUnicode(out)))
-        }
-    }
-}
-pub mod flatten {
-    //! Flattens an [Iterator] returning [`Result<T, E>`](Result) or [`Option<T>`](Option)
-    //! into a *non-[FusedIterator](std::iter::FusedIterator)* over `T`
-
-    /// Flattens an [Iterator] returning [`Result<T, E>`](Result) or [`Option<T>`](Option)
-    /// into a *non-[FusedIterator](std::iter::FusedIterator)* over `T`
-    #[derive(Clone, Debug)]
-    pub struct Flatten<T, I: Iterator<Item = T>>(pub I);
-
-    impl<T, E, I: Iterator<Item = Result<T, E>>> Iterator for Flatten<Result<T, E>, I> {
-        type Item = T;
-        fn next(&mut self) -> Option<Self::Item> {
-            self.0.next()?.ok()
-        }
-    }
-
-    impl<T, I: Iterator<Item = Option<T>>> Iterator for Flatten<Option<T>, I> {
-        type Item = T;
-        fn next(&mut self) -> Option<Self::Item> {
-            self.0.next()?
+            Some(char::from_u32(out).ok_or(Undecodable::BadUnicode(out)))
         }
     }
 }
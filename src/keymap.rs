@@ -0,0 +1,683 @@
+//! Key bindings: a [Keymap] maps each decoded [Key] to the [EditCommand] it runs.
+//!
+//! Keys are named like `Ctrl+A`, `Alt+Enter`, `Shift+Up`, `Ctrl+X Ctrl+E`, or `F5`, through
+//! [Display](std::fmt::Display) and [FromStr]. With the `serde` feature, a keymap can be
+//! saved and loaded as TOML.
+
+use crate::{history::Direction, repline::Focus};
+use std::{collections::HashMap, fmt, str::FromStr};
+
+/// A key (or chord) pressed by the user, decoded from the terminal's input
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    /// A printable character
+    Char(char),
+    /// A letter pressed with `Ctrl`, in lower case, like `Ctrl('c')`, or one of `\]^_`
+    Ctrl(char),
+    /// A character pressed with `Alt`
+    Alt(char),
+    /// `Ctrl+X` followed by a character
+    CtrlX(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete(Modifiers),
+    PageUp,
+    PageDown,
+    Up(Modifiers),
+    Down(Modifiers),
+    Left(Modifiers),
+    Right(Modifiers),
+    Home(Modifiers),
+    End(Modifiers),
+    /// A function key, like `F(5)` for `F5`
+    F(u8),
+    /// `Esc`, which is told apart from the start of another key by nothing following it in
+    /// time, or by being pressed twice. See
+    /// [set_escape_timeout](crate::Repline::set_escape_timeout).
+    Esc,
+    /// The terminal gained or lost focus, which is reported like a key, but can't be bound
+    Focus(Focus),
+    /// Text pasted while the terminal marks pastes, which is inserted (once
+    /// [confirmed](crate::Repline::set_paste_confirmation)), and can't be bound
+    Paste,
+    /// A left click at a row and column on screen, counting from 0, while the
+    /// [mouse](crate::Repline::set_mouse) is captured, which moves the cursor, and can't be
+    /// bound
+    Click(u16, u16),
+}
+
+/// Input which doesn't do anything, passed to the
+/// [unknown key handler](crate::Repline::set_unknown_key_handler) to diagnose a terminal
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Unhandled {
+    /// A control character or escape sequence which isn't decoded as a key, as it was read,
+    /// like `"\x1b[57;5u"`
+    Undecoded(String),
+    /// A key with no command bound to it
+    Unbound(Key),
+}
+
+/// The modifier keys held while pressing a navigation key
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+}
+
+impl Modifiers {
+    pub const NONE: Self = Self { shift: false, alt: false, ctrl: false };
+    pub const SHIFT: Self = Self { shift: true, alt: false, ctrl: false };
+    pub const ALT_SHIFT: Self = Self { shift: true, alt: true, ctrl: false };
+    pub const CTRL: Self = Self { shift: false, alt: false, ctrl: true };
+    pub const CTRL_SHIFT: Self = Self { shift: true, alt: false, ctrl: true };
+
+    /// Decodes an xterm modifier parameter, like the `5` in `CSI 1 ; 5 C` (Ctrl+Right)
+    pub fn from_param(param: u32) -> Self {
+        let bits = param.saturating_sub(1);
+        Self { shift: bits & 1 != 0, alt: bits & 2 != 0, ctrl: bits & 4 != 0 }
+    }
+
+    /// Encodes the xterm modifier parameter which [from_param](Self::from_param) decodes
+    pub fn param(self) -> u32 {
+        1 + self.shift as u32 + 2 * self.alt as u32 + 4 * self.ctrl as u32
+    }
+}
+
+/// A named editing action, which a [Key] can be bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EditCommand {
+    /// Inserts a character at the cursor
+    Insert(char),
+    /// Submits the whole buffer, wherever the cursor is, if the
+    /// [Validator](crate::validate::Validator) finds it complete, or inserts a line break
+    Enter,
+    /// Submits the line, wherever the cursor is
+    Submit,
+    /// Inserts a line break, even at the end of a complete buffer
+    Newline,
+    /// Ends the read with [Error::CtrlC](crate::Error::CtrlC)
+    Interrupt,
+    /// Ends the read with [Error::CtrlD](crate::Error::CtrlD) if the buffer is empty, or
+    /// deletes like [Delete](Self::Delete), like readline
+    EndOfFile,
+    /// Ends the read with [Error::EndOfInput](crate::Error::EndOfInput)
+    EndOfInput,
+    /// Ends the read with [Error::Action](crate::Error::Action), carrying the tag, so that
+    /// the application can treat the key as a command of its own, like `F5` to run the last
+    /// line again
+    Action(
+        // spelled out, or serde would borrow the tag from the input, rather than leak it
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "leak"))]
+        &'static std::primitive::str,
+    ),
+    /// Visits the next snippet placeholder, expands a snippet, completes the word under
+    /// the cursor, or indents, whichever applies first
+    Complete,
+    /// Inserts four spaces
+    Indent,
+    /// Inserts the character whose codepoint the user types next, in hex, like `3bb` for `λ`,
+    /// showing the digits on the status line. `Enter` or `Space` inserts it, and `Ctrl+G`
+    /// gives up.
+    InsertCodepoint,
+    /// Deletes the selection or the character before the cursor, or unindents
+    Backspace,
+    /// Deletes the selection or the character after the cursor
+    Delete,
+    /// Kills the word before the cursor, storing it in the kill ring
+    DeleteWord,
+    /// Kills the word after the cursor, storing it in the kill ring
+    DeleteWordForward,
+    /// Uppercases the rest of the word under the cursor, or the next word, moving past it
+    UpcaseWord,
+    /// Lowercases the rest of the word under the cursor, or the next word, moving past it
+    DowncaseWord,
+    /// Capitalizes the rest of the word under the cursor, or the next word, moving past it
+    CapitalizeWord,
+    /// Kills the rest of the line, or the line break at the end of it
+    KillLineEnd,
+    /// Kills the start of the line, or the line break before it
+    KillLineStart,
+    /// Kills the rest of the buffer, across lines
+    KillBufferEnd,
+    /// Kills the start of the buffer, across lines
+    KillBufferStart,
+    /// Inserts the text killed last
+    Yank,
+    /// Right after a yank, replaces the yanked text with the text killed before it
+    YankPop,
+    /// Copies the selection, or the text killed last, to the system clipboard
+    #[cfg(feature = "clipboard")]
+    Copy,
+    /// Asks the terminal for the system clipboard, which is inserted like a paste once the
+    /// terminal sends it
+    #[cfg(feature = "clipboard")]
+    PasteClipboard,
+    /// Inserts the last whitespace-separated argument of the previous history entry, or,
+    /// right after doing so, swaps it for that of the entry before
+    YankLastArg,
+    /// Moves up a line, or recalls the previous history entry on the first line of the buffer.
+    /// See [ArrowKeys](crate::repline::ArrowKeys).
+    Up,
+    /// Moves down a line, or recalls the next history entry on the last line of the buffer
+    Down,
+    Left,
+    Right,
+    WordLeft,
+    WordRight,
+    LineStart,
+    LineEnd,
+    BufferStart,
+    BufferEnd,
+    /// Recalls the previous history entry
+    HistoryPrev,
+    /// Recalls the next history entry
+    HistoryNext,
+    /// Recalls the oldest history entry, like binding `PageUp` to the top of the history
+    HistoryFirst,
+    /// Goes back to the line being typed before the history was recalled
+    HistoryLast,
+    /// Searches the history for lines containing what the user types next, newest first
+    SearchHistory,
+    /// Recalls the nearest history entry in the given direction which the search added by
+    /// [add_history_search](crate::Repline::add_history_search) at the given index accepts
+    SearchWith(usize, Direction),
+    SelectUp,
+    SelectDown,
+    SelectLeft,
+    SelectRight,
+    SelectWordLeft,
+    SelectWordRight,
+    SelectLineStart,
+    SelectLineEnd,
+    SelectBufferStart,
+    SelectBufferEnd,
+    /// Extends the block (column) selection upward
+    BlockUp,
+    BlockDown,
+    BlockLeft,
+    BlockRight,
+    /// Removes the innermost delimiter pair enclosing the cursor
+    Unsurround,
+    /// Selects the text inside the innermost delimiter pair enclosing the cursor
+    SelectInside,
+    /// Selects the innermost delimiter pair enclosing the cursor, delimiters and all
+    SelectAround,
+    /// Deletes the text inside the innermost delimiter pair enclosing the cursor
+    DeleteInside,
+    /// Deletes the innermost delimiter pair enclosing the cursor, delimiters and all
+    DeleteAround,
+    /// Jumps to the next diagnostic
+    NextDiagnostic,
+    /// Jumps to the previous diagnostic
+    PrevDiagnostic,
+    /// Applies the quick fix of the diagnostic shown on the status line
+    ApplyFix,
+    /// While cycling through completion candidates, puts back the word as it was typed
+    RevertCompletion,
+    /// Switches to the next scratch buffer
+    NextBuffer,
+    /// Rewrites the buffer with the [Formatter](crate::format::Formatter)
+    Format,
+    /// Opens the buffer in `$VISUAL` (or `$EDITOR`), and loads it back when the editor exits
+    ExternalEditor,
+    /// Clears the screen, and redraws the buffer at the top of it
+    ClearScreen,
+    /// Stops the program like `Ctrl+Z` outside of raw mode would, and redraws the buffer once
+    /// it's continued. Does nothing outside of Unix.
+    Suspend,
+    /// Reverts the last change to the buffer
+    Undo,
+    /// Reapplies the last change which was undone
+    Redo,
+    /// Does nothing, quietly
+    Ignore,
+}
+
+impl EditCommand {
+    /// Returns true if the command kills text, so that a kill right after it joins on
+    pub(crate) fn kills(self) -> bool {
+        matches!(
+            self,
+            Self::DeleteWord
+                | Self::DeleteWordForward
+                | Self::KillLineEnd
+                | Self::KillLineStart
+                | Self::KillBufferEnd
+                | Self::KillBufferStart
+        )
+    }
+}
+
+/// Loads an [Action](EditCommand::Action) tag, which lives as long as the program does, like
+/// the tags written in code
+#[cfg(feature = "serde")]
+fn leak<'de, D: serde::Deserializer<'de>>(d: D) -> Result<&'static str, D::Error> {
+    let tag: String = serde::Deserialize::deserialize(d)?;
+    Ok(Box::leak(tag.into_boxed_str()))
+}
+
+/// Strips `prefix` from `s`, ignoring case, unless nothing would be left
+fn strip<'s>(s: &'s str, prefix: &str) -> Option<&'s str> {
+    let head = s.get(..prefix.len())?;
+    (head.eq_ignore_ascii_case(prefix) && s.len() > prefix.len()).then(|| &s[prefix.len()..])
+}
+
+/// Returns the letter (or one of `\]^_`) which makes the control character `c` with `Ctrl`
+fn ctrl_letter(c: char) -> Option<char> {
+    match c {
+        '\x01'..='\x1a' => Some((c as u8 - 1 + b'a') as char),
+        '\x1c'..='\x1f' => Some((c as u8 + b'@') as char),
+        _ => None,
+    }
+}
+
+/// Returns the control character `Ctrl` makes of `letter`
+fn ctrl_char(letter: char) -> Option<char> {
+    match letter.to_ascii_lowercase() {
+        c @ 'a'..='z' => Some((c as u8 - b'a' + 1) as char),
+        c @ ('\\' | ']' | '^' | '_') => Some((c as u8 - b'@') as char),
+        _ => None,
+    }
+}
+
+impl Key {
+    /// Gets what a terminal sends for this key, as repline reads it, or [None] for keys which
+    /// are more than a keypress, like a [Paste](Key::Paste), or which repline doesn't read,
+    /// like `F13`
+    pub fn sequence(&self) -> Option<String> {
+        // cursor keys carry their modifiers as a parameter, like `CSI 1 ; 5 C` (Ctrl+Right)
+        let csi = |m: Modifiers, end: char| match m {
+            Modifiers::NONE => format!("\x1b[{end}"),
+            m => format!("\x1b[1;{}{end}", m.param()),
+        };
+        let seq = match *self {
+            Key::Char(c) => c.to_string(),
+            Key::Ctrl(c) => ctrl_char(c)?.to_string(),
+            Key::Alt(c) => format!("\x1b{c}"),
+            Key::CtrlX(c) => format!("\x18{c}"),
+            Key::Enter => "\r".into(),
+            Key::Tab => "\t".into(),
+            Key::Backspace => "\x7f".into(),
+            Key::Delete(Modifiers::NONE) => "\x1b[3~".into(),
+            Key::Delete(m) => format!("\x1b[3;{}~", m.param()),
+            Key::PageUp => "\x1b[5~".into(),
+            Key::PageDown => "\x1b[6~".into(),
+            Key::Up(m) => csi(m, 'A'),
+            Key::Down(m) => csi(m, 'B'),
+            Key::Right(m) => csi(m, 'C'),
+            Key::Left(m) => csi(m, 'D'),
+            Key::Home(m) => csi(m, 'H'),
+            Key::End(m) => csi(m, 'F'),
+            Key::F(n @ 1..=4) => format!("\x1bO{}", (b'P' + n - 1) as char),
+            // F5 to F12 skip a number after F5 and F10
+            Key::F(n @ 5..=12) => {
+                format!("\x1b[{}~", [15, 17, 18, 19, 20, 21, 23, 24][n as usize - 5])
+            }
+            Key::F(_) => None?,
+            // a lone escape is told apart from the start of a sequence by doubling it
+            Key::Esc => "\x1b\x1b".into(),
+            Key::Focus(Focus::Gained) => "\x1b[I".into(),
+            Key::Focus(Focus::Lost) => "\x1b[O".into(),
+            Key::Paste => None?,
+            Key::Click(row, col) => format!("\x1b[<0;{};{}M", col + 1, row + 1),
+        };
+        Some(seq)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let chord = |f: &mut fmt::Formatter<'_>, m: Modifiers, name: &str| {
+            let Modifiers { shift, alt, ctrl } = m;
+            let held = [(ctrl, "Ctrl+"), (alt, "Alt+"), (shift, "Shift+")];
+            for (_, prefix) in held.into_iter().filter(|&(held, _)| held) {
+                f.write_str(prefix)?;
+            }
+            f.write_str(name)
+        };
+        match *self {
+            Key::Char(' ') => f.write_str("Space"),
+            Key::Char(c) => write!(f, "{c}"),
+            Key::Ctrl(c) => write!(f, "Ctrl+{}", c.to_ascii_uppercase()),
+            Key::Alt('\r') => f.write_str("Alt+Enter"),
+            Key::Alt(' ') => f.write_str("Alt+Space"),
+            Key::Alt(c) => write!(f, "Alt+{c}"),
+            Key::CtrlX(c) => match ctrl_letter(c) {
+                Some(letter) => write!(f, "Ctrl+X {}", Key::Ctrl(letter)),
+                None => write!(f, "Ctrl+X {}", Key::Char(c)),
+            },
+            Key::Enter => f.write_str("Enter"),
+            Key::Tab => f.write_str("Tab"),
+            Key::Backspace => f.write_str("Backspace"),
+            Key::Delete(m) => chord(f, m, "Delete"),
+            Key::PageUp => f.write_str("PageUp"),
+            Key::PageDown => f.write_str("PageDown"),
+            Key::Up(m) => chord(f, m, "Up"),
+            Key::Down(m) => chord(f, m, "Down"),
+            Key::Left(m) => chord(f, m, "Left"),
+            Key::Right(m) => chord(f, m, "Right"),
+            Key::Home(m) => chord(f, m, "Home"),
+            Key::End(m) => chord(f, m, "End"),
+            Key::F(n) => write!(f, "F{n}"),
+            Key::Esc => f.write_str("Esc"),
+            Key::Focus(Focus::Gained) => f.write_str("FocusGained"),
+            Key::Focus(Focus::Lost) => f.write_str("FocusLost"),
+            Key::Paste => f.write_str("Paste"),
+            Key::Click(row, col) => write!(f, "Click({row}, {col})"),
+        }
+    }
+}
+
+/// The name given to [Key::from_str] didn't name a key
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownKey(pub String);
+
+impl fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown key {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownKey {}
+
+impl FromStr for Key {
+    type Err = UnknownKey;
+
+    /// Reads a key named like its [Display](fmt::Display) names it, ignoring the case of
+    /// modifiers and named keys
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unknown = || UnknownKey(s.to_string());
+        if let Some(rest) = strip(s, "Ctrl+X ") {
+            return match rest.parse()? {
+                Key::Ctrl(letter) => ctrl_char(letter).map(Key::CtrlX),
+                Key::Char(c) => Some(Key::CtrlX(c)),
+                _ => None,
+            }
+            .ok_or_else(unknown);
+        }
+        let (mut m, mut name) = (Modifiers::NONE, s);
+        loop {
+            if let Some(rest) = strip(name, "Ctrl+") {
+                (m.ctrl, name) = (true, rest);
+            } else if let Some(rest) = strip(name, "Alt+") {
+                (m.alt, name) = (true, rest);
+            } else if let Some(rest) = strip(name, "Shift+") {
+                (m.shift, name) = (true, rest);
+            } else {
+                break;
+            }
+        }
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return match m {
+                Modifiers::NONE => Some(Key::Char(c)),
+                Modifiers::CTRL => ctrl_char(c).map(|_| Key::Ctrl(c.to_ascii_lowercase())),
+                Modifiers { alt: true, shift: false, ctrl: false } => Some(Key::Alt(c)),
+                _ => None,
+            }
+            .ok_or_else(unknown);
+        }
+        let name = name.to_ascii_lowercase();
+        let key = match name.as_str() {
+            "up" => Key::Up(m),
+            "down" => Key::Down(m),
+            "left" => Key::Left(m),
+            "right" => Key::Right(m),
+            "home" => Key::Home(m),
+            "end" => Key::End(m),
+            "delete" => Key::Delete(m),
+            "enter" if m == Modifiers::NONE => Key::Enter,
+            "enter" if m == (Modifiers { alt: true, ..Modifiers::NONE }) => Key::Alt('\r'),
+            "space" if m == Modifiers::NONE => Key::Char(' '),
+            "space" if m == (Modifiers { alt: true, ..Modifiers::NONE }) => Key::Alt(' '),
+            _ if m != Modifiers::NONE => Err(unknown())?,
+            "tab" => Key::Tab,
+            "backspace" => Key::Backspace,
+            "pageup" => Key::PageUp,
+            "pagedown" => Key::PageDown,
+            "esc" => Key::Esc,
+            "focusgained" => Key::Focus(Focus::Gained),
+            "focuslost" => Key::Focus(Focus::Lost),
+            "paste" => Key::Paste,
+            f => match f.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                Some(n @ 1..=24) => Key::F(n),
+                _ => Err(unknown())?,
+            },
+        };
+        Ok(key)
+    }
+}
+
+/// Decodes a keystroke script, where each key named in angle brackets, like `<Left>` or
+/// `<Ctrl+A>`, stands for what a terminal sends for it. Everything else is typed as-is,
+/// including escapes written out, like `"\x1b[A"`, and a `<` which doesn't start a key name.
+///
+/// ```
+/// use repline::keymap::script;
+///
+/// assert_eq!(script("hello<Left><Backspace><Enter>").unwrap(), "hello\x1b[D\x7f\r");
+/// assert_eq!(script("1 < 2<Enter>").unwrap(), "1 < 2\r");
+/// ```
+pub fn script(text: &str) -> Result<String, UnknownKey> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(open) = rest.find('<') {
+        out.push_str(&rest[..open]);
+        rest = &rest[open..];
+        let name = rest[1..].find('>').map(|close| &rest[1..close + 1]);
+        // a single character names itself, so it's typed with its brackets
+        let key = name
+            .filter(|name| name.chars().nth(1).is_some())
+            .map(str::parse::<Key>);
+        match (name, key) {
+            (Some(name), Some(Ok(key))) => {
+                out.push_str(&key.sequence().ok_or_else(|| UnknownKey(name.to_string()))?);
+                rest = &rest[name.len() + 2..];
+            }
+            _ => {
+                out.push('<');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// A binding which differs between two [Keymap]s. See [Keymap::diff].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Change {
+    pub key: Key,
+    /// What the key runs in the keymap compared against
+    pub before: Option<EditCommand>,
+    /// What the key runs in this keymap
+    pub after: Option<EditCommand>,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { key, before, after } = self;
+        match (before, after) {
+            (Some(before), Some(after)) => write!(f, "{key}: {before:?} -> {after:?}"),
+            (None, Some(after)) => write!(f, "{key}: {after:?} (added)"),
+            (Some(before), None) => write!(f, "{key}: {before:?} (removed)"),
+            (None, None) => write!(f, "{key}: unbound"),
+        }
+    }
+}
+
+/// Maps [Key]s to [EditCommand]s.
+///
+/// [Char](Key::Char) keys which aren't bound insert themselves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Keymap(HashMap<Key, EditCommand>);
+
+impl Keymap {
+    /// Constructs a [Keymap] with no bindings at all
+    pub fn empty() -> Self {
+        Self(HashMap::new())
+    }
+
+    /// Binds `key` to `command`, returning the command it was bound to before
+    pub fn bind(&mut self, key: Key, command: EditCommand) -> Option<EditCommand> {
+        self.0.insert(key, command)
+    }
+
+    /// Removes the binding for `key`, returning the command it was bound to
+    pub fn unbind(&mut self, key: Key) -> Option<EditCommand> {
+        self.0.remove(&key)
+    }
+
+    /// Returns the command `key` runs, if any
+    pub fn get(&self, key: Key) -> Option<EditCommand> {
+        match self.0.get(&key) {
+            Some(&command) => Some(command),
+            None => match key {
+                Key::Char(c) => Some(EditCommand::Insert(c)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Returns the bindings which differ from those in `base`, ordered by key name, like a
+    /// user's customizations when `base` is the [default](Keymap::default)
+    pub fn diff(&self, base: &Keymap) -> Vec<Change> {
+        let keys = self
+            .0
+            .keys()
+            .chain(base.0.keys().filter(|key| !self.0.contains_key(key)));
+        let mut changes: Vec<_> = keys
+            .map(|&key| {
+                let (before, after) = (base.0.get(&key).copied(), self.0.get(&key).copied());
+                Change { key, before, after }
+            })
+            .filter(|change| change.before != change.after)
+            .collect();
+        changes.sort_by_cached_key(|change| change.key.to_string());
+        changes
+    }
+
+    /// Writes the bindings as TOML, one per line, like `"Ctrl+A" = "LineStart"`, ordered by
+    /// key name
+    #[cfg(feature = "serde")]
+    pub fn to_toml(&self) -> String {
+        let bindings: std::collections::BTreeMap<_, _> = self
+            .0
+            .iter()
+            .map(|(key, command)| (key.to_string(), command))
+            .collect();
+        toml::to_string(&bindings).expect("every binding can be written as TOML")
+    }
+
+    /// Reads bindings written by [to_toml](Self::to_toml), in place of the default ones.
+    ///
+    /// The error says which binding couldn't be read.
+    #[cfg(feature = "serde")]
+    pub fn from_toml(text: &str) -> crate::error::ReplResult<Self> {
+        let invalid = |e| std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+        let bindings: HashMap<String, EditCommand> =
+            toml::from_str(text).map_err(|e| invalid(e.to_string()))?;
+        let mut keymap = Self::empty();
+        for (key, command) in bindings {
+            keymap.bind(
+                key.parse()
+                    .map_err(|e: UnknownKey| invalid(e.to_string()))?,
+                command,
+            );
+        }
+        Ok(keymap)
+    }
+}
+
+impl Default for Keymap {
+    /// The default bindings
+    fn default() -> Self {
+        use EditCommand as E;
+        use Modifiers as M;
+        #[allow(unused_mut)]
+        let mut keymap = Self(HashMap::from([
+            (Key::Ctrl('c'), E::Interrupt),
+            (Key::Ctrl('d'), E::EndOfFile),
+            (Key::Alt('\r'), E::Newline),
+            (Key::Tab, E::Complete),
+            (Key::Enter, E::Enter),
+            // a line feed, when it isn't read as Enter
+            (Key::Ctrl('j'), E::Ignore),
+            (Key::Backspace, E::Backspace),
+            // Ctrl+Backspace in some terminals
+            (Key::Ctrl('w'), E::DeleteWord),
+            (Key::Alt('d'), E::DeleteWordForward),
+            (Key::Delete(M::CTRL), E::DeleteWordForward),
+            (Key::Ctrl('k'), E::KillLineEnd),
+            (Key::Alt('u'), E::UpcaseWord),
+            (Key::Alt('l'), E::DowncaseWord),
+            (Key::Alt('c'), E::CapitalizeWord),
+            (Key::Ctrl('u'), E::KillLineStart),
+            (Key::Ctrl('r'), E::SearchHistory),
+            (Key::Ctrl('y'), E::Yank),
+            (Key::Alt('y'), E::YankPop),
+            (Key::Alt('.'), E::YankLastArg),
+            (Key::Delete(M::NONE), E::Delete),
+            (Key::Up(M::NONE), E::Up),
+            (Key::Down(M::NONE), E::Down),
+            (Key::Left(M::NONE), E::Left),
+            (Key::Right(M::NONE), E::Right),
+            (Key::Home(M::NONE), E::LineStart),
+            (Key::End(M::NONE), E::LineEnd),
+            (Key::PageUp, E::BufferStart),
+            (Key::PageDown, E::BufferEnd),
+            // the readline (Emacs) movement keys
+            (Key::Ctrl('a'), E::LineStart),
+            (Key::Ctrl('e'), E::LineEnd),
+            (Key::Ctrl('b'), E::Left),
+            (Key::Ctrl('f'), E::Right),
+            (Key::Alt('<'), E::BufferStart),
+            (Key::Alt('>'), E::BufferEnd),
+            (Key::Left(M::CTRL), E::WordLeft),
+            (Key::Right(M::CTRL), E::WordRight),
+            (Key::Up(M::SHIFT), E::SelectUp),
+            (Key::Down(M::SHIFT), E::SelectDown),
+            (Key::Left(M::SHIFT), E::SelectLeft),
+            (Key::Right(M::SHIFT), E::SelectRight),
+            (Key::Home(M::SHIFT), E::SelectLineStart),
+            (Key::End(M::SHIFT), E::SelectLineEnd),
+            (Key::Left(M::CTRL_SHIFT), E::SelectWordLeft),
+            (Key::Right(M::CTRL_SHIFT), E::SelectWordRight),
+            (Key::Home(M::CTRL_SHIFT), E::SelectBufferStart),
+            (Key::End(M::CTRL_SHIFT), E::SelectBufferEnd),
+            (Key::Up(M::ALT_SHIFT), E::BlockUp),
+            (Key::Down(M::ALT_SHIFT), E::BlockDown),
+            (Key::Left(M::ALT_SHIFT), E::BlockLeft),
+            (Key::Right(M::ALT_SHIFT), E::BlockRight),
+            (Key::Alt('s'), E::Unsurround),
+            (Key::Alt('i'), E::SelectInside),
+            (Key::Alt('o'), E::SelectAround),
+            (Key::Alt('n'), E::NextDiagnostic),
+            (Key::Alt('p'), E::PrevDiagnostic),
+            (Key::Alt('a'), E::ApplyFix),
+            (Key::Esc, E::RevertCompletion),
+            (Key::Ctrl('g'), E::RevertCompletion),
+            (Key::Alt('q'), E::Format),
+            (Key::Alt('x'), E::InsertCodepoint),
+            // Ctrl+X Ctrl+E
+            (Key::CtrlX('\x05'), E::ExternalEditor),
+            (Key::CtrlX('b'), E::NextBuffer),
+            (Key::Ctrl('l'), E::ClearScreen),
+            (Key::Ctrl('z'), E::Suspend),
+            (Key::Ctrl('_'), E::Undo),
+            // Ctrl+X Ctrl+U
+            (Key::CtrlX('\x15'), E::Undo),
+            (Key::Alt('_'), E::Redo),
+        ]));
+        #[cfg(feature = "clipboard")]
+        {
+            keymap.bind(Key::Alt('w'), E::Copy);
+            // Ctrl+X Ctrl+Y
+            keymap.bind(Key::CtrlX('\x19'), E::PasteClipboard);
+        }
+        keymap
+    }
+}
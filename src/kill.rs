@@ -0,0 +1,58 @@
+//! The kill ring remembers killed (cut) text, so that it can be yanked (pasted) back.
+//!
+//! Consecutive kills join into one entry, so that killing several words in a row yanks
+//! them back together. After a yank, the yanked text can be swapped for older kills.
+
+use std::collections::VecDeque;
+
+/// The most kills remembered at once
+const CAPACITY: usize = 32;
+
+/// Killed text, newest first
+#[derive(Clone, Debug, Default)]
+pub struct KillRing {
+    kills: VecDeque<String>,
+    yank: usize, // index of the kill yanked last
+}
+
+impl KillRing {
+    /// Remembers killed `text`.
+    ///
+    /// If `join` is set, the text joins the newest kill instead: before it, if it was killed
+    /// `backward` from the cursor, or after it otherwise.
+    pub fn kill(&mut self, text: String, join: bool, backward: bool) {
+        if text.is_empty() {
+            return;
+        }
+        match self.kills.front_mut() {
+            Some(last) if join && backward => last.insert_str(0, &text),
+            Some(last) if join => last.push_str(&text),
+            _ => {
+                self.kills.push_front(text);
+                self.kills.truncate(CAPACITY);
+            }
+        }
+    }
+
+    /// Returns the newest kill, if any
+    pub fn yank(&mut self) -> Option<&str> {
+        self.yank = 0;
+        self.newest()
+    }
+
+    /// Returns the newest kill, if any
+    pub fn newest(&self) -> Option<&str> {
+        self.kills.front().map(String::as_str)
+    }
+
+    /// Returns the kill yanked last, if any
+    pub fn yanked(&self) -> Option<&str> {
+        self.kills.get(self.yank).map(String::as_str)
+    }
+
+    /// Moves on to the next older kill, wrapping around to the newest, and returns it
+    pub fn rotate(&mut self) -> Option<&str> {
+        self.yank = (self.yank + 1).checked_rem(self.kills.len())?;
+        self.yanked()
+    }
+}
@@ -1,13 +1,69 @@
 //! A small pseudo-multiline editing library
+//!
+//! # Features
+//! - `prebaked` (default): the ready-made menu loop in `prebaked`. Without it, only the editor and
+//!   [Repline] are compiled.
+//! - `ffi`: a C ABI in `ffi`, for embedding repline in programs written in other languages.
+//! - `pyo3`: a Python extension module in `python`, for Python REPLs.
+//! - `compat`: a rustyline-shaped interface in `compat`, for porting programs from rustyline.
+//! - `serde`: serialization for saved editor state, [saved sessions](Repline::save_session), and
+//!   history [exported as JSON](Repline::export_history).
+//! - `events`: reading keys through crossterm's event reader, which Windows consoles need. It's
+//!   used by default on Windows, and [elsewhere on request](Repline::set_event_input). It also lets
+//!   reads [time out](Repline::read_timeout), or [do something while
+//!   idle](Repline::set_idle_handler).
+//! - `tokio`: [read_async](Repline::read_async), which awaits keys instead of blocking, for tokio
+//!   and other async runtimes, and a menu loop which awaits its closure,
+//!   `prebaked::read_and_async`. Implies `events`.
+//! - `clipboard`: copying to and pasting from the system clipboard, through the terminal (OSC 52),
+//!   with `Alt+W` and `Ctrl+X Ctrl+Y`.
+//! - `screen`: an in-memory terminal in `screen`, for checking what the editor draws, and
+//!   end-to-end tests of a REPL in `testing`, which replay keys into it.
 
-mod editor;
+#[cfg(feature = "clipboard")]
+mod clipboard;
+#[cfg(feature = "events")]
+mod events;
 mod iter;
-mod raw;
+mod kill;
+mod render;
+#[cfg(feature = "serde")]
+mod session;
+mod snippet;
 
+pub mod backend;
+#[cfg(feature = "compat")]
+pub mod compat;
+pub mod completion;
+pub mod diagnostics;
+pub mod editor;
 pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod format;
+pub mod handle;
+pub mod highlight;
+pub mod history;
+pub mod input;
+pub mod keymap;
+pub mod middleware;
+pub mod multiplexer;
+#[cfg(feature = "prebaked")]
 pub mod prebaked;
+pub mod prompt;
+#[cfg(feature = "pyo3")]
+pub mod python;
+pub mod raw;
 pub mod repline;
+#[cfg(feature = "screen")]
+pub mod screen;
+pub mod strings;
+#[cfg(feature = "screen")]
+pub mod testing;
+pub mod theme;
+pub mod validate;
 
 pub use error::Error;
+#[cfg(feature = "prebaked")]
 pub use prebaked::{Response, read_and};
 pub use repline::Repline;
@@ -0,0 +1,70 @@
+//! Layers around key handling: each [Middleware] sees a key before the editor does, and
+//! decides what reaches the layers below it, like a macro recorder, or abbreviations which
+//! expand as they're typed.
+//!
+//! ```no_run
+//! # use repline::{Repline, keymap::Key, middleware::Context};
+//! let mut rl = Repline::new("", "> ", ". ");
+//! // expands "teh" to "the" when a space follows it
+//! rl.add_middleware(|key: Key, cx: &mut Context| {
+//!     if key == Key::Char(' ') && cx.text()[..cx.cursor()].ends_with("teh") {
+//!         for key in [Key::Backspace, Key::Backspace, Key::Char('h'), Key::Char('e')] {
+//!             cx.next(key)?;
+//!         }
+//!     }
+//!     cx.next(key)
+//! });
+//! ```
+
+use crate::{error::ReplResult, keymap::Key};
+
+/// Handles a key before the layers below it do, passing it (or other keys, or none at all)
+/// on with [Context::next].
+///
+/// Returns the line if a key it passed on submitted it, so that the read returns it. An
+/// error ends the read.
+///
+/// Implemented for all `FnMut(Key, &mut Context) -> ReplResult<Option<String>>`.
+pub trait Middleware {
+    fn handle(&mut self, key: Key, cx: &mut Context<'_>) -> ReplResult<Option<String>>;
+}
+
+impl<F: FnMut(Key, &mut Context<'_>) -> ReplResult<Option<String>>> Middleware for F {
+    fn handle(&mut self, key: Key, cx: &mut Context<'_>) -> ReplResult<Option<String>> {
+        self(key, cx)
+    }
+}
+
+/// What a [Middleware] can see of the editor, and the way on to the layers below it
+pub struct Context<'c>(pub(crate) &'c mut dyn Dispatch);
+
+impl Context<'_> {
+    /// Passes `key` on to the layers below, and then to the editor, returning the line if
+    /// the key submitted it
+    pub fn next(&mut self, key: Key) -> ReplResult<Option<String>> {
+        self.0.next(key)
+    }
+
+    /// Returns the text in the buffer
+    pub fn text(&self) -> String {
+        self.0.text()
+    }
+
+    /// Returns the byte index of the cursor in the [text](Self::text)
+    pub fn cursor(&self) -> usize {
+        self.0.cursor()
+    }
+}
+
+impl std::fmt::Debug for Context<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Context").finish_non_exhaustive()
+    }
+}
+
+/// The layers below a [Middleware], down to the editor
+pub(crate) trait Dispatch {
+    fn next(&mut self, key: Key) -> ReplResult<Option<String>>;
+    fn text(&self) -> String;
+    fn cursor(&self) -> usize;
+}
@@ -0,0 +1,70 @@
+//! Terminal multiplexers, like tmux and GNU screen, sit between the editor and the terminal.
+//!
+//! They interpret escape sequences themselves, and silently drop the ones they don't
+//! understand unless those are wrapped to be passed through to the outer terminal.
+
+use std::env::var;
+
+/// A terminal multiplexer which repline may be running inside of
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Multiplexer {
+    Tmux,
+    Screen,
+}
+
+impl Multiplexer {
+    /// Detects the multiplexer the process is running inside of, if any, from the
+    /// `TMUX`, `STY`, and `TERM` environment variables
+    pub fn detect() -> Option<Self> {
+        if var("TMUX").is_ok_and(|v| !v.is_empty()) {
+            return Some(Self::Tmux);
+        }
+        if var("STY").is_ok_and(|v| !v.is_empty()) {
+            return Some(Self::Screen);
+        }
+        match var("TERM") {
+            Ok(term) if term.starts_with("tmux") => Some(Self::Tmux),
+            Ok(term) if term.starts_with("screen") => Some(Self::Screen),
+            _ => None,
+        }
+    }
+
+    /// Wraps `sequence` in a DCS string, so that the multiplexer forwards it to the outer
+    /// terminal untouched.
+    ///
+    /// tmux only forwards these when its `allow-passthrough` option is on.
+    pub fn passthrough(self, sequence: &str) -> String {
+        match self {
+            Self::Tmux => format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b")),
+            // screen limits the length of a DCS string, so long sequences are split up
+            Self::Screen => {
+                let chars: Vec<char> = sequence.chars().collect();
+                let chunks = chars
+                    .chunks(512)
+                    .map(|chunk| chunk.iter().collect::<String>());
+                chunks.map(|chunk| format!("\x1bP{chunk}\x1b\\")).collect()
+            }
+        }
+    }
+
+    /// Returns true if the multiplexer can show 24-bit color.
+    ///
+    /// screen can't, while tmux can if the terminal outside of it can.
+    pub fn truecolor(self) -> bool {
+        match self {
+            Self::Tmux => colorterm(),
+            Self::Screen => false,
+        }
+    }
+}
+
+/// Guesses whether the terminal can show 24-bit color, from `COLORTERM` and the
+/// multiplexer in use, if any
+pub fn truecolor(multiplexer: Option<Multiplexer>) -> bool {
+    multiplexer.map_or_else(colorterm, Multiplexer::truecolor)
+}
+
+/// Returns true if `COLORTERM` advertises 24-bit color
+fn colorterm() -> bool {
+    var("COLORTERM").is_ok_and(|v| v == "truecolor" || v == "24bit")
+}
@@ -2,11 +2,37 @@
 //!
 //! Constructs a [Repline] and repeatedly runs the provided closure on the input strings,
 //! obeying the closure's [Response].
+//!
+//! [read_between] greets the user with a banner, and sees them off with a parting message.
+//! [ReadAndBuilder] configures the rest, like the history file, the theme, and how errors
+//! are shown.
+//!
+//! [Commands] registers commands with the arguments they take, and completes and hints them
+//! for the loop, so a small tool gets both without writing a [Completer].
+//!
+//! Also has one-off prompts ([prompt_for], [prompt_filtered], [confirm], and [wizard]), which
+//! can be answered ahead of time with [Answers], and single-key menus ([choose]).
 
-use crate::{error::Error as RlError, repline::Repline};
-use std::{error::Error, io::Stdin};
+use crate::{
+    completion::Completer,
+    diagnostics::Diagnostic,
+    error::Error as RlError,
+    keymap::Key,
+    repline::{ReadMode, Repline},
+    theme::Theme,
+    validate::Validator,
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    io::{ErrorKind, Stdin},
+    ops::{ControlFlow, Range},
+    path::PathBuf,
+    time::Instant,
+};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 /// Control codes for the [prebaked menu](read_and)
 pub enum Response {
     /// Accept the line, and save it to history
@@ -17,6 +43,15 @@ pub enum Response {
     Break,
     /// Gather more input and try again
     Continue,
+    /// Accept the line, but save this text to history in its place, like the line in a
+    /// canonical form
+    Replace(String),
+    /// Reject the line, and start the next prompt with this text in the buffer, like a
+    /// corrected command for the user to confirm
+    Prefill(String),
+    /// Reject the line, but leave it in the buffer to be corrected, with these problems
+    /// underlined in it until it's edited. See [report_diagnostics](Repline::report_diagnostics).
+    Diagnose(Vec<Diagnostic>),
 }
 
 /// Implements a basic menu loop using an embedded [Repline].
@@ -28,7 +63,7 @@ pub enum Response {
 ///
 /// # Keybinds
 /// - `Ctrl+C` exits the loop
-/// - `Ctrl+D` clears the input, but *runs the closure* with the old input
+/// - `Ctrl+D` on an empty line *runs the closure* with it, and deletes forward otherwise
 pub fn read_and<F>(color: &str, begin: &str, again: &str, mut f: F) -> Result<(), RlError>
 where F: FnMut(&str) -> Result<Response, Box<dyn Error>> {
     read_and_mut(color, begin, again, |_, line| f(line))
@@ -43,30 +78,815 @@ where F: FnMut(&str) -> Result<Response, Box<dyn Error>> {
 ///
 /// Captures and displays all user [Error]s.
 ///
+/// When stdin isn't a terminal, lines are read as-is, and errors are printed to stderr.
+/// When only stdout is redirected, the buffer is drawn on stderr (see [Repline::auto]).
+///
+/// The closure may run programs which use the terminal, like a pager, through
+/// [Repline::with_cooked_terminal].
+///
 /// # Keybinds
 /// - `Ctrl+C` exits the loop
-/// - `Ctrl+D` clears the input, but *runs the closure* with the old input
+/// - `Ctrl+D` on an empty line *runs the closure* with it, and deletes forward otherwise
 pub fn read_and_mut<F>(color: &str, begin: &str, again: &str, mut f: F) -> Result<(), RlError>
 where F: FnMut(&mut Repline<'_, Stdin>, &str) -> Result<Response, Box<dyn Error>> {
-    let mut rl = Repline::new(color, begin, again);
-    loop {
-        let line = match rl.read() {
-            Err(RlError::CtrlC(_)) => break,
-            Err(RlError::CtrlD(line)) => {
-                rl.deny();
-                line
+    read_until::<(), _>(color, begin, again, |rl, line| {
+        f(rl, line).map(ControlFlow::Continue)
+    })?;
+    Ok(())
+}
+
+/// Implements a basic menu loop like [read_and_mut], which ends when the closure
+/// returns [ControlFlow::Break] with a value, like the item chosen from a menu.
+///
+/// Returns the closure's value, or `None` if the loop ended otherwise: by
+/// [Response::Break], `Ctrl+C`, or the end of input.
+pub fn read_until<B, F>(color: &str, begin: &str, again: &str, f: F) -> Result<Option<B>, RlError>
+where F: FnMut(&mut Repline<'_, Stdin>, &str) -> Reply<B> {
+    ReadAndBuilder::new(color, begin, again).run(f)
+}
+
+/// A banner printed before the first prompt, and a parting message printed once the loop
+/// ends, for [read_between]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Bookends<'b> {
+    /// An ANSI color (or other styling) string, which both messages are printed in
+    pub color: &'b str,
+    /// Printed before the first prompt, unless empty
+    pub banner: &'b str,
+    /// Printed once the loop ends, unless empty or the loop ends with an error
+    pub goodbye: &'b str,
+}
+
+/// Implements a basic menu loop like [read_until], printing the [Bookends] around it through
+/// the [Repline], so they're mirrored, and uncolored when lines are read as-is.
+///
+/// ```no_run
+/// use repline::prebaked::{Bookends, Response, read_between};
+/// use std::ops::ControlFlow;
+///
+/// let bookends = Bookends { color: "\x1b[1;36m", banner: "calc v1.0", goodbye: "bye!" };
+/// read_between::<(), _>(bookends, "", "> ", "  ", |_, line| {
+///     println!("{}", line.trim().len());
+///     Ok(ControlFlow::Continue(Response::Accept))
+/// })?;
+/// # Ok::<(), repline::Error>(())
+/// ```
+pub fn read_between<B, F>(
+    bookends: Bookends<'_>,
+    color: &str,
+    begin: &str,
+    again: &str,
+    f: F,
+) -> Result<Option<B>, RlError>
+where
+    F: FnMut(&mut Repline<'_, Stdin>, &str) -> Reply<B>,
+{
+    ReadAndBuilder::new(color, begin, again)
+        .bookends(bookends)
+        .run(f)
+}
+
+/// Configures the [prebaked menu](read_until), for loops which need more than prompts.
+///
+/// ```no_run
+/// use repline::prebaked::{Bookends, ReadAndBuilder, Response};
+/// use std::ops::ControlFlow;
+///
+/// ReadAndBuilder::new("\x1b[33m", "> ", ". ")
+///     .history_file(".calc_history")
+///     .history_cap(500)
+///     .bookends(Bookends { color: "", banner: "calc v1.0", goodbye: "bye!" })
+///     .format_error(|e| format!("error: {e}"))
+///     .run::<(), _>(|_, line| {
+///         let n: f64 = line.trim().parse()?;
+///         println!("{}", n * 2.0);
+///         Ok(ControlFlow::Continue(Response::Accept))
+///     })?;
+/// # Ok::<(), repline::Error>(())
+/// ```
+pub struct ReadAndBuilder<'a> {
+    rl: Repline<'a, Stdin>,
+    history_file: Option<PathBuf>,
+    bookends: Bookends<'a>,
+    format_error: Box<FormatFn<'a>>,
+}
+type FormatFn<'a> = dyn FnMut(&dyn Error) -> String + 'a;
+
+/// What the closure of the menu loop returns: a [Response], or the loop's value to end it
+type Reply<B> = Result<ControlFlow<B, Response>, Box<dyn Error>>;
+
+impl std::fmt::Debug for ReadAndBuilder<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { rl, history_file, bookends, .. } = self;
+        f.debug_struct("ReadAndBuilder")
+            .field("rl", rl)
+            .field("history_file", history_file)
+            .field("bookends", bookends)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> ReadAndBuilder<'a> {
+    /// Starts configuring a menu loop with the provided prompt color, begin prompt, and
+    /// again prompt, on a [Repline::auto]
+    pub fn new(color: &'a str, begin: &'a str, again: &'a str) -> Self {
+        Self {
+            rl: Repline::auto(color, begin, again),
+            history_file: None,
+            bookends: Bookends::default(),
+            format_error: Box::new(|e| e.to_string()),
+        }
+    }
+
+    /// Sets the styles of the prompts, secondary text, and error messages
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.rl.set_theme(theme);
+        self
+    }
+
+    /// Loads the history from the file at `path`, if there is one, and appends each line
+    /// accepted to it. Lines other sessions append to it are reloaded before each read.
+    pub fn history_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.history_file = Some(path.into());
+        self
+    }
+
+    /// Sets the number of entries the history holds
+    pub fn history_cap(mut self, cap: usize) -> Self {
+        self.rl.set_history_cap(cap);
+        self
+    }
+
+    /// Sets how the closure's errors are shown, which is their [Display](std::fmt::Display)
+    /// by default
+    pub fn format_error(mut self, f: impl FnMut(&dyn Error) -> String + 'a) -> Self {
+        self.format_error = Box::new(f);
+        self
+    }
+
+    /// Sets the banner printed before the first prompt, and the parting message printed once
+    /// the loop ends
+    pub fn bookends(mut self, bookends: Bookends<'a>) -> Self {
+        self.bookends = bookends;
+        self
+    }
+
+    /// Sets what decides whether Enter submits the buffer. See [Repline::set_validator].
+    pub fn validator(mut self, validator: impl Validator + 'a) -> Self {
+        self.rl.set_validator(validator);
+        self
+    }
+
+    /// Sets what completes the word under the cursor. See [Repline::set_completer].
+    pub fn completer(mut self, completer: impl Completer + 'a) -> Self {
+        self.rl.set_completer(completer);
+        self
+    }
+
+    /// Completes the names, flags, and arguments of `commands`, and hints the argument
+    /// expected next at the right edge of the line. This replaces the [completer](Self::completer).
+    pub fn commands(mut self, commands: Commands<'a>) -> Self {
+        let hints = commands.clone();
+        self.rl.set_completer(commands);
+        self.rl.set_echo(move |line| hints.hint(line));
+        self
+    }
+
+    /// Configures anything else about the [Repline]
+    pub fn configure(mut self, f: impl FnOnce(&mut Repline<'a, Stdin>)) -> Self {
+        f(&mut self.rl);
+        self
+    }
+
+    /// Runs the menu loop, like [read_between]
+    pub fn run<B, F>(self, f: F) -> Result<Option<B>, RlError>
+    where F: FnMut(&mut Repline<'_, Stdin>, &str) -> Reply<B> {
+        let Self { mut rl, history_file, bookends, mut format_error } = self;
+        if let Some(path) = history_file {
+            match rl.load_history(&path) {
+                Err(RlError::IoFailure(e)) if e.kind() == ErrorKind::NotFound => {}
+                loaded => loaded?,
             }
-            Ok(line) => line,
-            Err(e) => Err(e)?,
+            rl.append_history(&path)?;
+        }
+        let Bookends { color: style, banner, goodbye } = bookends;
+        if !banner.is_empty() {
+            rl.print_message(style, banner)?;
+        }
+        let value = run(&mut rl, f, &mut format_error)?;
+        if !goodbye.is_empty() {
+            rl.print_message(style, goodbye)?;
+        }
+        Ok(value)
+    }
+}
+
+/// What a [Command]'s argument has to be
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ArgKind<'a> {
+    /// Any word
+    #[default]
+    Text,
+    /// A whole number, like `-3`
+    Integer,
+    /// Any number, like `2.5`
+    Number,
+    /// One of these words, which are completed
+    Choice(&'a [&'a str]),
+}
+
+/// A command registered with [Commands]: its name, the arguments it takes in order, and the
+/// flags it takes anywhere after its name
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Command<'a> {
+    pub name: &'a str,
+    /// The name and kind of each argument
+    pub args: Vec<(&'a str, ArgKind<'a>)>,
+    /// Flags, like `--force`
+    pub flags: Vec<&'a str>,
+}
+
+impl<'a> Command<'a> {
+    /// Constructs a Command which takes no arguments or flags
+    pub fn new(name: &'a str) -> Self {
+        Self { name, args: vec![], flags: vec![] }
+    }
+
+    /// Adds an argument, taken after those added before it
+    pub fn arg(mut self, name: &'a str, kind: ArgKind<'a>) -> Self {
+        self.args.push((name, kind));
+        self
+    }
+
+    /// Adds a flag
+    pub fn flag(mut self, flag: &'a str) -> Self {
+        self.flags.push(flag);
+        self
+    }
+}
+
+/// The commands of a REPL, typed as the first word of a line. They're parsed and checked
+/// against the arguments they take, and [ReadAndBuilder::commands] completes and hints them.
+///
+/// ```
+/// use repline::prebaked::{ArgKind, Command, Commands};
+///
+/// let commands = Commands::new()
+///     .command(Command::new("open").arg("file", ArgKind::Text).flag("--force"))
+///     .command(Command::new("speed").arg("mode", ArgKind::Choice(&["fast", "slow"])));
+///
+/// assert_eq!(commands.hint("speed ").as_deref(), Some("<mode: fast|slow>"));
+/// let call = commands.parse("open --force notes.txt")?.unwrap();
+/// assert_eq!((call.name, call.args, call.flags), ("open", vec!["notes.txt"], vec!["--force"]));
+/// assert!(commands.parse("speed medium").is_err());
+/// # Ok::<(), repline::prebaked::CommandError>(())
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Commands<'a> {
+    commands: Vec<Command<'a>>,
+}
+
+/// A line parsed by [Commands::parse], borrowing its words
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Invocation<'l> {
+    pub name: &'l str,
+    /// The arguments, in order
+    pub args: Vec<&'l str>,
+    pub flags: Vec<&'l str>,
+}
+
+/// Why [Commands::parse] rejected a line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CommandError {
+    /// No command has this name
+    Unknown(String),
+    /// The command doesn't take this flag
+    UnknownFlag(String),
+    /// The named argument wasn't given
+    Missing(String),
+    /// An argument was given past the last one the command takes
+    Extra(String),
+    /// The named argument isn't of its [kind](ArgKind)
+    Invalid { arg: String, value: String },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown(name) => write!(f, "unknown command {name:?}"),
+            Self::UnknownFlag(flag) => write!(f, "unknown flag {flag:?}"),
+            Self::Missing(arg) => write!(f, "missing <{arg}>"),
+            Self::Extra(value) => write!(f, "unexpected argument {value:?}"),
+            Self::Invalid { arg, value } => write!(f, "{value:?} isn't a valid <{arg}>"),
+        }
+    }
+}
+
+impl Error for CommandError {}
+
+impl<'a> Commands<'a> {
+    /// Constructs an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a command, replacing any with the same name
+    pub fn command(mut self, command: Command<'a>) -> Self {
+        self.commands.retain(|c| c.name != command.name);
+        self.commands.push(command);
+        self
+    }
+
+    /// Finds the command named `name`
+    pub fn get(&self, name: &str) -> Option<&Command<'a>> {
+        self.commands.iter().find(|c| c.name == name)
+    }
+
+    /// Splits `line` into the command it names, its arguments, and its flags, checking them
+    /// against what the command takes. A blank line is `None`.
+    pub fn parse<'l>(&self, line: &'l str) -> Result<Option<Invocation<'l>>, CommandError> {
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            return Ok(None);
         };
-        print!("\x1b[G\x1b[J");
-        match f(&mut rl, &line) {
-            Ok(Response::Accept) => rl.accept(),
-            Ok(Response::Deny) => rl.deny(),
-            Ok(Response::Break) => break,
-            Ok(Response::Continue) => continue,
-            Err(e) => rl.print_inline(format_args!("    \x1b[91m{e}\x1b[0m"))?,
+        let command = self
+            .get(name)
+            .ok_or_else(|| CommandError::Unknown(name.into()))?;
+        let mut call = Invocation { name, ..Default::default() };
+        for word in words {
+            match word.starts_with('-') && !is_number(word) {
+                true if command.flags.contains(&word) => call.flags.push(word),
+                true => return Err(CommandError::UnknownFlag(word.into())),
+                false => call.args.push(word),
+            }
+        }
+        if let Some(extra) = call.args.get(command.args.len()) {
+            return Err(CommandError::Extra(extra.to_string()));
+        }
+        if let Some((arg, _)) = command.args.get(call.args.len()) {
+            return Err(CommandError::Missing(arg.to_string()));
         }
+        for (&value, &(arg, kind)) in call.args.iter().zip(&command.args) {
+            let valid = match kind {
+                ArgKind::Text => true,
+                ArgKind::Integer => value.parse::<i64>().is_ok(),
+                ArgKind::Number => is_number(value),
+                ArgKind::Choice(choices) => choices.contains(&value),
+            };
+            if !valid {
+                let (arg, value) = (arg.into(), value.into());
+                return Err(CommandError::Invalid { arg, value });
+            }
+        }
+        Ok(Some(call))
+    }
+
+    /// Returns the argument expected at the end of `line`, like `<file>`, or `None` once the
+    /// command has all of its arguments, or isn't known
+    pub fn hint(&self, line: &str) -> Option<String> {
+        let mut words = line.split_whitespace();
+        let command = self.get(words.next()?)?;
+        let mut given = words
+            .filter(|w| !w.starts_with('-') || is_number(w))
+            .count();
+        // the word being typed is the argument hinted
+        if !line.ends_with(char::is_whitespace) {
+            given = given.saturating_sub(1);
+        }
+        let (arg, kind) = command.args.get(given)?;
+        Some(match kind {
+            ArgKind::Text => format!("<{arg}>"),
+            ArgKind::Integer => format!("<{arg}: integer>"),
+            ArgKind::Number => format!("<{arg}: number>"),
+            ArgKind::Choice(choices) => format!("<{arg}: {}>", choices.join("|")),
+        })
+    }
+}
+
+/// Completes command names as the first word, then the flags of the command, and the
+/// choices of its arguments
+impl Completer for Commands<'_> {
+    fn complete(&mut self, line: &str, cursor: usize, word: Range<usize>) -> Vec<String> {
+        let typed = &line[word.start..cursor];
+        let matching = |words: &[&str]| -> Vec<String> {
+            words
+                .iter()
+                .filter(|w| w.starts_with(typed))
+                .map(|w| w.to_string())
+                .collect()
+        };
+        let mut before = line[..word.start].split_whitespace();
+        let Some(name) = before.next() else {
+            let names: Vec<_> = self.commands.iter().map(|c| c.name).collect();
+            return matching(&names);
+        };
+        let Some(command) = self.get(name) else {
+            return vec![];
+        };
+        if typed.starts_with('-') {
+            return matching(&command.flags);
+        }
+        let given = before
+            .filter(|w| !w.starts_with('-') || is_number(w))
+            .count();
+        match command.args.get(given) {
+            Some((_, ArgKind::Choice(choices))) => matching(choices),
+            _ => vec![],
+        }
+    }
+}
+
+/// Whether `word` is a number, rather than a flag, when it starts with `-`
+fn is_number(word: &str) -> bool {
+    word.parse::<f64>().is_ok()
+}
+
+/// Runs the menu loop of [read_until] on `rl`, showing the closure's errors as
+/// `format_error` makes them
+fn run<B, F>(
+    rl: &mut Repline<'_, Stdin>,
+    mut f: F,
+    format_error: &mut FormatFn<'_>,
+) -> Result<Option<B>, RlError>
+where
+    F: FnMut(&mut Repline<'_, Stdin>, &str) -> Reply<B>,
+{
+    loop {
+        // lines other sessions appended to a shared history file; failing that changes nothing
+        let _ = rl.reload_history();
+        let read = rl.read();
+        let Some(line) = received(rl, read)? else {
+            return Ok(None);
+        };
+        rl.begin_busy()?;
+        let start = Instant::now();
+        let response = f(rl, &line);
+        rl.set_elapsed(start.elapsed());
+        rl.end_busy()?;
+        if let ControlFlow::Break(value) = respond(rl, response, format_error)? {
+            return Ok(value);
+        }
+    }
+}
+
+/// Implements a basic menu loop like [read_and_mut], which awaits each key and the closure,
+/// for REPLs which await inside the handler, like database shells or network clients.
+///
+/// ```no_run
+/// use repline::prebaked::{Response, read_and_async};
+///
+/// # async fn query(sql: &str) -> Result<usize, std::io::Error> { Ok(sql.len()) }
+/// # async fn repl() -> Result<(), repline::Error> {
+/// read_and_async("", "sql> ", "...> ", async |_, line| {
+///     let rows = query(line).await?;
+///     println!("{rows} rows");
+///     Ok(Response::Accept)
+/// })
+/// .await
+/// # }
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn read_and_async<F>(
+    color: &str,
+    begin: &str,
+    again: &str,
+    mut f: F,
+) -> Result<(), RlError>
+where
+    F: AsyncFnMut(&mut Repline<'_, Stdin>, &str) -> Result<Response, Box<dyn Error>>,
+{
+    let rl = &mut Repline::auto(color, begin, again);
+    let format_error = &mut |e: &dyn Error| e.to_string();
+    loop {
+        let _ = rl.reload_history();
+        let read = rl.read_async().await;
+        let Some(line) = received(rl, read)? else {
+            return Ok(());
+        };
+        rl.begin_busy()?;
+        let start = Instant::now();
+        let response = f(rl, &line).await.map(ControlFlow::<(), _>::Continue);
+        rl.set_elapsed(start.elapsed());
+        rl.end_busy()?;
+        if let ControlFlow::Break(_) = respond(rl, response, format_error)? {
+            return Ok(());
+        }
+    }
+}
+
+/// Takes what a read of the menu loop returned, clearing it off the screen for the closure,
+/// or returns `None` if it ends the loop
+fn received(
+    rl: &mut Repline<'_, Stdin>,
+    read: Result<String, RlError>,
+) -> Result<Option<String>, RlError> {
+    let interactive = rl.mode() == ReadMode::Interactive;
+    let line = match read {
+        Err(RlError::CtrlC(_)) => return Ok(None),
+        Err(RlError::CtrlD(line)) => {
+            rl.deny();
+            line
+        }
+        Ok(line) => line,
+        Err(RlError::EndOfInput) if !interactive => return Ok(None),
+        Err(e) => Err(e)?,
+    };
+    if interactive {
+        rl.write_out("\x1b[G\x1b[J")?;
+    }
+    Ok(Some(line))
+}
+
+/// Does what the closure of the menu loop asked, showing its errors as `format_error` makes
+/// them, and breaks with the loop's value once it ends
+fn respond<B>(
+    rl: &mut Repline<'_, Stdin>,
+    response: Reply<B>,
+    format_error: &mut FormatFn<'_>,
+) -> Result<ControlFlow<Option<B>>, RlError> {
+    match response {
+        Ok(ControlFlow::Break(value)) => return Ok(ControlFlow::Break(Some(value))),
+        Ok(ControlFlow::Continue(Response::Accept)) => rl.accept(),
+        Ok(ControlFlow::Continue(Response::Deny)) => rl.deny(),
+        Ok(ControlFlow::Continue(Response::Replace(text))) => {
+            rl.deny();
+            rl.add_history(text);
+        }
+        Ok(ControlFlow::Continue(Response::Prefill(text))) => {
+            rl.deny();
+            rl.set_text(&text);
+        }
+        Ok(ControlFlow::Continue(Response::Diagnose(found))) => {
+            let text = rl.text();
+            rl.deny();
+            rl.set_text(text.strip_suffix('\n').unwrap_or(&text));
+            rl.report_diagnostics(found);
+        }
+        Ok(ControlFlow::Continue(Response::Break)) => return Ok(ControlFlow::Break(None)),
+        Ok(ControlFlow::Continue(Response::Continue)) => {
+            // the closure may have cleared the screen, and the lines gathered so far with it
+            rl.detect_screen_cleared()?;
+        }
+        Err(e) if rl.mode() == ReadMode::Interactive => {
+            let message = rl.theme().error.apply(format_error(&*e));
+            rl.print_inline(format_args!("    {message}"))?
+        }
+        Err(e) => eprintln!("{}", format_error(&*e)),
+    }
+    Ok(ControlFlow::Continue(()))
+}
+
+/// A validator for [read_and] which collects indented blocks, like Python's REPL.
+///
+/// Once a line ends with `opener` (like `:`), or the last line is indented, Enter keeps
+/// collecting lines ([Response::Continue]) until an empty line is entered. Then, or if no
+/// block was opened, the whole input is passed on to `f`.
+///
+/// ```no_run
+/// use repline::prebaked::{blocks, read_and, Response};
+///
+/// read_and("\x1b[33m", ">>> ", "... ", blocks(":", |code| {
+///     print!("{code}");
+///     Ok(Response::Accept)
+/// }))?;
+/// # Ok::<(), repline::Error>(())
+/// ```
+pub fn blocks<F>(opener: &str, mut f: F) -> impl FnMut(&str) -> Result<Response, Box<dyn Error>>
+where F: FnMut(&str) -> Result<Response, Box<dyn Error>> {
+    move |text| match block_is_open(opener, text) {
+        true => Ok(Response::Continue),
+        false => f(text),
+    }
+}
+
+/// Returns true if `text` opens a block, by a line ending with `opener` or by an indented
+/// last line, which hasn't been closed by an empty line
+pub fn block_is_open(opener: &str, text: &str) -> bool {
+    let mut lines = text.lines();
+    let Some(last) = lines.next_back() else {
+        return false;
+    };
+    let opened = |line: &str| !opener.is_empty() && line.trim_end().ends_with(opener);
+    match last.trim().is_empty() {
+        true => false,
+        false => last.starts_with(char::is_whitespace) || opened(last) || lines.any(opened),
+    }
+}
+
+/// Predetermined answers for the prebaked prompts, keyed by field name.
+///
+/// A prompt whose field has an answer returns it without prompting, so that otherwise
+/// interactive flows can be automated with flags or environment variables.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Answers(HashMap<String, String>);
+
+impl Answers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collects answers from environment variables named `prefix` followed by the field
+    /// name in upper case, like `APP_NAME` for the field `name` with the prefix `APP_`
+    pub fn from_env(prefix: &str, fields: &[&str]) -> Self {
+        let var = |field: &&str| std::env::var(format!("{prefix}{}", field.to_uppercase()));
+        fields
+            .iter()
+            .filter_map(|field| Some((*field, var(field).ok()?)))
+            .collect()
+    }
+
+    /// Sets the answer for `field`
+    pub fn insert(&mut self, field: impl Into<String>, answer: impl Into<String>) {
+        self.0.insert(field.into(), answer.into());
+    }
+
+    /// Returns the answer for `field`, if there is one
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.0.get(field).map(String::as_str)
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Answers {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+}
+
+/// Asks for a single line of input for `field`, unless it's in `answers`.
+///
+/// The returned line doesn't include the newline.
+pub fn prompt_for(field: &str, prompt: &str, answers: &Answers) -> Result<String, RlError> {
+    if let Some(answer) = answers.get(field) {
+        return Ok(answer.to_string());
+    }
+    read_one(&mut Repline::auto("", prompt, prompt))
+}
+
+/// Asks for a single line of input for `field` like [prompt_for], which only lets the user
+/// type text `filter` accepts, showing `hint` when a keystroke is rejected (see
+/// [Repline::set_filter]).
+///
+/// An answer in `answers` which `filter` rejects is asked for again.
+pub fn prompt_filtered<F>(
+    field: &str,
+    prompt: &str,
+    answers: &Answers,
+    mut filter: F,
+    hint: &str,
+) -> Result<String, RlError>
+where
+    F: FnMut(&str) -> bool,
+{
+    if let Some(answer) = answers.get(field).filter(|answer| filter(answer)) {
+        return Ok(answer.to_string());
+    }
+    let mut rl = Repline::auto("", prompt, prompt);
+    rl.set_filter(filter, hint);
+    read_one(&mut rl)
+}
+
+/// Asks a yes-or-no question for `field`, unless it's in `answers`, repeating the question
+/// until it's answered with `y`, `yes`, `n`, or `no`
+pub fn confirm(field: &str, question: &str, answers: &Answers) -> Result<bool, RlError> {
+    let parse = |answer: &str| match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Some(true),
+        "n" | "no" => Some(false),
+        _ => None,
+    };
+    if let Some(answer) = answers.get(field).and_then(parse) {
+        return Ok(answer);
+    }
+    let prompt = format!("{question} [y/n] ");
+    let mut rl = Repline::auto("", &prompt, &prompt);
+    loop {
+        if let Some(answer) = parse(&read_one(&mut rl)?) {
+            return Ok(answer);
+        }
+    }
+}
+
+/// Asks for each of `fields`, given as field names and prompts, in order, skipping those
+/// in `answers`. Returns every field's answer.
+pub fn wizard(fields: &[(&str, &str)], answers: &Answers) -> Result<Answers, RlError> {
+    let mut out = Answers::new();
+    for &(field, prompt) in fields {
+        out.insert(field, prompt_for(field, prompt, answers)?);
+    }
+    Ok(out)
+}
+
+/// Asks `question`, offering `choices` as keys and what they stand for, and returns the
+/// key of the one the user picks with a single keystroke. `Enter` picks the first.
+///
+/// ```no_run
+/// # use repline::prebaked::choose;
+/// match choose("Save changes?", &[('y', "yes"), ('n', "no"), ('d', "diff")])? {
+///     'y' => { /* save */ }
+///     'd' => { /* show the diff */ }
+///     _ => {}
+/// }
+/// # Ok::<(), repline::Error>(())
+/// ```
+///
+/// Keys are matched ignoring case. Outside of [ReadMode::Interactive], a line is read
+/// instead, and its first character picks the choice. `Ctrl+C` and `Ctrl+D` end the menu
+/// with their errors.
+///
+/// # Panics
+/// If `choices` is empty
+pub fn choose(question: &str, choices: &[(char, &str)]) -> Result<char, RlError> {
+    assert!(!choices.is_empty(), "choose needs at least one choice");
+    let pick = |c: char| {
+        choices
+            .iter()
+            .find(|(key, _)| key.to_lowercase().eq(c.to_lowercase()))
+    };
+    let listed: Vec<_> = choices
+        .iter()
+        .map(|(key, label)| format!("({key}) {label}"))
+        .collect();
+    let prompt = format!("{question} {} ", listed.join(", "));
+    let mut rl = Repline::auto("", &prompt, &prompt);
+    if rl.mode() != ReadMode::Interactive {
+        loop {
+            let line = read_one(&mut rl)?;
+            match line.trim().chars().next() {
+                None => return Ok(choices[0].0),
+                Some(c) => {
+                    if let Some(&(key, _)) = pick(c) {
+                        return Ok(key);
+                    }
+                }
+            }
+        }
+    }
+    rl.write_out(&prompt)?;
+    let (key, label) = loop {
+        match rl.read_key()? {
+            Key::Enter => break choices[0],
+            Key::Char(c) => {
+                if let Some(&choice) = pick(c) {
+                    break choice;
+                }
+            }
+            Key::Ctrl('c') => {
+                rl.write_out("\n")?;
+                return Err(RlError::CtrlC(String::new()));
+            }
+            Key::Ctrl('d') => {
+                rl.write_out("\n")?;
+                return Err(RlError::CtrlD(String::new()));
+            }
+            _ => {}
+        }
+    };
+    rl.write_out(format_args!("{label}\n"))?;
+    Ok(key)
+}
+
+/// Reads one line, and clears it from the buffer
+fn read_one(rl: &mut Repline<'_, Stdin>) -> Result<String, RlError> {
+    let mut line = rl.read()?;
+    if rl.mode() == ReadMode::Interactive {
+        rl.write_out("\x1b[G\x1b[J")?;
+    }
+    rl.deny();
+    line.truncate(line.trim_end_matches('\n').len());
+    Ok(line)
+}
+
+#[cfg(all(test, feature = "screen"))]
+mod tests {
+    use super::{ArgKind, Command, Commands};
+    use crate::testing::{Replay, Terminal, assert_returned_line, assert_screen_contains};
+
+    #[test]
+    fn commands_are_completed_and_hinted() {
+        let commands = Commands::new()
+            .command(
+                Command::new("open")
+                    .arg("file", ArgKind::Text)
+                    .flag("--force"),
+            )
+            .command(Command::new("speed").arg("mode", ArgKind::Choice(&["fast", "slow"])));
+        let terminal = Terminal::new(30, 6);
+        // the input runs out with the argument still to type
+        let mut rl = terminal.repline(Replay::new("sp\t "), "> ", ". ");
+        let hints = commands.clone();
+        rl.set_completer(commands.clone());
+        rl.set_echo(move |line| hints.hint(line));
+        assert!(rl.read().is_err());
+        // the hint is drawn at the right edge
+        assert_screen_contains(&terminal.screen(), "<mode: fast|slow>");
+
+        let mut rl = rl.swap_input(Replay::new("f\t\r"));
+        assert_returned_line(rl.read(), "speed fast");
+        rl.accept();
+        let mut rl = rl.swap_input(Replay::new("open --f\t x\r"));
+        assert_returned_line(rl.read(), "open --force x");
     }
-    Ok(())
 }
@@ -0,0 +1,36 @@
+//! Prompts made at draw time: a [Prompt] makes the prompt before each line of the buffer,
+//! like `in[3]> `, the time, or the state of a repository, in place of fixed prompts.
+
+/// Makes the prompt drawn before each line of the buffer, in place of the begin and again
+/// prompts. It's asked again before each read, and after each key.
+///
+/// Implemented for all `FnMut(usize, &str) -> String`.
+///
+/// ```no_run
+/// # use repline::Repline;
+/// use std::{cell::Cell, rc::Rc};
+///
+/// let count = Rc::new(Cell::new(1));
+/// let mut rl = Repline::new("", "", "");
+/// rl.set_dynamic_prompt({
+///     let count = count.clone();
+///     move |line: usize, _: &str| match line {
+///         0 => format!("in[{}]> ", count.get()),
+///         _ => "   ...> ".to_string(),
+///     }
+/// });
+/// while rl.read().is_ok() {
+///     count.set(count.get() + 1);
+///     rl.accept();
+/// }
+/// ```
+pub trait Prompt {
+    /// Returns the prompt for line `line` (counting from 0) of the buffer `text`
+    fn prompt(&mut self, line: usize, text: &str) -> String;
+}
+
+impl<F: FnMut(usize, &str) -> String> Prompt for F {
+    fn prompt(&mut self, line: usize, text: &str) -> String {
+        self(line, text)
+    }
+}
@@ -0,0 +1,170 @@
+//! A Python extension module, for using repline as the line editor of a Python REPL.
+//!
+//! Build it with [maturin](https://www.maturin.rs): `maturin build --features pyo3`.
+//!
+//! ```python
+//! import repline
+//!
+//! rl = repline.Repline("", "> ", "  ")
+//! rl.set_completer(lambda line, cursor, start, end: ["print", "pass"])
+//! while True:
+//!     try:
+//!         line = rl.read()
+//!     except KeyboardInterrupt:
+//!         break
+//!     rl.accept()
+//! ```
+//!
+//! Positions passed to and from Python callbacks count code points, like Python's `str`.
+
+use crate::{
+    diagnostics::{Diagnostic, Severity},
+    error::Error,
+    repline::Repline,
+};
+use pyo3::{
+    exceptions::{PyEOFError, PyIOError, PyKeyboardInterrupt, PyValueError},
+    prelude::*,
+};
+use std::{io::Stdin, ops::Range, path::PathBuf};
+
+/// A [Repline] reading from stdin, which owns its prompts
+#[pyclass(name = "Repline", unsendable)]
+pub struct PyRepline {
+    // declared before the prompts it borrows, so that it's dropped first
+    rl: Repline<'static, Stdin>,
+    _prompts: [Box<str>; 3],
+}
+
+/// Converts a byte index of `text` into a code point index
+fn chars(text: &str, at: usize) -> usize {
+    text.get(..at).map_or(0, |s| s.chars().count())
+}
+
+/// Converts a code point index of `text` into a byte index
+fn bytes(text: &str, at: usize) -> usize {
+    text.char_indices().nth(at).map_or(text.len(), |(i, _)| i)
+}
+
+#[pymethods]
+impl PyRepline {
+    #[new]
+    #[pyo3(signature = (color = "", begin = "> ", again = "  "))]
+    fn new(color: &str, begin: &str, again: &str) -> Self {
+        let [color, begin, again]: [Box<str>; 3] = [color.into(), begin.into(), again.into()];
+        // The prompts live on the heap until the Repline is dropped, so they outlive it
+        let extend = |s: &str| -> &'static str { unsafe { &*(s as *const str) } };
+        let rl = Repline::new(extend(&color), extend(&begin), extend(&again));
+        Self { rl, _prompts: [color, begin, again] }
+    }
+
+    /// Reads a line. Raises `KeyboardInterrupt` on Ctrl+C, and `EOFError` on Ctrl+D.
+    fn read(&mut self) -> PyResult<String> {
+        self.rl.read().map_err(|e| match e {
+            Error::CtrlC(_) => PyKeyboardInterrupt::new_err(()),
+            Error::CtrlD(_) => PyEOFError::new_err(()),
+            Error::IoFailure(e) | Error::Terminal(e) => e.into(),
+            e => PyIOError::new_err(e.to_string()),
+        })
+    }
+
+    /// Adds the last line read to the history, and clears it
+    fn accept(&mut self) {
+        self.rl.accept()
+    }
+
+    /// Clears the last line read, without adding it to the history
+    fn deny(&mut self) {
+        self.rl.deny()
+    }
+
+    /// Adds `line` to the history
+    fn add_history(&mut self, line: String) {
+        self.rl.add_history(line)
+    }
+
+    /// Reads the history file at `path`, merging its entries into the history
+    fn load_history(&mut self, path: PathBuf) -> PyResult<()> {
+        self.rl
+            .load_history(path)
+            .map_err(|e| PyIOError::new_err(e.to_string()))
+    }
+
+    /// Writes the history to a history file at `path`, replacing its contents
+    fn save_history(&self, path: PathBuf) -> PyResult<()> {
+        self.rl
+            .save_history(path)
+            .map_err(|e| PyIOError::new_err(e.to_string()))
+    }
+
+    /// Appends each accepted line to the history file at `path`
+    fn append_history(&mut self, path: PathBuf) -> PyResult<()> {
+        self.rl
+            .append_history(path)
+            .map_err(|e| PyIOError::new_err(e.to_string()))
+    }
+
+    /// The previously entered lines, where the last is the most relevant
+    #[getter]
+    fn history(&self) -> Vec<String> {
+        let history = self.rl.history();
+        (0..history.len())
+            .filter_map(|i| history.get(i).map(str::to_owned))
+            .collect()
+    }
+
+    /// Sets the completion callback, `completer(line, cursor, start, end) -> list[str]`,
+    /// which returns candidates to replace `line[start:end]`.
+    ///
+    /// Exceptions raised by the callback are treated as having no candidates.
+    fn set_completer(&mut self, completer: Py<PyAny>) {
+        self.rl
+            .set_completer(move |line: &str, cursor: usize, word: Range<usize>| {
+                let args = (
+                    line,
+                    chars(line, cursor),
+                    chars(line, word.start),
+                    chars(line, word.end),
+                );
+                Python::attach(|py| completer.call1(py, args)?.extract::<Vec<String>>(py))
+                    .unwrap_or_default()
+            })
+    }
+
+    /// Sets the diagnostics callback, `diagnose(line) -> list[(start, end, severity, message)]`,
+    /// where `severity` is one of `"hint"`, `"info"`, `"warning"`, or `"error"`.
+    ///
+    /// Exceptions raised by the callback are treated as finding no problems.
+    fn set_diagnostics(&mut self, diagnose: Py<PyAny>) {
+        self.rl.set_diagnostics(move |line: &str| {
+            let found = Python::attach(|py| {
+                diagnose
+                    .call1(py, (line,))?
+                    .extract::<Vec<(usize, usize, String, String)>>(py)
+            });
+            let diagnostic = |(start, end, severity, message): (usize, usize, String, String)| {
+                let severity = match severity.as_str() {
+                    "hint" => Severity::Hint,
+                    "info" => Severity::Info,
+                    "warning" => Severity::Warning,
+                    "error" => Severity::Error,
+                    other => return Err(PyValueError::new_err(format!("bad severity: {other}"))),
+                };
+                Ok(Diagnostic::new(
+                    bytes(line, start)..bytes(line, end),
+                    severity,
+                    message,
+                ))
+            };
+            found
+                .and_then(|found| found.into_iter().map(diagnostic).collect())
+                .unwrap_or_default()
+        })
+    }
+}
+
+/// The `repline` Python module
+#[pymodule(name = "repline")]
+fn repline_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyRepline>()
+}
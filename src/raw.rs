@@ -1,24 +1,77 @@
-//! Sets the terminal to [`raw`] mode for the duration of the returned object's lifetime.
+//! Sets the terminal to [`raw`] mode for as long as a [`RawGuard`] lives.
+//!
+//! Guards nest: the terminal stays raw until the last one is dropped, so a read inside a
+//! closure called from another read doesn't leave the outer one in cooked mode.
 
-/// Sets the terminal to raw mode for the duration of the returned object's lifetime.
-pub fn raw() -> impl Drop {
-    Raw::default()
-}
+use crate::error::{Error, ReplResult};
+use std::{sync::Mutex, time::Duration};
 
-/// Internal [`Drop`] object for [`raw`]
-struct Raw();
+/// The number of [RawGuard]s alive
+static GUARDS: Mutex<usize> = Mutex::new(0);
 
-impl Default for Raw {
-    fn default() -> Self {
+/// Sets the terminal to raw mode, if it isn't already, until the returned guard and every
+/// other guard alive are dropped
+pub fn raw() -> ReplResult<RawGuard> {
+    let mut guards = GUARDS.lock().unwrap_or_else(|e| e.into_inner());
+    // the terminal may have been paused out of raw mode while other guards are alive
+    if *guards == 0 || !crossterm::terminal::is_raw_mode_enabled()? {
         std::thread::yield_now();
-        crossterm::terminal::enable_raw_mode().expect("should be able to transition into raw mode");
-        Raw()
+        crossterm::terminal::enable_raw_mode().map_err(Error::Terminal)?;
     }
+    *guards += 1;
+    Ok(RawGuard(()))
 }
 
-impl Drop for Raw {
+/// Keeps the terminal in raw mode while it lives. Returned by [raw].
+#[must_use = "the terminal leaves raw mode when the guard is dropped"]
+#[derive(Debug)]
+pub struct RawGuard(());
+
+impl Drop for RawGuard {
     fn drop(&mut self) {
-        crossterm::terminal::disable_raw_mode()
-            .expect("should be able to transition out of raw mode");
+        let mut guards = GUARDS.lock().unwrap_or_else(|e| e.into_inner());
+        *guards -= 1;
+        if *guards == 0 {
+            // there's nothing to be done about it here
+            let _ = crossterm::terminal::disable_raw_mode();
+        }
+    }
+}
+
+/// Runs `read` with reads from the terminal on stdin giving up after `timeout`, rounded up to
+/// a tenth of a second, instead of waiting for a key, like a read at the end of input.
+///
+/// Returns `None` without running `read` if stdin isn't a terminal, or outside of Unix.
+#[cfg(unix)]
+pub(crate) fn with_read_timeout<T>(timeout: Duration, read: impl FnOnce() -> T) -> Option<T> {
+    use std::{io::IsTerminal, mem::MaybeUninit};
+    if !std::io::stdin().is_terminal() {
+        return None;
+    }
+    let mut old = MaybeUninit::<libc::termios>::uninit();
+    // SAFETY: stdin is a terminal, whose attributes fill in `old` if tcgetattr succeeds
+    let old = unsafe {
+        if libc::tcgetattr(libc::STDIN_FILENO, old.as_mut_ptr()) != 0 {
+            return None;
+        }
+        old.assume_init()
+    };
+    // reads return once a byte arrives, or once `timeout` passes without one
+    let mut new = old;
+    let tenths = timeout.as_millis().div_ceil(100).clamp(1, 255) as libc::cc_t;
+    (new.c_cc[libc::VMIN], new.c_cc[libc::VTIME]) = (0, tenths);
+    // SAFETY: `new` is the terminal's attributes, with the timeout changed
+    if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &new) } != 0 {
+        return None;
     }
+    let out = read();
+    // SAFETY: `old` is the terminal's attributes, as they were
+    unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &old) };
+    Some(out)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn with_read_timeout<T>(timeout: Duration, read: impl FnOnce() -> T) -> Option<T> {
+    let _ = (timeout, read);
+    None
 }
@@ -0,0 +1,111 @@
+//! Offloads drawing to a background thread, so that a slow terminal (like a serial console)
+//! doesn't hold up reading and editing.
+//!
+//! Each flush hands the output drawn since the last one to the writer thread, then waits
+//! for it to be written, but only up to a latency budget. Output handed off while the thread
+//! is still busy is coalesced, and written all at once when it's free.
+
+use std::{
+    io::{Error, Result, Write},
+    mem::take,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+/// A [Writer](Write) which writes to another writer on a background thread
+pub struct Renderer {
+    pending: Vec<u8>,    // output drawn since the last flush
+    shared: Arc<Shared>, // output handed off to the writer thread
+    budget: Duration,    // how long a flush may wait on the writer thread
+    thread: Option<JoinHandle<()>>,
+}
+
+/// The state shared with the writer thread
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    frame: Vec<u8>,       // output waiting to be written
+    writing: bool,        // whether the writer thread is writing
+    closed: bool,         // whether the writer thread should stop once it's done
+    error: Option<Error>, // the last error the writer thread ran into
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Renderer {
+    /// Starts a writer thread for `inner`, which flushes wait on for at most `budget`
+    pub fn new(mut inner: Box<dyn Write + Send>, budget: Duration) -> Self {
+        let shared = Arc::new(Shared::default());
+        let thread = std::thread::spawn({
+            let shared = shared.clone();
+            move || loop {
+                let mut state = shared.lock();
+                while state.frame.is_empty() && !state.closed {
+                    state = shared
+                        .changed
+                        .wait(state)
+                        .unwrap_or_else(|e| e.into_inner());
+                }
+                if state.frame.is_empty() {
+                    break;
+                }
+                let frame = take(&mut state.frame);
+                state.writing = true;
+                drop(state);
+
+                let result = inner.write_all(&frame).and_then(|_| inner.flush());
+                let mut state = shared.lock();
+                state.writing = false;
+                state.error = result.err().or(state.error.take());
+                shared.changed.notify_all();
+            }
+        });
+        Self { pending: vec![], shared, budget, thread: Some(thread) }
+    }
+}
+
+impl Write for Renderer {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    /// Hands the output to the writer thread, and waits for it to be written, up to the
+    /// latency budget
+    fn flush(&mut self) -> Result<()> {
+        let Self { pending, shared, budget, .. } = self;
+        let mut state = shared.lock();
+        if let Some(error) = state.error.take() {
+            return Err(error);
+        }
+        state.frame.append(pending);
+        shared.changed.notify_all();
+        let busy = |state: &mut State| state.writing || !state.frame.is_empty();
+        let _ = shared.changed.wait_timeout_while(state, *budget, busy);
+        Ok(())
+    }
+}
+
+impl Drop for Renderer {
+    /// Finishes writing everything, so that nothing drawn is lost, or printed out of order
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.frame.append(&mut self.pending);
+        state.closed = true;
+        self.shared.changed.notify_all();
+        drop(state);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
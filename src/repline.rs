@@ -3,296 +3,4811 @@
 //! This module is in charge of parsing keyboard input and interpreting it for the line editor.
 #![allow(clippy::unbuffered_bytes)]
 
-use crate::{editor::Editor, error::*, iter::*, raw::raw};
+#[cfg(feature = "clipboard")]
+use crate::clipboard;
+#[cfg(feature = "serde")]
+use crate::session::{self, Session};
+use crate::{
+    backend::{self, Backend},
+    completion::{Completer, Completion},
+    diagnostics::{Diagnostic, Diagnostics, Linter},
+    editor::{Editor, first_restyled},
+    error::*,
+    format::Formatter,
+    handle::ReplineHandle,
+    highlight::{Highlight, Highlighter},
+    history::{Conflict, Direction, Entry, History, HistoryFile, Meta, Ranking},
+    iter::*,
+    keymap::{self, EditCommand, Key, Keymap, Modifiers, Unhandled, UnknownKey},
+    kill::KillRing,
+    middleware::{Context, Dispatch, Middleware},
+    multiplexer::Multiplexer,
+    prompt::Prompt,
+    raw::{self, RawGuard, raw},
+    render::Renderer,
+    snippet::{self, Expansion},
+    strings::Strings,
+    theme::Theme,
+    validate::{Validation, Validator},
+};
+use crossterm::{
+    cursor::{Hide, MoveDown, MoveToColumn, MoveUp, Show},
+    queue,
+    style::{Print, PrintStyledContent},
+    terminal::{Clear, ClearType},
+};
 use std::{
+    borrow::Cow,
+    cell::Cell,
     collections::VecDeque,
-    io::{Bytes, Read, Result, Write, stdout},
+    fs::{File, OpenOptions},
+    io::{
+        BufReader, BufWriter, Bytes, Cursor, IsTerminal, Read, Result, Write, stderr, stdin, stdout,
+    },
+    ops::Range,
+    path::Path,
+    rc::Rc,
+    time::{Duration, Instant},
 };
+use unicode_width::UnicodeWidthStr;
+
+pub use crate::editor::{EditorState, Frame, WordChars};
+
+/// The usual brackets and quotes, for [set_pairs](Repline::set_pairs)
+pub const BRACKETS: &[(&str, &str)] = &[("(", ")"), ("[", "]"), ("{", "}"), ("\"", "\"")];
 
 /// Prompts the user, reads the lines. Not much more to it than that.
 #[derive(Debug)]
 pub struct Repline<'a, R: Read> {
-    input: Chars<Flatten<Result<u8>, Bytes<R>>>,
+    input: Chars<Bytes<R>>,
 
-    history_cap: usize,
-    history: VecDeque<String>, // previous lines
-    hindex: usize,             // current index into the history buffer
+    history: History,           // previous lines
+    hindex: usize,              // current index into the history buffer
+    draft: Option<EditorState>, // the new line being edited before the history was recalled
 
-    ed: Editor<'a>, // the current line buffer
+    pairs: Vec<(&'a str, &'a str)>,    // auto-closing delimiter pairs
+    closers: Vec<usize>,               // closers inserted while typing, as distance from end
+    snippets: Vec<(&'a str, &'a str)>, // snippet triggers and bodies
+    stops: Vec<(usize, usize)>,        // unvisited placeholders, as (distance from end, length)
+    surround: bool,                    // whether typing an opener wraps the selection
+    redraw: bool,                      // whether the next read redraws the whole buffer
+    cleared: bool,                     // whether the screen was cleared from outside
+    unfinished: Option<usize>,         // rows drawn below the cursor by a read ended mid-edit
+    linter: Option<Linter<'a>>,        // checks the buffer as the user types
+    highlight: Option<Highlight<'a>>,  // colors the buffer as the user types
+    formatter: Option<BoxedFormatter<'a>>, // rewrites the buffer on demand
+    validator: Option<BoxedValidator<'a>>, // decides whether Enter submits the buffer
+    prompt: Option<BoxedPrompt<'a>>,   // makes the prompts at draw time
+    line_numbers: bool,                // whether the again prompts are line numbers
+    handle: Option<ReplineHandle>,     // follows the state for other threads
+    middleware: Vec<BoxedMiddleware<'a>>, // layers which see each key before the editor
+    completion: Option<Completion<'a>>, // completes the word under the cursor
+    metrics: Option<Metrics>,          // usage counters for the current read, if enabled
+    strings: Strings<'a>,              // text drawn by repline itself
+
+    ed: Editor<'a>,                            // the current line buffer
+    buffers: Vec<(String, Editor<'a>)>,        // named scratch buffers, if any were added
+    buffer: usize,                             // index of the active scratch buffer
+    tty: Option<File>,                         // the terminal to draw on, instead of stdout
+    backend: Option<Rc<backend::Shared<'a>>>,  // the terminal to draw on, if not a local one
+    stderr: bool,                              // whether to draw on stderr, instead of stdout
+    mirrors: Mirrors<'a>,                      // writers which mirror everything drawn
+    colors: bool,                              // whether colors and styles are drawn
+    watchdog: usize,                           // keys between display checks, or 0 to never check
+    multiplexer: Option<Multiplexer>,          // the multiplexer between repline and the terminal
+    history_file: Option<HistoryFile>,         // accepted lines are appended to this file
+    ignore_space: bool,                        // whether lines starting with a space go unrecorded
+    min_len: usize,                            // the fewest characters a line needs to be recorded
+    ignores: Vec<HistoryIgnore<'a>>,           // lines these accept aren't recorded
+    on_focus: Option<FocusHandler<'a>>,        // called when the terminal gains or loses focus
+    on_unknown: Option<UnknownKeyHandler<'a>>, // called with input which doesn't do anything
+    on_edit: Option<EditHook<'a>>,             // called after each key which changes the buffer
+    mode: ReadMode,                            // whether lines are edited, or read as-is
+    arrows: ArrowKeys,                         // what Up and Down move through
+    bell: Bell,                                // how keys which can't do anything are signaled
+    flashing: bool,                            /* whether a visible bell has reversed the
+                                                * screen, until the next key */
+    line_feed: LineFeed,              // what a line feed on its own is read as
+    after_cr: bool,                   // whether the last key read was a carriage return
+    keymap: Keymap,                   // the command each key runs
+    kills: KillRing,                  // killed text, which can be yanked back
+    last_arg: (usize, usize),         // where the argument yanked last came from, and its length
+    last: Option<EditCommand>,        // the command run before the current one
+    render_budget: Option<Duration>,  // how long a key waits for drawing, if offloaded
+    end: Option<EndPosition>,         // where the last line read ended on screen
+    unread: Option<Key>,              // a key to handle again before reading more
+    escape_timeout: Option<Duration>, // how long after Esc another key starts a sequence
+    injected: VecDeque<char>,         // input pushed by the program, read before the input
+    lossy: bool,                      // whether undecodable input is read as U+FFFD
+    sources: Vec<Source<'a>>,         // sources pushed by the program, the last read first
+    splitter: Option<Splitter<'a>>,   // splits each line read into logical lines
+    split: VecDeque<String>,          // logical lines yet to be returned by read
+    paste: Option<PastePolicy>,       // how keys arriving in a burst are treated, if at all
+    confirm_paste: bool,              // whether multi-line pastes are confirmed before insertion
+    pasted: String,                   // the text of the bracketed paste being handled
+    mouse: bool,                      // whether clicks are captured to move the cursor
+    paused: Option<bool>,             // whether the terminal was raw when pause was called
+    #[cfg(feature = "clipboard")]
+    awaiting_clipboard: bool, // whether the clipboard was asked for by the last key
+    pasting: Pasting,                 // whether the current key arrived in a burst
+    last_key: Option<Instant>,        // when the previous key arrived
+    echo: Option<Echo<'a>>,           // shows a live result beside the line being edited
+    searches: Vec<HistorySearch<'a>>, // history searches which keys can be bound to
+    elapsed_threshold: Option<Duration>, // how long a command takes before it's shown
+    busy_marker: Option<&'a str>,     // drawn where the prompt was while a command runs
+    filter: Option<Filter<'a>>,       // rejects keystrokes which make the buffer invalid
+    max_len: Option<usize>,           // the most characters the buffer can hold
+    submit_full: bool,                // whether filling the buffer submits it
+    auto_indent: Option<AutoIndent<'a>>, // carries indentation onto new lines
+    #[cfg(feature = "events")]
+    events: bool, // whether keys come from crossterm's event reader
+    #[cfg(feature = "events")]
+    idle: Option<IdleHandler<'a>>, // called while no key arrives
 }
 
 impl<'a> Repline<'a, std::io::Stdin> {
+    /// Constructs a [Repline] which reads from stdin, editing lines if it and stdout are
+    /// terminals, and otherwise reading them as-is, without raw mode or escape sequences.
+    /// See [ReadMode::detect].
     pub fn new(color: &'a str, begin: &'a str, again: &'a str) -> Self {
-        Self::with_input(std::io::stdin(), color, begin, again)
+        let mut rl = Self::with_input(std::io::stdin(), color, begin, again);
+        rl.mode = ReadMode::detect();
+        rl
+    }
+
+    /// Constructs a [Repline] which reads from stdin in the [ReadMode] that suits it:
+    /// editing lines on a terminal, or reading them as-is from pipes, files, and
+    /// dumb terminals.
+    ///
+    /// If stdin is a terminal, but stdout is redirected, like `myrepl > log.txt`, it draws on
+    /// stderr instead, so that only what the program prints ends up in the file. See
+    /// [auto_with](Self::auto_with) to draw elsewhere.
+    pub fn auto(color: &'a str, begin: &'a str, again: &'a str) -> Self {
+        Self::auto_with(color, begin, again, Fallback::default())
+    }
+
+    /// Constructs a [Repline] like [auto](Self::auto), which draws where `fallback` says
+    /// when stdin is a terminal, but stdout isn't
+    pub fn auto_with(color: &'a str, begin: &'a str, again: &'a str, fallback: Fallback) -> Self {
+        let mut rl = Self::new(color, begin, again);
+        if rl.mode != ReadMode::Plain || !stdin().is_terminal() || stdout().is_terminal() {
+            return rl;
+        }
+        match fallback {
+            Fallback::Stderr => {
+                rl.mode = ReadMode::detect_stderr();
+                rl.stderr = true;
+            }
+            Fallback::Tty => {
+                if let Ok(tty) = open_tty() {
+                    rl.mode = ReadMode::detect_with(true);
+                    rl.tty = Some(tty);
+                }
+            }
+            Fallback::Plain => {}
+        }
+        rl
+    }
+
+    /// Constructs a [Repline] like [auto](Self::auto), which draws on stderr, so that stdout
+    /// can be piped elsewhere. See [set_stderr](Self::set_stderr).
+    pub fn auto_stderr(color: &'a str, begin: &'a str, again: &'a str) -> Self {
+        let mut rl = Self::new(color, begin, again);
+        rl.mode = ReadMode::detect_stderr();
+        rl.stderr = true;
+        rl
+    }
+}
+
+impl<'a> Repline<'a, File> {
+    /// Constructs a [Repline] which reads from and draws on the controlling terminal,
+    /// rather than stdin and stdout.
+    ///
+    /// This allows prompting the user while stdin or stdout is redirected, like when
+    /// data is piped into the program.
+    pub fn from_tty(color: &'a str, begin: &'a str, again: &'a str) -> Result<Self> {
+        #[cfg(windows)]
+        let (input, output) = (
+            File::open("CONIN$")?,
+            OpenOptions::new().write(true).open("CONOUT$")?,
+        );
+        #[cfg(not(windows))]
+        let (input, output) = {
+            let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+            (tty.try_clone()?, tty)
+        };
+        let mut rl = Self::with_input(input, color, begin, again);
+        rl.tty = Some(output);
+        Ok(rl)
+    }
+}
+
+impl<'a> Repline<'a, Cursor<Vec<u8>>> {
+    /// Constructs a [Repline] which reads the keys of a [script](keymap::script), like
+    /// `"hello<Left><Backspace><Enter>"`, for demos and tests.
+    ///
+    /// ```no_run
+    /// # use repline::Repline;
+    /// let mut rl = Repline::with_script("ls -l<Home><Ctrl+K>pwd<Enter>", "", "> ", ". ")?;
+    /// assert_eq!(rl.read()?, "pwd");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_script(
+        script: &str,
+        color: &'a str,
+        begin: &'a str,
+        again: &'a str,
+    ) -> std::result::Result<Self, UnknownKey> {
+        let bytes = keymap::script(script)?.into_bytes();
+        Ok(Self::with_input(Cursor::new(bytes), color, begin, again))
     }
 }
 
 impl<'a, R: Read> Repline<'a, R> {
     /// Constructs a [Repline] with the given [Reader](Read), color, begin, and again prompts.
+    ///
+    /// The color, like that of every constructor, is only kept for compatibility: it's
+    /// turned into the prompt styles of the [theme](Self::set_theme), which is better set
+    /// directly, with the color left empty.
     pub fn with_input(input: R, color: &'a str, begin: &'a str, again: &'a str) -> Self {
         Self {
-            input: Chars(Flatten(input.bytes())),
-            history_cap: 200,
+            input: Chars::new(input.bytes()),
             history: Default::default(),
             hindex: 0,
+            draft: None,
+            pairs: vec![],
+            closers: vec![],
+            snippets: vec![],
+            stops: vec![],
+            surround: false,
+            redraw: false,
+            cleared: false,
+            unfinished: None,
+            linter: None,
+            highlight: None,
+            formatter: None,
+            validator: None,
+            prompt: None,
+            line_numbers: false,
+            handle: None,
+            middleware: vec![],
+            completion: None,
+            metrics: None,
+            strings: Strings::ENGLISH,
             ed: Editor::new(color, begin, again),
+            buffers: vec![],
+            buffer: 0,
+            tty: None,
+            backend: None,
+            stderr: false,
+            mirrors: Default::default(),
+            colors: colors_wanted(),
+            watchdog: 0,
+            multiplexer: Multiplexer::detect(),
+            history_file: None,
+            ignore_space: false,
+            min_len: 0,
+            ignores: vec![],
+            on_focus: None,
+            on_unknown: None,
+            on_edit: None,
+            mode: ReadMode::Interactive,
+            arrows: ArrowKeys::Edges,
+            bell: Bell::Audible,
+            flashing: false,
+            line_feed: LineFeed::Enter,
+            after_cr: false,
+            keymap: Keymap::default(),
+            kills: KillRing::default(),
+            last_arg: (0, 0),
+            last: None,
+            render_budget: None,
+            end: None,
+            unread: None,
+            escape_timeout: Some(Duration::from_millis(100)),
+            injected: VecDeque::new(),
+            lossy: false,
+            sources: vec![],
+            splitter: None,
+            split: VecDeque::new(),
+            paste: None,
+            confirm_paste: false,
+            pasted: String::new(),
+            mouse: false,
+            paused: None,
+            #[cfg(feature = "clipboard")]
+            awaiting_clipboard: false,
+            pasting: Pasting::default(),
+            last_key: None,
+            echo: None,
+            searches: vec![],
+            elapsed_threshold: None,
+            busy_marker: None,
+            filter: None,
+            max_len: None,
+            submit_full: false,
+            auto_indent: None,
+            #[cfg(feature = "events")]
+            events: cfg!(windows),
+            #[cfg(feature = "events")]
+            idle: None,
         }
     }
 
+    /// Constructs a [Repline] which reads lines from `input` as-is, without drawing them,
+    /// like [ReadMode::Plain]. Lines still go through the buffer and are recorded in the
+    /// history when [accepted](Self::accept), so scripted and interactive runs behave alike.
+    pub fn batch(
+        input: R,
+        color: &'a str,
+        begin: &'a str,
+        again: &'a str,
+    ) -> Repline<'a, BufReader<R>> {
+        let mut rl = Repline::with_input(BufReader::new(input), color, begin, again);
+        rl.mode = ReadMode::Plain;
+        rl
+    }
+
+    /// Replaces the input with `input`, keeping everything else, like the buffer and any
+    /// [pushed](Self::push_input) input yet to be read
+    pub fn set_input(&mut self, input: R) {
+        self.input = Chars::new(input.bytes())
+    }
+
+    /// Reads all of `source` before the rest of the input, like for a `:source file`
+    /// command. Sources pushed later are read first, and each is dropped once it runs out.
+    ///
+    /// See also [InputSource::then](crate::input::InputSource::then).
+    pub fn push_source(&mut self, source: impl Read + 'a) {
+        let source: BoxedRead<'a> = Box::new(source);
+        self.sources.push(Source(Chars::new(source.bytes())))
+    }
+
     /// Consumes self, and produces a new [Repline] with the `new_input` stream
     pub fn swap_input<S: Read>(self, new_input: S) -> Repline<'a, S> {
         Repline {
-            input: Chars(Flatten(new_input.bytes())),
-            history_cap: self.history_cap,
+            input: Chars::new(new_input.bytes()),
             history: self.history,
             hindex: self.hindex,
+            draft: self.draft,
+            pairs: self.pairs,
+            closers: vec![],
+            snippets: self.snippets,
+            stops: self.stops,
+            surround: self.surround,
+            redraw: self.redraw,
+            cleared: self.cleared,
+            unfinished: self.unfinished,
+            linter: self.linter,
+            highlight: self.highlight,
+            formatter: self.formatter,
+            validator: self.validator,
+            prompt: self.prompt,
+            line_numbers: self.line_numbers,
+            handle: self.handle,
+            middleware: self.middleware,
+            completion: self.completion,
+            metrics: self.metrics,
+            strings: self.strings,
             ed: self.ed,
+            buffers: self.buffers,
+            buffer: self.buffer,
+            tty: self.tty,
+            backend: self.backend,
+            stderr: self.stderr,
+            mirrors: self.mirrors,
+            colors: self.colors,
+            watchdog: self.watchdog,
+            multiplexer: self.multiplexer,
+            history_file: self.history_file,
+            ignore_space: self.ignore_space,
+            min_len: self.min_len,
+            ignores: self.ignores,
+            on_focus: self.on_focus,
+            on_unknown: self.on_unknown,
+            on_edit: self.on_edit,
+            mode: self.mode,
+            arrows: self.arrows,
+            bell: self.bell,
+            flashing: false,
+            line_feed: self.line_feed,
+            after_cr: false,
+            keymap: self.keymap,
+            kills: self.kills,
+            last_arg: self.last_arg,
+            last: self.last,
+            render_budget: self.render_budget,
+            end: self.end,
+            unread: self.unread,
+            escape_timeout: self.escape_timeout,
+            injected: self.injected,
+            lossy: self.lossy,
+            sources: self.sources,
+            splitter: self.splitter,
+            split: self.split,
+            paste: self.paste,
+            confirm_paste: self.confirm_paste,
+            pasted: self.pasted,
+            mouse: self.mouse,
+            paused: self.paused,
+            #[cfg(feature = "clipboard")]
+            awaiting_clipboard: self.awaiting_clipboard,
+            pasting: self.pasting,
+            last_key: self.last_key,
+            echo: self.echo,
+            searches: self.searches,
+            elapsed_threshold: self.elapsed_threshold,
+            busy_marker: self.busy_marker,
+            filter: self.filter,
+            max_len: self.max_len,
+            submit_full: self.submit_full,
+            auto_indent: self.auto_indent,
+            #[cfg(feature = "events")]
+            events: self.events,
+            #[cfg(feature = "events")]
+            idle: self.idle,
         }
     }
 
-    /// Set the terminal prompt color
+    /// Set the terminal prompt color, escape sequences like `\x1b[33m`, which style the
+    /// prompts in the [theme](Self::set_theme)
+    #[deprecated(note = "style the prompts with `set_theme` instead")]
     pub fn set_color(&mut self, color: &'a str) {
-        self.ed.color = color
+        self.ed.color_prompts(color)
     }
 
-    /// Set the terminal `begin` prompt, used for the first line
-    pub fn set_begin(&mut self, begin: &'a str) {
-        self.ed.begin = begin
+    /// Set the terminal `begin` prompt, used for the first line.
+    ///
+    /// It may span several lines, like a banner above the input. The text follows its last
+    /// line.
+    pub fn set_begin(&mut self, begin: impl Into<Cow<'a, str>>) {
+        self.ed.begin = begin.into()
     }
 
     /// Set the terminal `again` prompt, used for subsequent lines
-    pub fn set_again(&mut self, again: &'a str) {
-        self.ed.again = again
+    pub fn set_again(&mut self, again: impl Into<Cow<'a, str>>) {
+        self.ed.again = again.into()
+    }
+
+    /// Set the `begin` and `again` prompts together, which may be borrowed or owned, like
+    /// prompts made between reads to show a mode or a sub-shell:
+    ///
+    /// ```no_run
+    /// # use repline::Repline;
+    /// let mut rl = Repline::new("", "> ", ". ");
+    /// let db = "orders";
+    /// rl.set_prompts(format!("{db}> "), ".".repeat(db.len()) + "> ");
+    /// ```
+    ///
+    /// Not to be confused with [Editor::set_prompts], which prompts each line differently.
+    pub fn set_prompts(&mut self, begin: impl Into<Cow<'a, str>>, again: impl Into<Cow<'a, str>>) {
+        (self.ed.begin, self.ed.again) = (begin.into(), again.into());
     }
 
     /// Set the entire terminal prompt sequence
-    pub fn set_prompt(&mut self, color: &'a str, begin: &'a str, again: &'a str) {
-        let Editor { color: ed_color, begin: ed_begin, again: ed_again, .. } = &mut self.ed;
-        (*ed_color, *ed_begin, *ed_again) = (color, begin, again);
+    #[deprecated(note = "set the prompts with `set_prompts`, and style them with `set_theme`")]
+    pub fn set_prompt(
+        &mut self,
+        color: &'a str,
+        begin: impl Into<Cow<'a, str>>,
+        again: impl Into<Cow<'a, str>>,
+    ) {
+        self.ed.color_prompts(color);
+        self.set_prompts(begin, again);
     }
 
-    /// Append line to history and clear it
-    pub fn accept(&mut self) {
-        self.history_append(self.ed.to_string());
-        self.ed.clear();
-        self.hindex = self.history.len();
+    /// Registers a pair of delimiters, where typing `open` automatically inserts `close`
+    /// after the cursor, and typing `close` in front of the `close` inserted that way skips
+    /// over it, until anything but typing moves the cursor or changes the buffer.
+    /// `Backspace` right after typing `open` deletes both.
+    ///
+    /// No pairs are registered to begin with. [BRACKETS] holds the usual ones.
+    ///
+    /// When several openers match, the longest one wins, so `("${", "}")` takes priority
+    /// over `("{", "}")`.
+    pub fn add_pair(&mut self, open: &'a str, close: &'a str) {
+        self.pairs.retain(|(o, _)| *o != open);
+        self.pairs.push((open, close))
     }
 
-    /// Clear the line
-    pub fn deny(&mut self) {
-        self.ed.clear()
+    /// Set the entire list of auto-closing delimiter pairs, like [BRACKETS]
+    pub fn set_pairs(&mut self, pairs: &[(&'a str, &'a str)]) {
+        self.pairs = pairs.to_vec()
     }
 
-    /// Reads in a line, and returns it for validation
-    pub fn read(&mut self) -> ReplResult<String> {
-        const INDENT: &str = "    ";
-        let mut stdout = stdout().lock();
-        let stdout = &mut stdout;
-        let _make_raw = raw();
+    /// Registers a snippet, which replaces `trigger` with `body` when `Tab` is pressed after it.
+    ///
+    /// Placeholders in `body`, written `${1}` or `${1:default}`, are selected in order by
+    /// each `Tab` which follows, so they can be typed over. `${0}`, if present, is visited
+    /// last. Once every placeholder has been visited, `Tab` indents as usual.
+    pub fn add_snippet(&mut self, trigger: &'a str, body: &'a str) {
+        self.snippets.retain(|(t, _)| *t != trigger);
+        self.snippets.push((trigger, body))
+    }
 
-        self.ed.print_head(stdout)?;
-        loop {
-            stdout.flush()?;
-            match self.input.next().ok_or(Error::EndOfInput)?? {
-                // Ctrl+C: End of Text. Immediately exits.
-                '\x03' => {
-                    drop(_make_raw);
-                    writeln!(stdout)?;
-                    return Err(Error::CtrlC(self.ed.to_string()));
-                }
-                // Ctrl+D: End of Transmission. Ends the current line.
-                '\x04' => {
-                    drop(_make_raw);
-                    writeln!(stdout)?;
-                    return Err(Error::CtrlD(self.ed.to_string()));
-                }
-                // Tab: extend line by 4 spaces
-                '\t' => self.ed.extend(INDENT.chars(), stdout)?,
-                // ignore newlines, process line feeds. Not sure how cross-platform this is.
-                '\n' => {}
-                '\r' => {
-                    self.ed.push('\n', stdout)?;
-                    if self.ed.at_end() {
-                        return Ok(self.ed.to_string());
-                    }
-                }
-                // Ctrl+Backspace in my terminal
-                '\x17' => self.ed.erase_word(stdout)?,
-                // Escape sequence
-                '\x1b' => self.escape(stdout)?,
-                // backspace
-                '\x08' | '\x7f' => {
-                    let ed = &mut self.ed;
-                    if ed.ends_with(INDENT.chars()) {
-                        for _ in 0..INDENT.len() {
-                            ed.pop(stdout)?;
-                        }
-                    } else {
-                        ed.pop(stdout)?;
-                    }
-                }
-                c if c.is_ascii_control() => {
-                    if cfg!(debug_assertions) {
-                        self.print_err(
-                            stdout,
-                            format_args!("\t\x1b[30mUnhandled ASCII C0 {c:?}\x1b[0m"),
-                        )?;
-                    }
-                }
-                c => {
-                    self.ed.push(c, stdout)?;
-                }
-            }
-        }
+    /// Set whether typing the opener of a registered [pair](Self::add_pair) while text is
+    /// selected wraps the selection in that pair, instead of replacing it
+    pub fn set_surround(&mut self, surround: bool) {
+        self.surround = surround
     }
 
-    /// Prints a message without moving the cursor
-    pub fn print_inline(&mut self, value: impl std::fmt::Display) -> ReplResult<()> {
-        let mut stdout = stdout().lock();
-        self.print_err(&mut stdout, value)
+    /// Attaches a short annotation (like `✓` or a lint count) to a line of the buffer,
+    /// which is shown in a gutter before that line's prompt.
+    ///
+    /// The buffer is redrawn with the new annotations on the next [read](Self::read).
+    /// Annotations are cleared along with the buffer.
+    pub fn annotate(&mut self, line: usize, note: impl Into<String>) {
+        self.ed.annotate(line, note);
+        self.redraw = true;
     }
 
-    /// Prints a message (ideally an error) without moving the cursor
-    fn print_err<W: Write>(&self, w: &mut W, value: impl std::fmt::Display) -> ReplResult<()> {
-        if self.ed.at_start() {
-            let pad = Padding(self.ed.begin.len());
-            self.ed.print_err(format_args!("{pad}{value}"), w)
-        } else if self.ed.at_line_start() {
-            let pad = Padding(self.ed.again.len());
-            self.ed.print_err(format_args!("{pad}{value}"), w)
-        } else {
-            self.ed.print_err(value, w)
+    /// Removes all line annotations
+    pub fn clear_annotations(&mut self) {
+        self.ed.clear_annotations();
+        self.redraw = true;
+    }
+
+    /// Sets the [Diagnostics] provider, which checks the buffer as the user types.
+    ///
+    /// Reported diagnostics are underlined in the buffer, and summarized on a status line
+    /// below it. `Alt+N` and `Alt+P` jump to the next and previous diagnostic, and `Alt+A`
+    /// applies the shown diagnostic's [quick fix](Diagnostic::with_fix), if it has one.
+    pub fn set_diagnostics(&mut self, provider: impl Diagnostics + 'a) {
+        self.linter = Some(Linter::new(provider))
+    }
+
+    /// Sets the minimum time between runs of the [Diagnostics] provider.
+    ///
+    /// Input is read in blocking fashion, so the provider only runs after an edit:
+    /// edits made before the delay elapses are checked after the next one.
+    pub fn set_diagnostics_delay(&mut self, delay: Duration) {
+        if let Some(linter) = &mut self.linter {
+            linter.delay = delay
         }
     }
 
-    // Prints some debug info into the editor's buffer and the provided writer
-    pub fn put<D: std::fmt::Display, W: Write>(&mut self, disp: D, w: &mut W) -> ReplResult<()> {
-        self.ed.extend(format!("{disp}").chars(), w)
+    /// Sets the [Formatter], which rewrites the buffer when `Alt+Q` is pressed, keeping the
+    /// cursor in place where the text around it is unchanged
+    pub fn set_formatter(&mut self, formatter: impl Formatter + 'a) {
+        self.formatter = Some(BoxedFormatter(Box::new(formatter)))
     }
 
-    /// Handle ANSI Escape
-    fn escape<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
-        match self.input.next().ok_or(Error::EndOfInput)?? {
-            '\r' => Err(Error::EndOfInput)?,
-            '[' => self.csi(w)?,
-            'O' => todo!("Process alternate character mode"),
-            other => {
-                if cfg!(debug_assertions) {
-                    self.print_err(w, format_args!("\t\x1b[30mANSI escape: {other:?}\x1b[0m"))?;
-                }
-            }
-        }
-        Ok(())
-    }
-
-    /// Handle ANSI Control Sequence Introducer
-    fn csi<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
-        match self.input.next().ok_or(Error::EndOfInput)?? {
-            'A' if self.ed.at_start() && self.hindex > 0 => {
-                if self.history.len() > self.hindex {
-                    self.history[self.hindex] = self.ed.to_string()
-                } else {
-                    self.history_append(self.ed.to_string());
-                }
-                self.hindex -= 1;
-                self.restore_history(w, true)?;
-            }
-            'A' => self.ed.cursor_up(w)?,
-            'B' if self.ed.at_end() && self.hindex < self.history.len().saturating_sub(1) => {
-                self.history[self.hindex] = self.ed.to_string();
-                self.hindex += 1;
-                self.restore_history(w, false)?;
-            }
-            'B' => self.ed.cursor_down(w)?,
-            'C' => self.ed.cursor_forward(w)?,
-            'D' => self.ed.cursor_back(w)?,
-            'H' => self.ed.cursor_line_start(w)?,
-            'F' => self.ed.cursor_line_end(w)?,
-            '1' => {
-                // TODO: this as a separate function
-                if let ';' = self.input.next().ok_or(Error::EndOfInput)??
-                    && let '5' = self.input.next().ok_or(Error::EndOfInput)??
-                {
-                    match self.input.next().ok_or(Error::EndOfInput)?? {
-                        'A' => self.print_err(w, "TODO: direction A")?,
-                        'B' => self.print_err(w, "TODO: direction B")?,
-                        'C' => self.ed.cursor_word_forward(w)?,
-                        'D' => self.ed.cursor_word_back(w)?,
-                        other => self.print_err(w, format_args!("Unhandled direction {other}"))?,
-                    }
-                } else {
-                    self.print_err(
-                        w,
-                        format_args!("\t\x1b[30mUnhandled control sequence\x1b[0m"),
-                    )?;
-                }
-            }
-            '3' => {
-                if let '~' = self.input.next().ok_or(Error::EndOfInput)?? {
-                    self.ed.delete(w)?;
-                }
-            }
-            '5' => {
-                if let '~' = self.input.next().ok_or(Error::EndOfInput)?? {
-                    self.ed.cursor_start(w)?
-                }
-            }
-            '6' => {
-                if let '~' = self.input.next().ok_or(Error::EndOfInput)?? {
-                    self.ed.cursor_end(w)?
-                }
-            }
-            other => {
-                if cfg!(debug_assertions) {
-                    self.print_err(
-                        w,
-                        format_args!(" \x1b[30mUnhandled control sequence: {other:?}\x1b[0m"),
-                    )?;
-                }
-            }
+    /// Sets the [Validator], which decides whether Enter submits the buffer, or inserts a
+    /// line break so that editing goes on.
+    ///
+    /// Lines read as-is, outside of [ReadMode::Interactive], are gathered until the validator
+    /// accepts them too.
+    pub fn set_validator(&mut self, validator: impl Validator + 'a) {
+        self.validator = Some(BoxedValidator(Box::new(validator)))
+    }
+
+    /// Removes the [Validator], so that Enter always submits the buffer
+    pub fn clear_validator(&mut self) {
+        self.validator = None
+    }
+
+    /// Returns true unless the [Validator] finds `text` incomplete
+    fn is_complete(&mut self, text: &str) -> bool {
+        let Some(BoxedValidator(validator)) = &mut self.validator else {
+            return true;
+        };
+        validator.validate(text) == Validation::Complete
+    }
+
+    /// Sets the [Prompt], which makes the prompt before each line of the buffer whenever it's
+    /// drawn, in place of the begin and again prompts
+    pub fn set_dynamic_prompt(&mut self, prompt: impl Prompt + 'a) {
+        self.prompt = Some(BoxedPrompt(Box::new(prompt)))
+    }
+
+    /// Removes the [Prompt], going back to the begin and again prompts
+    pub fn clear_dynamic_prompt(&mut self) {
+        self.prompt = None;
+        self.ed.set_prompts(vec![]);
+        self.redraw = true;
+    }
+
+    /// Set whether each line after the first is prompted with its number, counting from 1,
+    /// like ` 2| `, in place of the again prompt, so that errors reported by line number are
+    /// easy to find. A [Prompt] takes priority over the numbers.
+    pub fn set_line_numbers(&mut self, line_numbers: bool) {
+        self.line_numbers = line_numbers;
+        if !line_numbers && self.prompt.is_none() {
+            self.ed.set_prompts(vec![]);
         }
-        Ok(())
+        self.redraw = true;
     }
 
-    /// Restores the currently selected history
-    fn restore_history<W: Write>(&mut self, w: &mut W, upward: bool) -> ReplResult<()> {
-        let Self { history, hindex, ed, .. } = self;
-        if let Some(history) = history.get(*hindex) {
-            ed.restore(history, w)?;
-            if cfg!(debug_assertions) {
-                ed.print_err(format_args!(" \x1b[30m{hindex}\x1b[0m"), w)?;
+    /// Makes the prompts for the buffer with the [Prompt], or numbers its lines, returning
+    /// the prompts if they changed
+    fn make_prompts(&mut self) -> Option<Vec<String>> {
+        let Self { prompt, line_numbers, ed, .. } = self;
+        let text = ed.to_string();
+        let lines = text.split('\n').count();
+        let prompts: Vec<_> = match prompt {
+            Some(BoxedPrompt(prompt)) => {
+                (0..lines).map(|line| prompt.prompt(line, &text)).collect()
             }
-            if upward {
-                ed.cursor_start(w)?;
+            None if *line_numbers => {
+                let width = lines.to_string().len().max(2);
+                let numbers = (2..=lines).map(|n| format!("{n:>width$}| "));
+                std::iter::once(ed.begin.to_string())
+                    .chain(numbers)
+                    .collect()
             }
+            None => return None,
+        };
+        (prompts != ed.prompts()).then_some(prompts)
+    }
+
+    /// Makes the prompts again with the [Prompt], redrawing the buffer if they changed
+    fn redraw_prompts<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        let Some(prompts) = self.make_prompts() else {
+            return Ok(());
+        };
+        // the old prompts may have been wider, so clear them away
+        self.ed.undraw(w)?;
+        queue!(w, Clear(ClearType::FromCursorDown))?;
+        self.ed.set_prompts(prompts);
+        self.ed.draw_head(w)?;
+        self.ed.redraw_tail(w)
+    }
+
+    /// Adds a [Middleware] around key handling, outside those added before it, so that it
+    /// sees each key first
+    pub fn add_middleware(&mut self, middleware: impl Middleware + 'a) {
+        self.middleware.push(BoxedMiddleware(Box::new(middleware)))
+    }
+
+    /// Removes all the [Middleware], so that keys go straight to the editor
+    pub fn clear_middleware(&mut self) {
+        self.middleware.clear()
+    }
+
+    /// Sets a splitter, which splits each line read into logical lines, like the commands
+    /// of `a; b` in a shell. [read](Self::read) returns them one at a time, without reading
+    /// more input until they run out.
+    ///
+    /// The splitter is given the line as read, line break and all. The history records the
+    /// line as it was entered, when the first of its logical lines is
+    /// [accepted](Self::accept). If the splitter returns no lines, the line is returned whole.
+    pub fn set_splitter(&mut self, splitter: impl FnMut(&str) -> Vec<String> + 'a) {
+        self.splitter = Some(Splitter(Box::new(splitter)))
+    }
+
+    /// Sets how keys which arrive in a burst, like a paste, are treated, or stops telling
+    /// pastes apart from typing if `None` (the default).
+    ///
+    /// This helps where the terminal doesn't mark pastes itself. Whether the current key is
+    /// part of a paste can be checked by hooks through [pasting](Self::pasting).
+    pub fn set_paste_policy(&mut self, policy: Option<PastePolicy>) {
+        self.paste = policy;
+        self.pasting.0.set(false);
+    }
+
+    /// Returns a handle which tells whether the key being handled arrived in a burst, like
+    /// a paste, for hooks which should treat pasted text differently.
+    ///
+    /// Always false unless a [paste policy](Self::set_paste_policy) is set.
+    pub fn pasting(&self) -> Pasting {
+        self.pasting.clone()
+    }
+
+    /// Set whether pasted text containing line breaks or control characters is previewed on
+    /// the status line, and only inserted once the user confirms it with `y`. This guards
+    /// against pasting commands from a web page which hides them, like paste-jacking.
+    ///
+    /// Confirmed text is inserted, never submitted, and control characters other than line
+    /// breaks and tabs are left out. It relies on the terminal marking pastes (bracketed
+    /// paste), which isn't asked for while keys are read as [events](Self::set_event_input).
+    pub fn set_paste_confirmation(&mut self, confirm: bool) {
+        self.confirm_paste = confirm
+    }
+
+    /// Set whether the terminal is asked to report mouse clicks while reading, so that a
+    /// left click in the buffer moves the cursor there.
+    ///
+    /// While clicks are captured, most terminals only select text when Shift is held.
+    pub fn set_mouse(&mut self, mouse: bool) {
+        self.mouse = mouse
+    }
+
+    /// Returns true if the terminal should be asked to mark pastes
+    fn brackets_pastes(&self) -> bool {
+        #[cfg(feature = "events")]
+        if self.events {
+            return false;
         }
-        Ok(())
+        self.confirm_paste
+    }
+
+    /// Returns the paste policy, if the key being handled is part of a paste
+    fn paste_policy(&self) -> Option<PastePolicy> {
+        self.paste.filter(|_| self.pasting.get())
     }
 
-    /// Append line to history
-    fn history_append(&mut self, mut buf: String) {
-        while buf.ends_with(char::is_whitespace) {
-            buf.pop();
+    /// Sets a function which evaluates the buffer as the user types, returning a short
+    /// result, like `= 42` in a calculator, or `None` to show nothing.
+    ///
+    /// The result is drawn dimmed at the right edge of the line the cursor is on, if the
+    /// line leaves room for it, and is cleared when the line is submitted.
+    pub fn set_echo(&mut self, echo: impl FnMut(&str) -> Option<String> + 'a) {
+        self.echo = Some(Echo { echo: Box::new(echo), drawn: None })
+    }
+
+    /// Sets a filter, which constrains what can be typed to text it accepts, like digits
+    /// only, or a partial IP address.
+    ///
+    /// Typing a character which makes the filter reject the buffer rings the bell and shows
+    /// `hint` on the status line, instead of inserting it. The filter should accept every
+    /// prefix of a valid line, so that valid lines can be typed one character at a time.
+    ///
+    /// ```no_run
+    /// # use repline::Repline;
+    /// let mut rl = Repline::new("", "hex> ", "   > ");
+    /// rl.set_filter(|text: &str| text.chars().all(|c| c.is_ascii_hexdigit()), "hex digits only");
+    /// ```
+    pub fn set_filter(&mut self, filter: impl FnMut(&str) -> bool + 'a, hint: &'a str) {
+        self.filter = Some(Filter { filter: Box::new(filter), hint, rejected: false })
+    }
+
+    /// Removes the filter, so that anything can be typed
+    pub fn clear_filter(&mut self) {
+        self.filter = None
+    }
+
+    /// Sets the most characters a line can have, line breaks included, or `None` for no limit.
+    ///
+    /// Keys which would make the line longer, like typing or yanking, ring the
+    /// [bell](Self::set_bell) instead, and a paste is cut short to fit.
+    pub fn set_max_len(&mut self, max_len: Option<usize>) {
+        self.max_len = max_len
+    }
+
+    /// Sets whether a line is submitted as soon as it reaches the
+    /// [maximum length](Self::set_max_len), like a code of a fixed length
+    pub fn set_submit_at_max_len(&mut self, submit: bool) {
+        self.submit_full = submit
+    }
+
+    /// Sets whether a line break carries the indentation of the line it breaks onto the new
+    /// line, so that an indented block doesn't need its indentation typed on every line.
+    ///
+    /// Pasted line breaks are left alone, since pasted text brings its own indentation.
+    pub fn set_auto_indent(&mut self, auto_indent: bool) {
+        if !auto_indent {
+            self.auto_indent = None
+        } else if self.auto_indent.is_none() {
+            self.auto_indent = Some(AutoIndent(None))
+        }
+    }
+
+    /// Indents new lines like [set_auto_indent](Self::set_auto_indent), adding another level
+    /// after each line which `opens` accepts, like one ending with `{` or `:`.
+    ///
+    /// `opens` is given the line up to the cursor, where it's broken.
+    ///
+    /// ```no_run
+    /// # use repline::Repline;
+    /// let mut rl = Repline::new("", ">>> ", "... ");
+    /// rl.set_indent_opener(|line: &str| line.trim_end().ends_with(':'));
+    /// ```
+    pub fn set_indent_opener(&mut self, opens: impl FnMut(&str) -> bool + 'a) {
+        self.auto_indent = Some(AutoIndent(Some(Box::new(opens))))
+    }
+
+    /// Reads a line like [read](Self::read), constrained by a [filter](Self::set_filter) for
+    /// this read only
+    pub fn read_filtered(
+        &mut self,
+        filter: impl FnMut(&str) -> bool + 'a,
+        hint: &'a str,
+    ) -> ReplResult<String> {
+        let outer = self.filter.take();
+        self.set_filter(filter, hint);
+        let line = self.read();
+        self.filter = outer;
+        line
+    }
+
+    /// Reads a line like [read](Self::read), starting from `text` in the buffer with the cursor
+    /// at byte index `cursor`, like a previous command to edit, or a suggestion to correct.
+    ///
+    /// The text replaces whatever was in the buffer, and is only recorded in the history if
+    /// the line is [accepted](Self::accept). Outside of [ReadMode::Interactive], where lines
+    /// aren't edited, it's left out.
+    pub fn read_with_initial(&mut self, text: &str, cursor: usize) -> ReplResult<String> {
+        if self.mode == ReadMode::Interactive {
+            let cursor = char_index(text, cursor);
+            self.restore_state(EditorState { text: text.into(), cursor, ..Default::default() });
+            self.ed.clear_undo();
+        }
+        self.read()
+    }
+
+    /// Reads a line like [read](Self::read), offering `default`, which is drawn dimmed after the
+    /// prompt until something's typed, and read in place of an empty line, like the `main` of
+    /// `Branch [main]:`.
+    pub fn read_with_default(&mut self, default: &str) -> ReplResult<String> {
+        self.ed.placeholder = Some(default.into());
+        let line = self.read();
+        self.ed.placeholder = None;
+        line
+    }
+
+    /// Sets the [Highlighter], which styles the buffer as the user types
+    pub fn set_highlighter(&mut self, highlighter: impl Highlighter + 'a) {
+        self.highlight = Some(Highlight::new(highlighter))
+    }
+
+    /// Shows `found` in the buffer, underlined and summarized on the status line like a
+    /// [Diagnostics] provider's, until the buffer is next edited. The spans are byte ranges of
+    /// the [text](Self::text).
+    ///
+    /// Meant for problems found once a line is submitted, like by the closure of
+    /// [read_and](crate::prebaked::read_and), with the line put back to be corrected.
+    pub fn report_diagnostics(&mut self, found: Vec<Diagnostic>) {
+        let linter = self
+            .linter
+            .get_or_insert_with(|| Linter::new(|_: &str| vec![]));
+        linter.report(self.ed.to_string(), found);
+        self.ed.set_decorations(linter.decorations());
+        self.show_status();
+        self.redraw = true;
+    }
+
+    /// Returns the diagnostics reported for the current buffer
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        self.linter
+            .as_ref()
+            .map(|l| l.found.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Sets the [Completer], which completes the word under the cursor when `Tab` is pressed.
+    ///
+    /// Candidates replace the word, leaving the rest of the line in place, as chosen by the
+    /// completer's [mode](crate::completion::CompletionMode). Multiple candidates are listed
+    /// on the status line. `Tab` in the indentation at the start of a line still indents.
+    pub fn set_completer(&mut self, completer: impl Completer + 'a) {
+        self.completion = Some(Completion::new(completer))
+    }
+
+    /// Sets how history entries are ordered for recall with the arrow keys
+    pub fn set_history_ranking(&mut self, ranking: Ranking) {
+        self.history.set_ranking(ranking);
+        self.hindex = self.history.len();
+    }
+
+    /// Sets how entries [loaded](Self::load_history) from a history file are resolved with
+    /// the same entries already in the history
+    pub fn set_history_conflict(&mut self, conflict: Conflict) {
+        self.history.set_conflict(conflict)
+    }
+
+    /// Sets the number of entries the history holds, dropping the least relevant entries
+    /// past it
+    pub fn set_history_cap(&mut self, cap: usize) {
+        self.history.set_cap(cap);
+        self.hindex = self.history.len();
+    }
+
+    /// Sets the context (like the working directory, or a database name) which accepted
+    /// lines are recorded in
+    pub fn set_context(&mut self, context: Option<String>) {
+        self.history.set_context(context);
+        self.hindex = self.history.len();
+    }
+
+    /// Sets whether history entries from the current [context](Self::set_context) are
+    /// recalled before entries from other contexts
+    pub fn set_context_recall(&mut self, by_context: bool) {
+        self.history.set_by_context(by_context);
+        self.hindex = self.history.len();
+    }
+
+    /// Replaces the text which repline draws by itself, to translate the interface
+    pub fn set_strings(&mut self, strings: Strings<'a>) {
+        self.strings = strings;
+        self.ed.line_label = strings.line;
+        for (_, ed) in &mut self.buffers {
+            ed.line_label = strings.line;
+        }
+    }
+
+    /// Replaces the styles of what repline draws by itself: the prompts, secondary text, and
+    /// error messages
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.ed.theme = theme;
+        for (_, ed) in &mut self.buffers {
+            ed.theme = theme;
+        }
+    }
+
+    /// Returns the styles of what repline draws by itself
+    pub fn theme(&self) -> &Theme {
+        &self.ed.theme
+    }
+
+    /// Enables or disables collection of [Metrics] for each read
+    pub fn set_metrics(&mut self, enabled: bool) {
+        self.metrics = enabled.then(Metrics::default)
+    }
+
+    /// Returns the [Metrics] of the current (or most recent) read, if enabled
+    pub fn metrics(&self) -> Option<&Metrics> {
+        self.metrics.as_ref()
+    }
+
+    /// Adds an empty scratch buffer named `name`, which retains its own contents and cursor.
+    ///
+    /// `Ctrl+X b` switches to the next buffer, and the active buffer is shown on the
+    /// status line. The buffer in use before any are added is named after
+    /// [`Strings::main_buffer`].
+    pub fn add_buffer(&mut self, name: impl Into<String>) {
+        let Editor { begin, again, .. } = &self.ed;
+        if self.buffers.is_empty() {
+            let main = self.strings.main_buffer.to_string();
+            self.buffers
+                .push((main, self.ed.sibling(begin.clone(), again.clone())));
+        }
+        self.buffers
+            .push((name.into(), self.ed.sibling(begin.clone(), again.clone())));
+        self.redraw = true;
+    }
+
+    /// Returns the name of the active scratch buffer, if any were added
+    pub fn buffer(&self) -> Option<&str> {
+        self.buffers.get(self.buffer).map(|(name, _)| name.as_str())
+    }
+
+    /// Makes the scratch buffer named `name` active.
+    ///
+    /// Returns false if there is no such buffer.
+    pub fn switch_buffer(&mut self, name: &str) -> bool {
+        let Some(index) = self.buffers.iter().position(|(n, _)| n == name) else {
+            return false;
+        };
+        self.swap_buffer(index);
+        self.redraw = true;
+        true
+    }
+
+    /// Stores the active editor in its buffer, and takes out the buffer at `index`
+    fn swap_buffer(&mut self, index: usize) {
+        let Self { ed, buffers, buffer, .. } = self;
+        let (theme, cols) = (ed.theme, ed.cols);
+        let (begin, again) = (ed.begin.clone(), ed.again.clone());
+        self.stops.clear();
+        std::mem::swap(ed, &mut buffers[*buffer].1);
+        *buffer = index;
+        std::mem::swap(ed, &mut buffers[index].1);
+        (ed.theme, ed.begin, ed.again, ed.cols) = (theme, begin, again, cols);
+    }
+
+    /// Switches to the next scratch buffer, and draws it in place of the current one
+    fn next_buffer<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        if self.buffers.is_empty() {
+            return Ok(());
+        }
+        self.ed.undraw(w)?;
+        queue!(w, Clear(ClearType::FromCursorDown))?;
+        self.swap_buffer((self.buffer + 1) % self.buffers.len());
+        self.show_status();
+        self.ed.draw_head(w)?;
+        self.ed.redraw_tail(w)
+    }
+
+    /// Shows the active scratch buffer and the selected diagnostic (or the listed completion
+    /// candidates, the filter's hint, or the idle handler's message) on the status line
+    fn show_status(&mut self) {
+        #[cfg(feature = "events")]
+        let idle = self.idle.as_ref().and_then(|idle| idle.status.clone());
+        #[cfg(not(feature = "events"))]
+        let idle = None;
+        let Self { linter, completion, filter, strings, ed, buffers, buffer, .. } = self;
+        let buffer = buffers.get(*buffer).map(|(name, _)| {
+            let (label, n) = (strings.buffer, buffers.len());
+            format!("{label} {name} ({}/{n})", *buffer + 1)
+        });
+        let summary = match (completion, filter) {
+            _ if idle.is_some() => idle,
+            (_, Some(filter)) if filter.rejected => Some(filter.hint.to_string()),
+            (Some(completion), _) if !completion.shown.is_empty() => {
+                Some(completion.shown.join("  "))
+            }
+            _ => linter.as_ref().and_then(|l| l.summary(strings)),
+        };
+        ed.set_status(match (buffer, summary) {
+            (Some(buffer), Some(summary)) => Some(format!("{buffer} | {summary}")),
+            (buffer, summary) => buffer.or(summary),
+        });
+    }
+
+    /// Saves the current buffer, cursor, selection, and what can be undone and redone, so
+    /// the entry can be resumed with [restore_state](Self::restore_state), even in another
+    /// process
+    pub fn snapshot(&self) -> EditorState {
+        self.ed.snapshot()
+    }
+
+    /// Replaces the current buffer, and what can be undone and redone, with a
+    /// [snapshot](Self::snapshot).
+    ///
+    /// The buffer is redrawn with the restored state on the next [read](Self::read).
+    pub fn restore_state(&mut self, state: EditorState) {
+        self.stops.clear();
+        self.ed.restore_state(state);
+        self.redraw = true;
+    }
+
+    /// Replaces the current buffer like [restore_state](Self::restore_state), keeping what
+    /// can be undone and redone
+    fn set_state(&mut self, state: EditorState) {
+        self.stops.clear();
+        self.ed.set_state(state);
+        self.redraw = true;
+    }
+
+    /// Returns the text in the buffer
+    pub fn text(&self) -> String {
+        self.ed.to_string()
+    }
+
+    /// Returns the byte index of the cursor in the [text](Self::text)
+    pub fn cursor(&self) -> usize {
+        self.ed.byte_cursor()
+    }
+
+    /// Replaces the text in the buffer, like with a template or a corrected line, placing the
+    /// cursor at the end. The change can be undone.
+    ///
+    /// The buffer is redrawn on the next [read](Self::read).
+    pub fn set_text(&mut self, text: &str) {
+        self.edit_text(text.to_owned(), text.len())
+    }
+
+    /// Inserts `text` at the cursor, placing the cursor after it. The change can be undone.
+    ///
+    /// The buffer is redrawn on the next [read](Self::read).
+    pub fn insert_at_cursor(&mut self, text: &str) {
+        let (mut buf, cursor) = (self.ed.to_string(), self.ed.byte_cursor());
+        buf.insert_str(cursor, text);
+        self.edit_text(buf, cursor + text.len())
+    }
+
+    /// Queues `input` to be read before anything else arrives, as if it were typed. Unlike
+    /// [set_text](Self::set_text), it goes through the keymap, so a `\r` submits the line, and
+    /// keys can be written by name with [script](crate::keymap::script):
+    ///
+    /// ```no_run
+    /// # use repline::{Repline, keymap::script};
+    /// let mut rl = Repline::new("", "> ", ". ");
+    /// rl.push_input(&script("git commit -m \"\"<Left>")?);
+    /// let line = rl.read()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn push_input(&mut self, input: &str) {
+        self.injected.extend(input.chars())
+    }
+
+    /// Drops input [pushed](Self::push_input) which hasn't been read yet
+    pub fn clear_pushed_input(&mut self) {
+        self.injected.clear()
+    }
+
+    /// Sets whether input which isn't UTF-8 is read as U+FFFD (`�`), rather than failing the
+    /// read with [Error::BadUtf8] or [Error::BadUnicode]. Errors reading the input still fail.
+    pub fn set_lossy_input(&mut self, lossy: bool) {
+        self.lossy = lossy
+    }
+
+    /// Moves the cursor to byte index `cursor` of the [text](Self::text), or as near before
+    /// it as a character starts
+    pub fn move_cursor_to(&mut self, cursor: usize) {
+        let mut state = self.ed.state();
+        state.cursor = char_index(&state.text, cursor);
+        self.set_state(EditorState { mark: None, block: false, ..state })
+    }
+
+    /// Replaces the buffer with `text`, with the cursor at byte index `cursor`, remembering
+    /// the buffer as it was to undo back to
+    fn edit_text(&mut self, text: String, cursor: usize) {
+        let before = self.ed.state();
+        let cursor = char_index(&text, cursor);
+        self.set_state(EditorState { text, cursor, ..Default::default() });
+        self.ed.checkpoint(before);
+    }
+
+    /// Lays out the buffer in rows of styled text, as it would be drawn where lines wrap
+    /// every `cols` columns (or never, if 0), so that a program which draws for itself, like
+    /// a TUI, can draw it in its own frame, instead of repline writing escape sequences.
+    ///
+    /// The whole buffer is laid out, however tall, with the lines of a multi-line begin
+    /// prompt above it, and the status line below it.
+    pub fn render_to_rows(&self, cols: usize) -> Frame {
+        self.ed.render(cols)
+    }
+
+    /// Returns where the line returned by the last [read](Self::read) ended on screen, so
+    /// that output can be placed beside it.
+    ///
+    /// `None` if the last read didn't return a line, or didn't draw it.
+    pub fn end_position(&self) -> Option<EndPosition> {
+        self.end
+    }
+
+    /// Returns the previously entered lines
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Returns a [ReplineHandle], which other threads can watch the buffer, the cursor, and
+    /// the history through
+    pub fn handle(&mut self) -> ReplineHandle {
+        let handle = self.handle.get_or_insert_default().clone();
+        self.publish(true);
+        handle
+    }
+
+    /// Updates the [ReplineHandle], if there is one, with the buffer and cursor, and the
+    /// history too if `history`
+    fn publish(&self, history: bool) {
+        let Some(handle) = &self.handle else {
+            return;
+        };
+        handle.set_buffer(self.ed.to_string(), self.ed.byte_cursor());
+        if history {
+            handle.set_history(self.history.iter().cloned().collect());
         }
-        if let Some(idx) = self.history.iter().position(|v| *v == buf) {
-            self.history
-                .remove(idx)
-                .expect("should have just found this");
+    }
+
+    /// Returns the index of the history entry being recalled, or the length of the history
+    /// if none is (the buffer is a new line)
+    pub fn history_index(&self) -> usize {
+        self.hindex
+    }
+
+    /// Recalls the history entry at `index`, as if navigated to with the arrow keys, or stops
+    /// recalling entries if `index` is past the end of the history.
+    ///
+    /// The buffer being edited is kept like during navigation, so it can be returned to. The
+    /// recalled entry is drawn on the next [read](Self::read).
+    pub fn set_history_index(&mut self, index: usize) {
+        let Some(text) = self.history.get(index).map(str::to_owned) else {
+            return self.reset_navigation();
         };
-        self.history.push_back(buf);
-        while self.history.len() > self.history_cap {
-            self.history.pop_front();
+        self.leave_entry();
+        self.hindex = index;
+        let cursor = text.chars().count();
+        self.set_state(EditorState { text, cursor, ..Default::default() });
+    }
+
+    /// Recalls the nearest history entry in `direction` from the one being recalled which
+    /// `predicate` accepts, like the previous command that touched some table, returning
+    /// false if there isn't one.
+    ///
+    /// The entry is recalled like with [set_history_index](Self::set_history_index). To
+    /// search from a key, see [add_history_search](Self::add_history_search).
+    pub fn search_history(
+        &mut self,
+        mut predicate: impl FnMut(&str) -> bool,
+        direction: Direction,
+    ) -> bool {
+        match self.find_history(&mut predicate, direction) {
+            Some(index) => {
+                self.set_history_index(index);
+                true
+            }
+            None => false,
         }
     }
-}
 
-/// Helper struct for padding inline-printed messages
-struct Padding(usize);
-impl std::fmt::Display for Padding {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for _ in 0..self.0 {
-            f.write_str(" ")?;
+    /// Adds a history search which keys can be bound to, returning its index for
+    /// [EditCommand::SearchWith].
+    ///
+    /// ```no_run
+    /// # use repline::{Repline, history::Direction, keymap::{EditCommand, Key}};
+    /// let mut rl = Repline::new("", "sql> ", "   > ");
+    /// let users = rl.add_history_search(|line| line.contains("users"));
+    /// rl.bind(Key::Alt('u'), EditCommand::SearchWith(users, Direction::Backward));
+    /// ```
+    pub fn add_history_search(&mut self, predicate: impl FnMut(&str) -> bool + 'a) -> usize {
+        self.searches.push(HistorySearch(Box::new(predicate)));
+        self.searches.len() - 1
+    }
+
+    /// Returns the index of the nearest history entry in `direction` which `predicate`
+    /// accepts
+    fn find_history(
+        &self,
+        predicate: &mut dyn FnMut(&str) -> bool,
+        direction: Direction,
+    ) -> Option<usize> {
+        let Self { history, hindex, .. } = self;
+        let mut accepts = |&i: &usize| history.get(i).is_some_and(&mut *predicate);
+        match direction {
+            Direction::Backward => (0..*hindex).rev().find(|i| accepts(i)),
+            Direction::Forward => (*hindex + 1..history.len()).find(|i| accepts(i)),
         }
-        Ok(())
+    }
+
+    /// Recalls an entry with the history search at index `search`, redrawing the buffer
+    fn search_with<W: Write>(
+        &mut self,
+        w: &mut W,
+        search: usize,
+        direction: Direction,
+    ) -> ReplResult<()> {
+        let mut searches = std::mem::take(&mut self.searches);
+        let found = match searches.get_mut(search) {
+            Some(HistorySearch(predicate)) => self.find_history(predicate, direction),
+            None => None,
+        };
+        self.searches = searches;
+        let Some(index) = found else {
+            return Ok(());
+        };
+        self.ed.undraw(w)?;
+        queue!(w, Clear(ClearType::FromCursorDown))?;
+        self.set_history_index(index);
+        self.show_status();
+        self.ed.draw_head(w)?;
+        self.ed.redraw_tail(w)
+    }
+
+    /// Stops recalling history entries, so that the next `Up` recalls the most relevant one.
+    ///
+    /// The buffer is left as it is.
+    pub fn reset_navigation(&mut self) {
+        self.hindex = self.history.len();
+    }
+
+    /// Sets whether lines starting with a space are left out of the history, like
+    /// `HISTCONTROL=ignorespace`, so a user can keep a line to themselves
+    pub fn set_history_ignore_space(&mut self, ignore_space: bool) {
+        self.ignore_space = ignore_space
+    }
+
+    /// Sets the fewest characters a line needs, leading and trailing whitespace aside, to be
+    /// recorded in the history, so short lines like `ls` aren't kept
+    pub fn set_history_min_len(&mut self, min_len: usize) {
+        self.min_len = min_len
+    }
+
+    /// Leaves the lines `ignore` accepts out of the history, like `HISTIGNORE`. Lines any
+    /// ignore accepts aren't recorded.
+    ///
+    /// ```no_run
+    /// # use repline::Repline;
+    /// let mut rl = Repline::new("", "> ", ". ");
+    /// rl.add_history_ignore(|line| line.to_lowercase().contains("password"));
+    /// ```
+    pub fn add_history_ignore(&mut self, ignore: impl FnMut(&str) -> bool + 'a) {
+        self.ignores.push(HistoryIgnore(Box::new(ignore)))
+    }
+
+    /// Records a use of `line` in the history, as if it had been entered and accepted
+    pub fn add_history(&mut self, line: impl Into<String>) {
+        self.record(line.into(), Meta::default());
+        self.hindex = self.history.len();
+    }
+
+    /// Returns the text of the history entry at `index`, where the last entry is the most
+    /// relevant. See [history](Self::history) for the entries themselves.
+    pub fn history_get(&self, index: usize) -> Option<&str> {
+        self.history.get(index)
+    }
+
+    /// Adds `entry` to the history as it is, like one from the program's own config, merging
+    /// it like a [loaded](Self::load_history) entry. Unlike [add_history](Self::add_history),
+    /// no use is recorded, and it isn't appended to the history file.
+    pub fn add_history_entry(&mut self, entry: Entry) {
+        self.history.merge([entry]);
+        self.hindex = self.history.len();
+    }
+
+    /// Removes and returns the history entry at `index`, and stops recalling entries.
+    ///
+    /// Entries already appended to a history file stay there until it's
+    /// [saved](Self::save_history) over.
+    pub fn remove_history(&mut self, index: usize) -> Option<Entry> {
+        let entry = self.history.remove(index);
+        self.hindex = self.history.len();
+        entry
+    }
+
+    /// Removes every history entry `keep` rejects, like those containing a password, and
+    /// stops recalling entries
+    pub fn retain_history(&mut self, keep: impl FnMut(&Entry) -> bool) {
+        self.history.retain(keep);
+        self.hindex = self.history.len();
+    }
+
+    /// Removes every history entry
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+        self.hindex = 0;
+    }
+
+    /// Reads the history file at `path`, merging its entries into the history
+    pub fn load_history(&mut self, path: impl AsRef<Path>) -> ReplResult<()> {
+        self.history.load(path)?;
+        self.hindex = self.history.len();
+        Ok(())
+    }
+
+    /// Writes the history to a history file at `path`, replacing its contents
+    pub fn save_history(&self, path: impl AsRef<Path>) -> ReplResult<()> {
+        Ok(self.history.save(path)?)
+    }
+
+    /// Writes the history to `path` as JSON, replacing its contents. See
+    /// [History::export_json].
+    #[cfg(feature = "serde")]
+    pub fn export_history(&self, path: impl AsRef<Path>) -> ReplResult<()> {
+        let mut file = std::io::BufWriter::new(File::create(path)?);
+        self.history.export_json(&mut file)?;
+        Ok(file.flush()?)
+    }
+
+    /// Reads history entries exported as JSON from `path`, merging them into the history
+    #[cfg(feature = "serde")]
+    pub fn import_history(&mut self, path: impl AsRef<Path>) -> ReplResult<()> {
+        self.history
+            .import_json(BufReader::new(File::open(path)?))?;
+        self.hindex = self.history.len();
+        Ok(())
+    }
+
+    /// Appends each line recorded in the history from now on to the history file at `path`,
+    /// so that it's kept even if the program doesn't exit cleanly.
+    ///
+    /// Each use of a line appends another copy, which [load_history](Self::load_history)
+    /// merges. [save_history](Self::save_history) to the same path compacts the file.
+    /// Failures to write to the file are ignored.
+    ///
+    /// The file can be shared by sessions running at the same time: each line is appended
+    /// whole, under an advisory lock, and [reload_history](Self::reload_history) picks up the
+    /// lines the other sessions appended.
+    pub fn append_history(&mut self, path: impl AsRef<Path>) -> ReplResult<()> {
+        self.history_file = Some(HistoryFile::open(path)?);
+        Ok(())
+    }
+
+    /// Merges the lines other sessions appended to the [history file](Self::append_history)
+    /// since it was opened or last reloaded, like `fc -R`, so they can be recalled. Does
+    /// nothing without a history file.
+    ///
+    /// To compact a shared file with [save_history](Self::save_history) without dropping the
+    /// lines of other sessions, reload it first.
+    pub fn reload_history(&mut self) -> ReplResult<()> {
+        let Some(file) = &mut self.history_file else {
+            return Ok(());
+        };
+        self.history.merge(file.reload()?);
+        self.hindex = self.history.len();
+        Ok(())
+    }
+
+    /// Writes the history, the scratch buffers, and the options a user may have changed to a
+    /// session file at `path`, replacing its contents, so that
+    /// [load_session](Self::load_session) can resume where the user left off
+    #[cfg(feature = "serde")]
+    pub fn save_session(&self, path: impl AsRef<Path>) -> ReplResult<()> {
+        let Self { history, ed, buffers, buffer, .. } = self;
+        let buffers = buffers.iter().enumerate();
+        let session = Session {
+            version: session::VERSION,
+            history: history.iter().cloned().collect(),
+            ranking: history.ranking(),
+            context: history.context().map(str::to_owned),
+            context_recall: history.by_context(),
+            // the active buffer's editor is in use, rather than in its place
+            buffers: buffers
+                .map(|(i, (name, b))| (name.clone(), if i == *buffer { ed } else { b }.snapshot()))
+                .collect(),
+            buffer: *buffer,
+            state: ed.snapshot(),
+            align_prompts: ed.align,
+        };
+        let mut file = std::io::BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut file, &session).map_err(std::io::Error::from)?;
+        Ok(file.flush()?)
+    }
+
+    /// Restores a session saved by [save_session](Self::save_session), replacing the
+    /// history, the scratch buffers, and the buffer being edited.
+    ///
+    /// Fails if the file is malformed, or was written by a newer version of repline.
+    #[cfg(feature = "serde")]
+    pub fn load_session(&mut self, path: impl AsRef<Path>) -> ReplResult<()> {
+        let file = BufReader::new(File::open(path)?);
+        let session: Session = serde_json::from_reader(file).map_err(std::io::Error::from)?;
+        if session.version > session::VERSION {
+            let version = session.version;
+            Err(std::io::Error::other(format!(
+                "unsupported session version {version}"
+            )))?
+        }
+        let Session { history, ranking, context, context_recall, buffers, buffer, .. } = session;
+        self.history.set_entries(history);
+        self.history.set_context(context);
+        self.history.set_by_context(context_recall);
+        self.history.set_ranking(ranking);
+        self.reset_navigation();
+
+        let Editor { begin, again, .. } = &self.ed;
+        self.buffers = (buffers.into_iter())
+            .map(|(name, state)| {
+                let mut ed = self.ed.sibling(begin.clone(), again.clone());
+                ed.restore_state(state);
+                (name, ed)
+            })
+            .collect();
+        self.buffer = buffer.min(self.buffers.len().saturating_sub(1));
+        self.set_align_prompts(session.align_prompts);
+        self.restore_state(session.state);
+        Ok(())
+    }
+
+    /// Records a use of `line` in the history, and appends it to the history file, unless
+    /// it's ignored
+    fn record(&mut self, line: String, meta: Meta) {
+        let Self { ignore_space, min_len, ignores, .. } = self;
+        let ignored = (*ignore_space && line.starts_with(' '))
+            || line.trim().chars().count() < *min_len
+            || ignores
+                .iter_mut()
+                .any(|HistoryIgnore(ignore)| ignore(&line));
+        if ignored {
+            return;
+        }
+        let entry = self.history.add_with_meta(line, meta);
+        if let Some(file) = &mut self.history_file {
+            let _ = file.append(&entry);
+        }
+    }
+
+    /// Append line to history and clear it.
+    ///
+    /// Lines containing [secrets](Completer::secrets) are not added to the history, nor are
+    /// lines it's set to ignore, like with [add_history_ignore](Self::add_history_ignore).
+    pub fn accept(&mut self) {
+        self.accept_with_meta(Meta::default())
+    }
+
+    /// [Accepts](Self::accept) the line, recording what's known about it once it's been run,
+    /// like its exit status, in its history entry.
+    ///
+    /// ```no_run
+    /// # use repline::{Repline, history::Meta};
+    /// # fn run(line: &str) -> i32 { 0 }
+    /// let mut rl = Repline::new("", "$ ", "> ");
+    /// let line = rl.read()?;
+    /// let status = run(&line);
+    /// rl.accept_with_meta(Meta { status: Some(status) });
+    /// # Ok::<(), repline::Error>(())
+    /// ```
+    pub fn accept_with_meta(&mut self, meta: Meta) {
+        self.stops.clear();
+        // the rest of a split line, whose line was recorded with the first
+        if self.ed.is_empty() {
+            return;
+        }
+        if self.ed.secrets().is_empty() {
+            self.record(self.ed.to_string(), meta);
+        }
+        self.ed.clear();
+        self.ed.clear_undo();
+        self.hindex = self.history.len();
+    }
+
+    /// Clear the line
+    pub fn deny(&mut self) {
+        self.stops.clear();
+        self.ed.clear();
+        self.ed.clear_undo()
+    }
+
+    /// Iterates over the lines read, each [accepted](Self::accept) into the history and
+    /// without its line break, until Ctrl+C, Ctrl+D, or the end of input.
+    ///
+    /// ```no_run
+    /// # use repline::Repline;
+    /// let mut rl = Repline::new("", "> ", ". ");
+    /// for line in rl.lines() {
+    ///     println!("{}", line?.to_uppercase());
+    /// }
+    /// # Ok::<(), repline::Error>(())
+    /// ```
+    ///
+    /// Any other error ends the iteration after it's returned.
+    pub fn lines(&mut self) -> Lines<'_, 'a, R> {
+        Lines { rl: Some(self) }
+    }
+
+    /// Reads in a line using a nested prompt, with its own buffer and place in the history.
+    ///
+    /// Meant for taking additional input while handling a line, like inside
+    /// [read_and_mut](crate::prebaked::read_and_mut). The outer buffer, prompts, and history
+    /// position are restored afterwards, and the line is not recorded in the history. The
+    /// linter, highlighter, validator, and [Prompt] are left out of the nested prompt.
+    pub fn sub_prompt(&mut self, begin: &'a str) -> ReplResult<String> {
+        let inner = self.ed.sibling(begin, begin);
+        let outer = std::mem::replace(&mut self.ed, inner);
+        let hindex = std::mem::replace(&mut self.hindex, self.history.len());
+        let draft = self.draft.take();
+        let redraw = std::mem::take(&mut self.redraw);
+        let linter = self.linter.take();
+        let highlight = self.highlight.take();
+        let validator = self.validator.take();
+        let prompt = self.prompt.take();
+        let line_numbers = std::mem::take(&mut self.line_numbers);
+
+        let line = self.read_line();
+        if line.is_ok() {
+            self.with_output(|_, stdout| {
+                queue!(stdout, MoveToColumn(0), Clear(ClearType::FromCursorDown))?;
+                Ok(stdout.flush()?)
+            })?;
+        }
+
+        (self.ed, self.hindex, self.draft, self.redraw) = (outer, hindex, draft, redraw);
+        (self.linter, self.highlight, self.validator) = (linter, highlight, validator);
+        (self.prompt, self.line_numbers) = (prompt, line_numbers);
+        line
+    }
+
+    /// Reads in a line using a [nested prompt](Self::sub_prompt) which records its lines in
+    /// the history [context](Self::set_context) `context`, and recalls them first.
+    pub fn sub_prompt_in(&mut self, begin: &'a str, context: &str) -> ReplResult<String> {
+        let outer = self.history.context().map(str::to_owned);
+        let by_context = self.history.by_context();
+        self.history.set_context(Some(context.to_owned()));
+        self.history.set_by_context(true);
+
+        let line = self.sub_prompt(begin);
+        if let Ok(line) = &line {
+            self.record(line.clone(), Meta::default());
+        }
+
+        self.history.set_context(outer);
+        self.history.set_by_context(by_context);
+        self.hindex = self.history.len();
+        line
+    }
+
+    /// Reads in a line, and returns it for validation
+    pub fn read(&mut self) -> ReplResult<String> {
+        if let Some(line) = self.split.pop_front() {
+            return Ok(line);
+        }
+        let start = Instant::now();
+        self.count(|m| *m = Metrics::default());
+        let line = self.read_line();
+        self.count(|m| m.elapsed = start.elapsed());
+        line.map(|line| self.split(line))
+    }
+
+    /// Reads in a line like [read](Self::read), which ends with [Error::TimedOut] if no key
+    /// arrives for `timeout`, like when a prompt is left unattended.
+    ///
+    /// Keys are read from the terminal like with an [idle handler](Self::set_idle_handler).
+    #[cfg(feature = "events")]
+    pub fn read_timeout(&mut self, timeout: Duration) -> ReplResult<String> {
+        let outer = self.idle.take();
+        self.set_idle_handler(timeout, |_| Idle::Abort);
+        let line = self.read();
+        self.idle = outer;
+        line
+    }
+
+    /// Reads in a line like [read](Self::read), awaiting each key instead of blocking the
+    /// thread, so that other tasks can run between keystrokes.
+    ///
+    /// Keys are read through crossterm's [EventStream](crossterm::event::EventStream), which
+    /// works under tokio and other async runtimes. Outside of [ReadMode::Interactive], lines
+    /// are read as-is, blocking, like `read` does.
+    ///
+    /// ```no_run
+    /// # async fn repl() -> repline::error::ReplResult<()> {
+    /// let mut rl = repline::Repline::new("", "> ", ". ");
+    /// loop {
+    ///     let line = rl.read_async().await?;
+    ///     println!("{line}");
+    ///     rl.accept();
+    /// }
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn read_async(&mut self) -> ReplResult<String> {
+        if let Some(line) = self.split.pop_front() {
+            return Ok(line);
+        }
+        let start = Instant::now();
+        self.count(|m| *m = Metrics::default());
+        let line = match self.mode {
+            ReadMode::Interactive => self.edit_async().await,
+            _ => self.read_line(),
+        };
+        self.count(|m| m.elapsed = start.elapsed());
+        line.map(|line| self.split(line))
+    }
+
+    /// Edits a line like [edit](Self::edit), awaiting each key.
+    ///
+    /// The output is taken for each key, rather than held while awaiting the next.
+    #[cfg(feature = "tokio")]
+    async fn edit_async(&mut self) -> ReplResult<String> {
+        self.end = None;
+        let _make_raw = self.raw()?;
+        // keys read while handling one, like while searching the history, must come from
+        // the same reader as the events
+        let events = std::mem::replace(&mut self.events, true);
+        self.with_output(|rl, stdout| {
+            rl.reports(stdout, true)?;
+            rl.start_edit(stdout)?;
+            Ok(stdout.flush()?)
+        })?;
+        let mut stream = crossterm::event::EventStream::new();
+        let mut keys = 0;
+        let line = loop {
+            let key = match self.unread.take() {
+                Some(key) => Some(key),
+                None => match crate::events::next_key(&mut stream).await {
+                    Ok(key) => key,
+                    Err(e) => break Err(e),
+                },
+            };
+            self.time_key();
+            keys += 1;
+            let step = self.with_output(|rl, stdout| {
+                let step = rl.dispatch(key, stdout);
+                if let Err(Error::CtrlC(_) | Error::CtrlD(_) | Error::Action(..)) = step {
+                    rl.unfinished = Some(rl.ed.rows_below());
+                    write!(stdout, "\r\n")?;
+                } else if step.is_ok() && rl.watchdog > 0 && keys % rl.watchdog == 0 {
+                    rl.check_display(stdout)?;
+                }
+                stdout.flush()?;
+                step
+            });
+            match step {
+                Ok(None) => {}
+                Ok(Some(line)) => break Ok(line),
+                Err(e) => break Err(e),
+            }
+        };
+        self.with_output(|rl, stdout| {
+            rl.unflash(stdout)?;
+            rl.reports(stdout, false)?;
+            Ok(stdout.flush()?)
+        })?;
+        self.events = events;
+        line
+    }
+
+    /// Splits `line` with the splitter, if there is one, returning the first logical line
+    /// and keeping the rest for later reads
+    fn split(&mut self, line: String) -> String {
+        let Some(Splitter(splitter)) = &mut self.splitter else {
+            return line;
+        };
+        let mut lines = splitter(&line).into_iter();
+        match lines.next() {
+            Some(first) => {
+                self.split.extend(lines);
+                first
+            }
+            None => line,
+        }
+    }
+
+    fn read_line(&mut self) -> ReplResult<String> {
+        self.end = None;
+        match self.mode {
+            ReadMode::Interactive => {}
+            ReadMode::Dumb => return self.read_plain(true),
+            ReadMode::Plain => return self.read_plain(false),
+        }
+        self.with_output(|rl, stdout| {
+            rl.reports(stdout, true)?;
+            let line = rl.edit(stdout);
+            rl.unflash(stdout)?;
+            rl.reports(stdout, false)?;
+            stdout.flush()?;
+            line
+        })
+    }
+
+    /// Asks the terminal to start (or stop) reporting what the read relies on: focus
+    /// changes, pastes, and mouse clicks, each only if it's used
+    fn reports<W: Write>(&self, w: &mut W, on: bool) -> ReplResult<()> {
+        let set = if on { 'h' } else { 'l' };
+        if self.on_focus.is_some() {
+            queue!(w, Print(format_args!("\x1b[?1004{set}")))?;
+        }
+        if self.brackets_pastes() {
+            queue!(w, Print(format_args!("\x1b[?2004{set}")))?;
+        }
+        if self.mouse {
+            queue!(w, Print(format_args!("\x1b[?1000{set}\x1b[?1006{set}")))?;
+        }
+        Ok(())
+    }
+
+    /// Reads a line as-is, without editing or drawing it, after printing a plain prompt
+    /// if `prompt` is set. The line is added to the buffer, for [accept](Self::accept).
+    fn read_plain(&mut self, prompt: bool) -> ReplResult<String> {
+        loop {
+            if prompt {
+                let prompt = match self.ed.is_empty() {
+                    true => self.ed.begin.clone(),
+                    false => self.ed.again.clone(),
+                };
+                self.with_output(|_, stdout| {
+                    write!(stdout, "{prompt}")?;
+                    Ok(stdout.flush()?)
+                })?;
+            }
+            let mut line = String::new();
+            while let Some(c) = self.next_input() {
+                match c? {
+                    '\n' => {
+                        line.truncate(line.trim_end_matches('\r').len());
+                        line.push('\n');
+                        break;
+                    }
+                    c => line.push(c),
+                }
+            }
+            if line.is_empty() {
+                return Err(Error::EndOfInput);
+            }
+            if line == "\n"
+                && self.ed.is_empty()
+                && let Some(default) = &self.ed.placeholder
+            {
+                line = format!("{default}\n");
+            }
+            let text = self.ed.to_string() + &line;
+            let cursor = text.chars().count();
+            self.ed
+                .set_state(EditorState { text: text.clone(), cursor, ..Default::default() });
+            // gather more lines, like Enter does while editing
+            if self.is_complete(&text) {
+                return Ok(text);
+            }
+        }
+    }
+
+    fn edit(&mut self, stdout: &mut Output<'a>) -> ReplResult<String> {
+        let _make_raw = self.raw()?;
+
+        self.start_edit(stdout)?;
+        let mut keys = 0;
+        loop {
+            if self.watchdog > 0 && keys > 0 && keys % self.watchdog == 0 {
+                self.check_display(stdout)?;
+            }
+            keys += 1;
+            stdout.flush()?;
+            let step = self
+                .next_key(stdout)
+                .and_then(|key| self.dispatch(key, stdout));
+            match step {
+                Ok(None) => {}
+                Ok(Some(line)) => return Ok(line),
+                Err(
+                    e
+                    @ (Error::CtrlC(_) | Error::CtrlD(_) | Error::Action(..) | Error::TimedOut(_)),
+                ) => {
+                    drop(_make_raw);
+                    self.unfinished = Some(self.ed.rows_below());
+                    writeln!(stdout)?;
+                    return Err(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Draws the prompt and the buffer, ready to edit a line
+    fn start_edit<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        (self.ed.cols, self.ed.rows) = self.terminal_size();
+        self.unfinished = None;
+        if let Some(filter) = &mut self.filter {
+            filter.rejected = false;
+        }
+        self.show_status();
+        self.publish(true);
+        if let Some(prompts) = self.make_prompts() {
+            self.ed.set_prompts(prompts);
+        }
+        if std::mem::take(&mut self.cleared) {
+            // nothing drawn before is left, so draw it all again from here
+            self.redraw = false;
+            queue!(w, MoveToColumn(0))?;
+            self.ed.draw_head(w)?;
+            self.ed.redraw_tail(w)?;
+        } else if std::mem::take(&mut self.redraw) {
+            self.ed.redraw(w)?;
+        } else {
+            self.ed.start_head(w)?;
+            if !self.buffers.is_empty() || self.ed.placeholder.is_some() {
+                self.ed.redraw_tail(w)?;
+            }
+        }
+        (self.last, self.last_key) = (None, None);
+        self.closers.clear();
+        Ok(())
+    }
+
+    /// Passes a key read while editing through the [Middleware], and on to
+    /// [handle_key](Self::handle_key), returning the line if it was submitted
+    fn dispatch<W: Write>(&mut self, key: Option<Key>, w: &mut W) -> ReplResult<Option<String>> {
+        self.unflash(w)?;
+        let Some(key) = key.filter(|_| !self.middleware.is_empty()) else {
+            return self.handle_key(key, w);
+        };
+        let mut layers = std::mem::take(&mut self.middleware);
+        let line = Layers { rl: self, layers: &mut layers, w }.next(key);
+        self.middleware = layers;
+        line
+    }
+
+    /// Runs the command bound to a key read while editing, returning the line if the command
+    /// submits it, and tells the [edit hook](Self::set_edit_hook) what it did
+    fn handle_key<W: Write>(&mut self, key: Option<Key>, w: &mut W) -> ReplResult<Option<String>> {
+        let before = self
+            .on_edit
+            .is_some()
+            .then(|| (self.ed.state(), self.hindex));
+        let line = self.run_key(key, w)?;
+        if let Some((before, hindex)) = before {
+            self.edited(before, hindex, line.as_deref());
+        }
+        Ok(line)
+    }
+
+    /// Calls the [edit hook](Self::set_edit_hook) with what changed since `before`, when
+    /// the history entry `hindex` was recalled, if anything did
+    fn edited(&mut self, before: EditorState, hindex: usize, submitted: Option<&str>) {
+        let Some(EditHook(on_edit)) = &mut self.on_edit else {
+            return;
+        };
+        let text = self.ed.to_string();
+        let event = match submitted {
+            Some(line) => return on_edit(EditEvent::Submit, line, line.len()),
+            None if self.hindex != hindex => EditEvent::History,
+            None if text.len() < before.text.len() => EditEvent::Delete,
+            None if text != before.text => EditEvent::Insert,
+            None if self.ed.cursor() != before.cursor => EditEvent::Move,
+            None => return,
+        };
+        on_edit(event, &text, self.ed.byte_cursor())
+    }
+
+    /// Runs the command bound to a key, like [handle_key](Self::handle_key)
+    fn run_key<W: Write>(&mut self, key: Option<Key>, w: &mut W) -> ReplResult<Option<String>> {
+        // the terminal may have been resized, rewrapping long lines
+        (self.ed.cols, self.ed.rows) = self.terminal_size();
+        let (key, command) = match key {
+            Some(Key::Focus(focus)) => {
+                self.focus(w, focus)?;
+                return Ok(None);
+            }
+            Some(Key::Paste) => {
+                self.end_completion(w)?;
+                let before = self.max_len.map(|_| self.ed.state());
+                self.closers.clear();
+                self.paste(w)?;
+                if let Some(line) = self.limit_len(before, w)? {
+                    return Ok(Some(line));
+                }
+                return self.refresh(w).map(|_| None);
+            }
+            Some(Key::Click(row, col)) => {
+                self.end_completion(w)?;
+                self.closers.clear();
+                self.click(w, row as usize, col as usize)?;
+                return self.refresh(w).map(|_| None);
+            }
+            Some(key) => (key, self.keymap.get(key)),
+            None => return Ok(None),
+        };
+        if !matches!(
+            command,
+            Some(EditCommand::Complete | EditCommand::RevertCompletion)
+        ) {
+            self.end_completion(w)?;
+        }
+        self.count(|m| {
+            m.keystrokes += 1;
+            m.backspaces += (command == Some(EditCommand::Backspace)) as usize;
+        });
+        match command {
+            Some(command) => {
+                let before = self.max_len.map(|_| self.ed.state());
+                if let Some(line) = self.run(command, w)? {
+                    return Ok(Some(line));
+                }
+                if let Some(line) = self.limit_len(before, w)? {
+                    return Ok(Some(line));
+                }
+            }
+            None => self.unknown_key(Unhandled::Unbound(key)),
+        }
+        self.refresh(w)?;
+        Ok(None)
+    }
+
+    /// Brings everything drawn from the buffer up to date with it, after a key
+    fn refresh<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        self.redraw_prompts(w)?;
+        self.highlight(w)?;
+        self.lint(w)?;
+        self.mask(w)?;
+        self.echo(w)?;
+        self.publish(false);
+        Ok(())
+    }
+
+    /// Inserts the text of a bracketed paste, once the user confirms it, if it has line
+    /// breaks or control characters and [confirmation](Self::set_paste_confirmation) is on
+    fn paste<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        let text = std::mem::take(&mut self.pasted)
+            .replace("\r\n", "\n")
+            .replace('\r', "\n");
+        let suspicious = text.chars().any(|c| c.is_control() && c != '\t');
+        if self.confirm_paste && suspicious && !self.confirm_paste(&text, w)? {
+            return Ok(());
+        }
+        let before = self.ed.state();
+        if self.ed.delete_selection(w)?.is_none() {
+            self.ed.unmark(w)?;
+        }
+        let text = text
+            .chars()
+            .filter(|&c| matches!(c, '\n' | '\t') || !c.is_control());
+        let room = self
+            .max_len
+            .map_or(usize::MAX, |max| max.saturating_sub(self.ed.len()));
+        let mut text = text.peekable();
+        self.ed.extend(text.by_ref().take(room), w)?;
+        self.ed.checkpoint(before);
+        if text.peek().is_some() {
+            self.ring(w)?;
+        }
+        Ok(())
+    }
+
+    /// Takes back an edit which made the buffer longer than the
+    /// [maximum length](Self::set_max_len), or submits the buffer if the edit filled it and
+    /// [that's set](Self::set_submit_at_max_len). `before` is the buffer before the edit.
+    fn limit_len<W: Write>(
+        &mut self,
+        before: Option<EditorState>,
+        w: &mut W,
+    ) -> ReplResult<Option<String>> {
+        let (Some(max), Some(before)) = (self.max_len, before) else {
+            return Ok(None);
+        };
+        let len = self.ed.len();
+        if len <= before.text.chars().count() {
+            return Ok(None);
+        }
+        if len > max {
+            self.ed.revert(before, w)?;
+            // what was yanked is gone, so there's nothing for the next yank to replace
+            (self.last, self.last_arg) = (None, (0, 0));
+            self.closers.clear();
+            self.ring(w)?;
+        } else if len == max && self.submit_full {
+            return self.run(EditCommand::Submit, w);
+        }
+        Ok(None)
+    }
+
+    /// Previews pasted `text` on the status line, and asks whether to insert it
+    fn confirm_paste<W: Write>(&mut self, text: &str, w: &mut W) -> ReplResult<bool> {
+        let mut preview = String::new();
+        for c in text.chars() {
+            match c {
+                '\n' => preview.push('⏎'),
+                '\t' => preview.push(' '),
+                // caret notation, like ^[ for Esc
+                c if c.is_ascii_control() => {
+                    preview.push('^');
+                    preview.push((c as u8 ^ 0x40) as char);
+                }
+                c if c.is_control() => preview.push('�'),
+                c => preview.push(c),
+            }
+        }
+        let question = self.strings.paste_confirm;
+        let room = self.ed.cols.saturating_sub(question.width() + 2).max(8);
+        if preview.width() > room {
+            preview = preview
+                .chars()
+                .scan(0, |used, c| {
+                    *used += c.to_string().width();
+                    (*used < room).then_some(c)
+                })
+                .chain(['…'])
+                .collect();
+        }
+        self.ed.set_status(Some(format!("{preview}  {question}")));
+        self.ed.redraw_tail(w)?;
+        w.flush()?;
+        let key = loop {
+            if let Some(key) = self.next_key(w)? {
+                break key;
+            }
+        };
+        self.show_status();
+        self.ed.redraw_tail(w)?;
+        Ok(matches!(key, Key::Char('y' | 'Y')))
+    }
+
+    /// Runs an [EditCommand], returning the line if the command submits it
+    fn run<W: Write>(&mut self, command: EditCommand, w: &mut W) -> ReplResult<Option<String>> {
+        use EditCommand as E;
+        let (before, last) = (self.ed.state(), self.last);
+        #[cfg(feature = "clipboard")]
+        {
+            self.awaiting_clipboard = false;
+        }
+        let out = self.run_command(command, w);
+        // a run of typing is undone all at once, up to the whitespace which ends it
+        let typing = match (last, command) {
+            (Some(E::Insert(_)), E::Insert(c)) => !c.is_whitespace(),
+            _ => false,
+        };
+        if !typing && !matches!(command, E::Undo | E::Redo) {
+            self.ed.checkpoint(before);
+        }
+        out
+    }
+
+    /// Returns the indentation a line break at the cursor carries onto the new line: that of
+    /// the line it breaks, and `level` more if the line opens a block
+    fn carried_indent(&mut self, level: &str) -> String {
+        let pasted = self.paste_policy().is_some();
+        let Some(AutoIndent(opens)) = &mut self.auto_indent else {
+            return String::new();
+        };
+        if pasted {
+            return String::new();
+        }
+        let head: String = self.ed.iter().take(self.ed.cursor()).collect();
+        let line = head.rsplit('\n').next().unwrap_or_default();
+        let mut indent: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+        if opens.as_mut().is_some_and(|opens| opens(line)) {
+            indent.push_str(level);
+        }
+        indent
+    }
+
+    /// Runs an [EditCommand], returning the line if it was submitted
+    fn run_command<W: Write>(
+        &mut self,
+        command: EditCommand,
+        w: &mut W,
+    ) -> ReplResult<Option<String>> {
+        use EditCommand as E;
+        const INDENT: &str = "    ";
+        let last = self.last.replace(command);
+        // typing and deleting what was typed leave the inserted closers where they were
+        if !matches!(command, E::Insert(_) | E::Backspace) || self.ed.selection().is_some() {
+            self.closers.clear();
+        }
+        match command {
+            E::Insert(c) => self.insert_filtered(c, w)?,
+            E::Enter | E::Submit if self.ed.is_empty() && self.ed.placeholder.is_some() => {
+                // an empty line stands for the default, which is filled in to be submitted
+                let default = self.ed.placeholder.take().unwrap_or_default();
+                self.ed.extend(default.chars(), w)?;
+                return self.run(command, w);
+            }
+            E::Enter => {
+                // the whole buffer is submitted, wherever the cursor is in it
+                let text = self.ed.to_string() + "\n";
+                if self.paste_policy().is_none_or(|p| p.submit) && self.is_complete(&text) {
+                    return self.run(E::Submit, w);
+                }
+                self.ed.unmark(w)?;
+                let indent = self.carried_indent(INDENT);
+                self.ed.push('\n', w)?;
+                self.ed.extend(indent.chars(), w)?;
+            }
+            E::Submit => {
+                self.ed.unmark(w)?;
+                self.ed.cursor_end(w)?;
+                self.ed.push('\n', w)?;
+                return self.submit(w).map(Some);
+            }
+            E::Newline => {
+                self.ed.unmark(w)?;
+                let indent = self.carried_indent(INDENT);
+                self.ed.push('\n', w)?;
+                self.ed.extend(indent.chars(), w)?;
+            }
+            E::Interrupt => Err(Error::CtrlC(self.ed.to_string()))?,
+            E::EndOfFile if !self.ed.is_empty() => return self.run(E::Delete, w),
+            E::EndOfFile => Err(Error::CtrlD(self.ed.to_string()))?,
+            E::EndOfInput => Err(Error::EndOfInput)?,
+            E::Action(tag) => Err(Error::Action(tag, self.ed.to_string()))?,
+            // visit the next placeholder, expand a snippet, complete, or extend line by 4 spaces
+            E::Complete if self.paste_policy().is_some_and(|p| !p.expand) => {
+                return self.run(E::Indent, w);
+            }
+            E::Complete => {
+                if !self.next_stop(w)? && !self.expand_snippet(w)? && !self.complete(w)? {
+                    return self.run(E::Indent, w);
+                }
+            }
+            E::Indent => {
+                self.ed.unmark(w)?;
+                self.ed.extend(INDENT.chars(), w)?
+            }
+            E::Backspace if self.ed.block_selection().is_some() => {
+                self.ed.block_backspace(w)?;
+            }
+            E::Backspace if self.ed.selection().is_some() => {
+                self.ed.delete_selection(w)?;
+            }
+            E::Backspace => match self.fresh_pair(last) {
+                // the pair was just typed, so take it back whole
+                Some((open, close)) => {
+                    if self.closers.last() == Some(&(self.ed.len() - self.ed.cursor())) {
+                        self.closers.pop();
+                    }
+                    for _ in open.chars() {
+                        self.ed.pop(w)?;
+                    }
+                    for _ in close.chars() {
+                        self.ed.delete(w)?;
+                    }
+                }
+                None if self.ed.ends_with(INDENT.chars()) => {
+                    for _ in 0..INDENT.len() {
+                        self.ed.pop(w)?;
+                    }
+                }
+                None if self.ed.cursor() == 0 => self.ring(w)?,
+                None => {
+                    self.ed.pop(w)?;
+                }
+            },
+            E::Delete => {
+                if !self.ed.block_delete(w)? && self.ed.delete_selection(w)?.is_none() {
+                    self.ed.delete(w)?;
+                }
+            }
+            E::DeleteWord => {
+                self.ed.unmark(w)?;
+                let word = self.ed.erase_word(w)?;
+                self.kills.kill(word, last.is_some_and(E::kills), true)
+            }
+            E::UpcaseWord => self.ed.recase_word(str::to_uppercase, w)?,
+            E::DowncaseWord => self.ed.recase_word(str::to_lowercase, w)?,
+            E::CapitalizeWord => self.ed.recase_word(capitalize, w)?,
+            E::DeleteWordForward => {
+                self.ed.unmark(w)?;
+                let word = self.ed.erase_word_forward(w)?;
+                self.kills.kill(word, last.is_some_and(E::kills), false)
+            }
+            E::KillLineEnd => {
+                self.ed.unmark(w)?;
+                let text = self.ed.erase_line_end(w)?;
+                self.kills.kill(text, last.is_some_and(E::kills), false)
+            }
+            E::KillLineStart => {
+                self.ed.unmark(w)?;
+                let text = self.ed.erase_line_start(w)?;
+                self.kills.kill(text, last.is_some_and(E::kills), true)
+            }
+            E::KillBufferEnd => {
+                self.ed.unmark(w)?;
+                let text = self.ed.erase_buffer_end(w)?;
+                self.kills.kill(text, last.is_some_and(E::kills), false)
+            }
+            E::KillBufferStart => {
+                self.ed.unmark(w)?;
+                let text = self.ed.erase_buffer_start(w)?;
+                self.kills.kill(text, last.is_some_and(E::kills), true)
+            }
+            E::Yank => {
+                self.ed.unmark(w)?;
+                if let Some(text) = self.kills.yank() {
+                    self.ed.extend(text.chars(), w)?
+                }
+            }
+            // swap the text just yanked for the next older kill
+            E::YankPop if matches!(last, Some(E::Yank | E::YankPop)) => {
+                let len = self.kills.yanked().map_or(0, |text| text.chars().count());
+                let cursor = self.ed.cursor();
+                self.ed.erase(cursor.saturating_sub(len)..cursor, w)?;
+                if let Some(text) = self.kills.rotate() {
+                    self.ed.extend(text.chars(), w)?
+                }
+            }
+            // otherwise, it does nothing, and mustn't count as a yank
+            E::YankPop => self.last = last,
+            E::YankLastArg => self.yank_last_arg(w, last == Some(E::YankLastArg))?,
+            #[cfg(feature = "clipboard")]
+            E::Copy => {
+                let text: String = match self.ed.selection() {
+                    Some(range) => self.ed.iter().skip(range.start).take(range.len()).collect(),
+                    None => self.kills.newest().unwrap_or_default().to_string(),
+                };
+                if !text.is_empty() {
+                    queue!(w, Print(clipboard::copy(&text, self.multiplexer)))?;
+                }
+            }
+            #[cfg(feature = "clipboard")]
+            E::PasteClipboard => {
+                queue!(w, Print(clipboard::request(self.multiplexer)))?;
+                self.awaiting_clipboard = true;
+            }
+            E::Up => {
+                let recall = match self.arrows {
+                    ArrowKeys::Edges => self.ed.on_first_line(),
+                    ArrowKeys::History => true,
+                    ArrowKeys::Lines => false,
+                };
+                if !(recall && self.history_prev(w)?) {
+                    let cursor = self.ed.cursor();
+                    self.motion(w, Editor::cursor_up)?;
+                    if recall && self.ed.cursor() == cursor {
+                        self.ring(w)?
+                    }
+                }
+            }
+            E::Down => {
+                let recall = match self.arrows {
+                    ArrowKeys::Edges => self.ed.on_last_line(),
+                    ArrowKeys::History => true,
+                    ArrowKeys::Lines => false,
+                };
+                if !(recall && self.history_next(w)?) {
+                    let cursor = self.ed.cursor();
+                    self.motion(w, Editor::cursor_down)?;
+                    if recall && self.ed.cursor() == cursor {
+                        self.ring(w)?
+                    }
+                }
+            }
+            E::Left => self.motion(w, Editor::cursor_back)?,
+            E::Right => self.motion(w, Editor::cursor_forward)?,
+            E::WordLeft => self.motion(w, Editor::cursor_word_back)?,
+            E::WordRight => self.motion(w, Editor::cursor_word_forward)?,
+            E::LineStart => self.motion(w, Editor::cursor_line_start)?,
+            E::LineEnd => self.motion(w, Editor::cursor_line_end)?,
+            E::BufferStart => self.motion(w, Editor::cursor_start)?,
+            E::BufferEnd => self.motion(w, Editor::cursor_end)?,
+            E::HistoryPrev => {
+                if !self.history_prev(w)? {
+                    self.ring(w)?
+                }
+            }
+            E::HistoryNext => {
+                if !self.history_next(w)? {
+                    self.ring(w)?
+                }
+            }
+            E::HistoryFirst => self.history_first(w)?,
+            E::HistoryLast => self.history_last(w)?,
+            E::SelectUp => self.select(w, Editor::cursor_up)?,
+            E::SelectDown => self.select(w, Editor::cursor_down)?,
+            E::SelectLeft => self.select(w, Editor::cursor_back)?,
+            E::SelectRight => self.select(w, Editor::cursor_forward)?,
+            E::SelectWordLeft => self.select(w, Editor::cursor_word_back)?,
+            E::SelectWordRight => self.select(w, Editor::cursor_word_forward)?,
+            E::SelectLineStart => self.select(w, Editor::cursor_line_start)?,
+            E::SelectLineEnd => self.select(w, Editor::cursor_line_end)?,
+            E::SelectBufferStart => self.select(w, Editor::cursor_start)?,
+            E::SelectBufferEnd => self.select(w, Editor::cursor_end)?,
+            E::BlockUp => self.select_block(w, Editor::cursor_up)?,
+            E::BlockDown => self.select_block(w, Editor::cursor_down)?,
+            E::BlockLeft => self.select_block(w, Editor::cursor_back)?,
+            E::BlockRight => self.select_block(w, Editor::cursor_forward)?,
+            E::Unsurround => self.unsurround(w)?,
+            E::SelectInside => self.select_pair(w, true)?,
+            E::SelectAround => self.select_pair(w, false)?,
+            E::DeleteInside => self.delete_pair(w, true)?,
+            E::DeleteAround => self.delete_pair(w, false)?,
+            E::NextDiagnostic => self.jump_to_diagnostic(w, true)?,
+            E::PrevDiagnostic => self.jump_to_diagnostic(w, false)?,
+            E::ApplyFix => self.apply_fix(w)?,
+            E::RevertCompletion => self.revert_completion(w)?,
+            E::NextBuffer => self.next_buffer(w)?,
+            E::Format => self.format(w)?,
+            E::ExternalEditor => self.edit_externally(w)?,
+            E::ClearScreen => self.ed.clear_screen(w)?,
+            E::Suspend => self.suspend(w)?,
+            E::SearchHistory => self.incremental_search(w)?,
+            E::InsertCodepoint => self.codepoint_entry(w)?,
+            E::SearchWith(search, direction) => self.search_with(w, search, direction)?,
+            E::Undo => {
+                self.ed.undo(w)?;
+            }
+            E::Redo => {
+                self.ed.redo(w)?;
+            }
+            E::Ignore => {}
+        }
+        Ok(None)
+    }
+
+    /// Finishes drawing the buffer, which ends in a line break, and returns its contents
+    fn submit<W: Write>(&mut self, w: &mut W) -> ReplResult<String> {
+        self.clear_echo(w)?;
+        self.ed.set_status(None);
+        self.ed.unscroll(w)?;
+        // the cursor is left on the line after the buffer
+        self.end = Some(EndPosition { up: 1, column: self.ed.end_column() });
+        Ok(self.ed.to_string())
+    }
+
+    /// Updates the [Metrics], if they're enabled
+    fn count(&mut self, f: impl FnOnce(&mut Metrics)) {
+        if let Some(metrics) = &mut self.metrics {
+            f(metrics)
+        }
+    }
+
+    /// Runs the [Highlighter] if the buffer has changed, and redraws the buffer from where
+    /// the highlighting changed, or from the span covering the cursor (where the edit was)
+    fn highlight<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        let Self { highlight: Some(highlight), ed, .. } = self else {
+            return Ok(());
+        };
+        let Some(spans) = highlight.highlight(ed.to_string()) else {
+            return Ok(());
+        };
+        let cursor = ed.cursor();
+        let near = spans
+            .iter()
+            .find(|(span, _)| span.start <= cursor && cursor <= span.end);
+        let near = near.map(|(span, _)| span.start);
+        if let Some(from) = first_restyled(ed.highlights(), &spans)
+            .into_iter()
+            .chain(near)
+            .min()
+        {
+            ed.set_highlights(spans);
+            ed.redraw_from(from, w)?;
+        }
+        Ok(())
+    }
+
+    /// Evaluates the buffer with the [echo](Self::set_echo) function, and draws its result at
+    /// the right edge of the cursor's line, in place of the last one
+    fn echo<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        let Some(echo) = &mut self.echo else {
+            return Ok(());
+        };
+        let Some(value) = (echo.echo)(&self.ed.to_string()) else {
+            return self.clear_echo(w);
+        };
+        self.clear_echo(w)?;
+        let columns = match self.terminal_size().0 {
+            0 => 80,
+            width => width,
+        };
+        let Self { echo: Some(echo), ed, .. } = self else {
+            return Ok(());
+        };
+        let line = ed.line();
+        let start = columns.saturating_sub(value.width() + 1);
+        // leave a gap after the text, or don't draw it at all
+        if start > ed.line_end_column(line) + 1 {
+            let value = PrintStyledContent(ed.theme.hint.apply(value.as_str()));
+            queue!(
+                w,
+                MoveToColumn(start as u16),
+                value,
+                MoveToColumn(ed.column() as u16)
+            )?;
+            echo.drawn = Some((line, start));
+        }
+        Ok(())
+    }
+
+    /// Erases the [echo](Self::set_echo) result, if one is drawn
+    fn clear_echo<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        let Self { echo: Some(Echo { drawn, .. }), ed, .. } = self else {
+            return Ok(());
+        };
+        let Some((line, start)) = drawn.take() else {
+            return Ok(());
+        };
+        // the text may have grown over it since
+        let start = start.max(ed.line_end_column(line)) as u16;
+        let (row, drawn) = (ed.cursor_row(), ed.row(line));
+        let (up, down) = (row.saturating_sub(drawn), drawn.saturating_sub(row));
+        if up > 0 {
+            queue!(w, MoveUp(up as u16))?;
+        }
+        if down > 0 {
+            queue!(w, MoveDown(down as u16))?;
+        }
+        queue!(w, MoveToColumn(start), Clear(ClearType::UntilNewLine))?;
+        if up > 0 {
+            queue!(w, MoveDown(up as u16))?;
+        }
+        if down > 0 {
+            queue!(w, MoveUp(down as u16))?;
+        }
+        queue!(w, MoveToColumn(ed.column() as u16))?;
+        Ok(())
+    }
+
+    /// Runs the [Diagnostics] provider if the buffer has changed, and redraws its results
+    fn lint<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        let Self { linter: Some(linter), ed, .. } = self else {
+            return Ok(());
+        };
+        if linter.lint(ed.to_string()) {
+            let decorations = linter.decorations();
+            // the status line is redrawn along with the buffer after the cursor
+            let from = first_restyled(ed.decorations(), &decorations).unwrap_or(ed.cursor());
+            ed.set_decorations(decorations);
+            self.show_status();
+            self.ed.redraw_from(from, w)?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the buffer with the [Formatter], or shows why it couldn't
+    fn format<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        let Self { formatter: Some(BoxedFormatter(formatter)), ed, .. } = self else {
+            return Ok(());
+        };
+        let text = ed.to_string();
+        match formatter.format(&text) {
+            // formatters tend to end their output with a newline, which the buffer may lack
+            Ok(new) if !text.ends_with('\n') => ed.replace_all(new.trim_end_matches('\n'), w),
+            Ok(new) => ed.replace_all(&new, w),
+            Err(e) => self.print_err(w, format_args!("\t{}", self.ed.theme.error.apply(e))),
+        }
+    }
+
+    /// Stops the process group with the terminal out of raw mode, as the shell expects it,
+    /// until the shell continues it
+    fn suspend<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        // the user of a remote terminal can't continue a process they don't run
+        #[cfg(unix)]
+        if self.backend.is_none() {
+            // the terminal is out of raw mode, and its reports are off, before the signal is
+            // sent, so the shell gets it back as it expects it
+            self.cooked(w, |_| {
+                // SAFETY: kill takes no pointers. A pid of 0 signals every process in this
+                // process group, this one included, like the shell's own Ctrl+Z would. This
+                // process stops before kill returns, so it returns once the group is continued.
+                unsafe { libc::kill(0, libc::SIGTSTP) };
+            })?;
+        }
+        #[cfg(not(unix))]
+        let _ = w;
+        Ok(())
+    }
+
+    /// Opens the buffer in `$VISUAL` (or `$EDITOR`) with the terminal out of raw mode, and
+    /// loads what the editor saved back into the buffer once it exits successfully
+    fn edit_externally<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        let var = |name| {
+            std::env::var(name)
+                .ok()
+                .filter(|v: &String| !v.trim().is_empty())
+        };
+        let Some(editor) = var("VISUAL").or_else(|| var("EDITOR")) else {
+            let message = self.strings.no_editor;
+            return self.print_err(w, format_args!("\t{}", self.ed.theme.error.apply(message)));
+        };
+        let file = TempFile::create(&self.ed.to_string())?;
+
+        // the editor may be set with arguments, like `code --wait`
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or_default();
+        let status = self.cooked(w, |_| {
+            std::process::Command::new(program)
+                .args(words)
+                .arg(&file.0)
+                .status()
+        })?;
+        let text = std::fs::read_to_string(&file.0);
+        drop(file);
+
+        let text = match status {
+            Ok(status) if !status.success() => Err(status.to_string()),
+            Ok(_) => text.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        let text = match text {
+            Ok(text) => text,
+            Err(e) => {
+                let message = self.ed.theme.error.apply(format!("{editor}: {e}"));
+                return self.print_err(w, format_args!("\t{message}"));
+            }
+        };
+        // editors end the file with a newline, which the buffer may lack
+        match self.ed.to_string().ends_with('\n') {
+            true => self.ed.replace_all(&text, w),
+            false => self
+                .ed
+                .replace_all(text.strip_suffix('\n').unwrap_or(&text), w),
+        }
+    }
+
+    /// Asks the [Completer] which parts of the buffer are secret, and redraws them masked
+    fn mask<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        let Self { completion: Some(completion), ed, .. } = self else {
+            return Ok(());
+        };
+        let buf: Vec<char> = ed.iter().copied().collect();
+        let secrets = completion.secrets(&buf);
+        // the editor masks characters typed just after a secret, in case they're part of it
+        let guessed = ed
+            .secrets()
+            .iter()
+            .find(|s| s.end + 1 == ed.cursor())
+            .map(|s| s.end);
+        let unit = |ranges: &[Range<usize>]| -> Vec<_> {
+            ranges.iter().map(|range| (range.clone(), ())).collect()
+        };
+        let changed = first_restyled(&unit(ed.secrets()), &unit(&secrets));
+        if let Some(from) = changed.into_iter().chain(guessed).min() {
+            ed.set_secrets(secrets);
+            ed.redraw_from(from, w)?;
+        }
+        Ok(())
+    }
+
+    /// Moves the cursor to the next (or previous) diagnostic, and shows it on the status line
+    fn jump_to_diagnostic<W: Write>(&mut self, w: &mut W, forward: bool) -> ReplResult<()> {
+        let Self { linter: Some(linter), ed, .. } = self else {
+            return Ok(());
+        };
+        let span = match forward {
+            true => linter.select_next(ed.cursor()),
+            false => linter.select_prev(ed.cursor()),
+        };
+        if let Some(span) = span {
+            ed.unmark(w)?;
+            ed.cursor_to(span.start, w)?;
+            self.show_status();
+            self.ed.redraw_tail(w)?;
+        }
+        Ok(())
+    }
+
+    /// Applies the quick fix of the diagnostic shown on the status line
+    fn apply_fix<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        let Self { linter: Some(linter), ed, .. } = self else {
+            return Ok(());
+        };
+        if let Some((span, replacement)) = linter.fix() {
+            ed.unmark(w)?;
+            ed.rewrite(w, |buf| {
+                buf.splice(span.clone(), replacement.chars());
+                span.start + replacement.chars().count()
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Expands the longest snippet whose trigger is a word before the cursor, and selects
+    /// its first placeholder.
+    ///
+    /// Returns false if there is no such snippet.
+    fn expand_snippet<W: Write>(&mut self, w: &mut W) -> ReplResult<bool> {
+        let Self { snippets, stops, ed, .. } = self;
+        let cursor = ed.cursor();
+        let is_word = |at: Option<usize>| {
+            at.and_then(|at| ed.iter().nth(at))
+                .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+        };
+        let Some((trigger, body)) = (snippets.iter())
+            .filter(|(trigger, _)| !trigger.is_empty() && ed.ends_with(trigger.chars()))
+            .filter(|(trigger, _)| !is_word((cursor - trigger.chars().count()).checked_sub(1)))
+            .max_by_key(|(trigger, _)| trigger.len())
+        else {
+            return Ok(false);
+        };
+        let start = cursor - trigger.chars().count();
+        let Expansion { text, stops: found } = snippet::expand(body);
+        let end = start + text.len();
+
+        ed.unmark(w)?;
+        ed.rewrite(w, |buf| {
+            buf.splice(start..cursor, text);
+            end
+        })?;
+        let len = ed.len();
+        *stops = found
+            .into_iter()
+            .rev()
+            .map(|r| (len - start - r.start, r.len()))
+            .collect();
+        self.next_stop(w)?;
+        Ok(true)
+    }
+
+    /// Completes the word under the cursor, if there is a [Completer].
+    ///
+    /// Returns false if there is no word to complete.
+    fn complete<W: Write>(&mut self, w: &mut W) -> ReplResult<bool> {
+        let Self { completion: Some(completion), ed, metrics, history, .. } = self else {
+            return Ok(false);
+        };
+        let buf: Vec<char> = ed.iter().copied().collect();
+        let cursor = ed.cursor();
+        let line_start = buf[..cursor]
+            .iter()
+            .rposition(|&c| c == '\n')
+            .map_or(0, |i| i + 1);
+        if !completion.cycling() && buf[line_start..cursor].iter().all(|c| c.is_whitespace()) {
+            return Ok(false);
+        }
+        let edit = completion.complete(&buf, cursor, history);
+        if edit.is_some()
+            && let Some(metrics) = metrics
+        {
+            metrics.completions += 1;
+        }
+        self.ed.set_preview(completion.preview());
+        self.show_status();
+        match edit {
+            Some((span, replacement)) => {
+                self.ed.unmark(w)?;
+                self.ed.rewrite(w, |buf| {
+                    buf.splice(span.clone(), replacement.chars());
+                    span.start + replacement.chars().count()
+                })?;
+            }
+            None => {
+                self.ed.redraw_tail(w)?;
+                if self.completion.as_ref().is_some_and(|c| c.shown.is_empty()) {
+                    self.ring(w)?;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Asks the terminal where the cursor is, and repaints the buffer if it isn't where
+    /// the editor expects it to be
+    fn check_display<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        let Some((_, column)) = self.query_cursor(w)? else {
+            return Ok(());
+        };
+        let width = match self.terminal_size().0 {
+            0 => usize::MAX,
+            width => width,
+        };
+        if column as usize != self.ed.column() % width + 1 {
+            queue!(w, MoveToColumn(0), Clear(ClearType::FromCursorDown))?;
+            self.show_status();
+            self.ed.draw_head(w)?;
+            self.ed.redraw_tail(w)?;
+        }
+        Ok(())
+    }
+
+    /// Tells the editor that the screen was cleared from outside, like by running `clear`
+    /// while handling a line, so that the next [read](Self::read) draws the whole buffer
+    /// again where the cursor is, rather than relative to where it was drawn before
+    pub fn notify_screen_cleared(&mut self) {
+        self.cleared = true
+    }
+
+    /// Notices when the screen was cleared since the last read returned, if the
+    /// [watchdog](Self::set_watchdog) is allowed to ask the terminal where the cursor is.
+    ///
+    /// The lines of a buffer still being gathered sit right above the cursor, so if the
+    /// cursor is nearer the top of the screen than that, they've been cleared away.
+    #[cfg(feature = "prebaked")]
+    pub(crate) fn detect_screen_cleared(&mut self) -> ReplResult<()> {
+        if self.watchdog == 0 || self.ed.is_empty() || self.mode != ReadMode::Interactive {
+            return Ok(());
+        }
+        let _make_raw = self.raw()?;
+        let Some((row, _)) = self.with_output(|rl, stdout| rl.query_cursor(stdout))? else {
+            return Ok(());
+        };
+        if row as usize <= self.ed.cursor_row() {
+            self.cleared = true;
+        }
+        Ok(())
+    }
+
+    /// Asks the terminal where the cursor is, returning its 1-based row and column
+    fn query_cursor<W: Write>(&mut self, w: &mut W) -> ReplResult<Option<(u16, u16)>> {
+        // the event reader takes the report for itself
+        #[cfg(feature = "events")]
+        if self.events || self.idle.is_some() {
+            w.flush()?;
+            let position = crossterm::cursor::position().ok();
+            return Ok(position.map(|(column, row)| (row + 1, column + 1)));
+        }
+        queue!(w, Print("\x1b[6n"))?;
+        w.flush()?;
+        self.cursor_report()
+    }
+
+    /// Reads a cursor position report (`ESC [ row ; column R`), returning `None` if the
+    /// input is something else
+    fn cursor_report(&mut self) -> ReplResult<Option<(u16, u16)>> {
+        let mut next = || self.input.next().ok_or(Error::EndOfInput);
+        if next()?? != '\x1b' || next()?? != '[' {
+            return Ok(None);
+        }
+        let mut report = String::new();
+        loop {
+            match next()?? {
+                'R' => break,
+                c @ ('0'..='9' | ';') => report.push(c),
+                _ => return Ok(None),
+            }
+        }
+        let Some((row, column)) = report.split_once(';') else {
+            return Ok(None);
+        };
+        Ok(row.parse().ok().zip(column.parse().ok()))
+    }
+
+    /// Stops cycling through completion candidates, and removes them from the status line
+    fn end_completion<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        let Some(completion) = &mut self.completion else {
+            return Ok(());
+        };
+        let (shown, cycling) = (!completion.shown.is_empty(), completion.cycling());
+        completion.end();
+        self.ed.set_preview(None);
+        if shown {
+            self.show_status();
+        }
+        // the previewed candidate is before the cursor
+        if cycling {
+            self.ed.redraw(w)?;
+        } else if shown {
+            self.ed.redraw_tail(w)?;
+        }
+        Ok(())
+    }
+
+    /// Stops cycling through completion candidates, putting back the word as it was typed
+    fn revert_completion<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        let Some((span, typed)) = self.completion.as_mut().and_then(Completion::revert) else {
+            return Ok(());
+        };
+        self.ed.set_preview(None);
+        self.show_status();
+        self.ed.rewrite(w, |buf| {
+            buf.splice(span.clone(), typed.chars());
+            span.start + typed.chars().count()
+        })
+    }
+
+    /// Selects the next unvisited snippet placeholder.
+    ///
+    /// Returns false if there are none left.
+    fn next_stop<W: Write>(&mut self, w: &mut W) -> ReplResult<bool> {
+        let Self { stops, ed, .. } = self;
+        let Some((from_end, len)) = stops.pop() else {
+            return Ok(false);
+        };
+        let Some(start) = ed.len().checked_sub(from_end) else {
+            stops.clear();
+            return Ok(false);
+        };
+        ed.unmark(w)?;
+        match len {
+            0 => ed.cursor_to(start, w)?,
+            _ => ed.select(start..start + len, w)?,
+        }
+        Ok(true)
+    }
+
+    /// Inserts a character at the cursor, closing and skipping over registered
+    /// [pairs](Self::add_pair)
+    fn insert<W: Write>(&mut self, c: char, w: &mut W) -> ReplResult<()> {
+        let pairing = self.paste_policy().is_none_or(|p| p.pairs);
+        let Self { pairs, closers, surround, ed, .. } = self;
+        let pairs: &[_] = if pairing { pairs } else { &[] };
+        if ed.block_insert(c, w)? {
+            return Ok(());
+        }
+        if ed.selection().is_some() {
+            if let Some((open, close)) = pairs.iter().find(|(open, _)| open.starts_with(c))
+                && *surround
+            {
+                ed.surround(open, close, w)?;
+                return Ok(());
+            }
+            ed.delete_selection(w)?;
+        }
+
+        // Type over a closing delimiter which was inserted in front of the cursor
+        if closers.last() == Some(&(ed.len() - ed.cursor()))
+            && let Some((_, close)) = pairs
+                .iter()
+                .find(|(_, close)| close.starts_with(c) && ed.starts_with(close.chars()))
+        {
+            closers.pop();
+            ed.cursor_to(ed.cursor() + close.chars().count(), w)?;
+            return Ok(());
+        }
+
+        ed.push(c, w)?;
+        if let Some((_, close)) = pairs
+            .iter()
+            .filter(|(open, _)| ed.ends_with(open.chars()))
+            .max_by_key(|(open, _)| open.len())
+        {
+            ed.extend(close.chars(), w)?;
+            ed.cursor_to(ed.cursor() - close.chars().count(), w)?;
+            closers.push(ed.len() - ed.cursor());
+        }
+        Ok(())
+    }
+
+    /// Inserts a character like [insert](Self::insert), unless the [filter](Self::set_filter)
+    /// rejects the result, which rings the bell and shows the filter's hint instead
+    fn insert_filtered<W: Write>(&mut self, c: char, w: &mut W) -> ReplResult<()> {
+        let (before, closers) = (self.ed.state(), self.closers.clone());
+        self.insert(c, w)?;
+        let Some(filter) = &mut self.filter else {
+            return Ok(());
+        };
+        let accepted = (filter.filter)(&self.ed.to_string());
+        if accepted && !filter.rejected {
+            return Ok(());
+        }
+        filter.rejected = !accepted;
+        if !accepted {
+            self.ed.revert(before, w)?;
+            self.closers = closers;
+            self.ring(w)?;
+        }
+        self.show_status();
+        self.ed.redraw_tail(w)
+    }
+
+    /// Signals that a key couldn't do anything, as set by [set_bell](Self::set_bell)
+    fn ring<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        match self.bell {
+            Bell::Audible => queue!(w, Print('\x07'))?,
+            // reverses the colors of the whole screen, until the next key or redraw
+            Bell::Visible => {
+                queue!(w, Print("\x1b[?5h"))?;
+                self.flashing = true;
+            }
+            Bell::None => {}
+        }
+        Ok(())
+    }
+
+    /// Puts the colors of the screen back, if a visible bell reversed them
+    fn unflash<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        if std::mem::take(&mut self.flashing) {
+            queue!(w, Print("\x1b[?5l"))?;
+        }
+        Ok(())
+    }
+
+    /// Prints a message without moving the cursor.
+    ///
+    /// Outside of [ReadMode::Interactive], the message is printed on a line of its own.
+    pub fn print_inline(&mut self, value: impl std::fmt::Display) -> ReplResult<()> {
+        self.with_output(|rl, stdout| {
+            match rl.mode {
+                ReadMode::Interactive => rl.print_err(stdout, value)?,
+                _ => writeln!(stdout, "{value}")?,
+            }
+            Ok(stdout.flush()?)
+        })
+    }
+
+    /// Leaves the buffer of a read which ended mid-edit, like by `Ctrl+C` or an
+    /// [Action](EditCommand::Action), on screen as it was, like a submitted line: the cursor
+    /// is moved below it, the style is reset, and the status line is cleared away.
+    ///
+    /// Call it right after the read returns, before printing anything else. It does nothing
+    /// if the last read didn't end mid-edit.
+    pub fn finalize(&mut self) -> ReplResult<()> {
+        let Some(rows) = self.unfinished.take() else {
+            return Ok(());
+        };
+        self.with_output(|_, stdout| {
+            // the read left the cursor at the start of the row after its own
+            queue!(
+                stdout,
+                Print("\x1b[0m"),
+                Print("\n".repeat(rows)),
+                MoveToColumn(0)
+            )?;
+            queue!(stdout, Clear(ClearType::FromCursorDown))?;
+            Ok(stdout.flush()?)
+        })
+    }
+
+    /// Runs `f` with the terminal as it was before the read: out of raw mode, with the cursor
+    /// shown. This lets `f` run a pager, an editor, or another program which reads from or
+    /// draws on the terminal.
+    ///
+    /// If a line is being read, its buffer is cleared away first, and drawn again after.
+    pub fn with_cooked_terminal<T>(&mut self, f: impl FnOnce() -> T) -> ReplResult<T> {
+        self.with_output(|rl, stdout| rl.cooked(stdout, |_| f()))
+    }
+
+    /// Leaves the terminal as it was before the read, until [resume](Self::resume): out of
+    /// raw mode, with the cursor shown below the buffer. This lets a REPL run a program or
+    /// print freely, like for a `:!cmd` command.
+    ///
+    /// Unlike [with_cooked_terminal](Self::with_cooked_terminal), the buffer is left on
+    /// screen, above whatever is printed. Between reads, like in the closure of
+    /// [read_and_mut](crate::prebaked::read_and_mut), the terminal is already out of raw
+    /// mode, and is left that way.
+    pub fn pause(&mut self) -> ReplResult<()> {
+        if self.paused.is_some() {
+            return Ok(());
+        }
+        let raw = self.is_raw()?;
+        self.with_output(|rl, stdout| {
+            let rows = rl.ed.rows_below() + 1;
+            queue!(
+                stdout,
+                Print("\x1b[0m"),
+                Print("\n".repeat(rows)),
+                MoveToColumn(0)
+            )?;
+            queue!(stdout, Clear(ClearType::FromCursorDown), Show)?;
+            if raw {
+                rl.reports(stdout, false)?;
+            }
+            Ok(stdout.flush()?)
+        })?;
+        if raw {
+            self.set_raw(false)?;
+        }
+        self.paused = Some(raw);
+        Ok(())
+    }
+
+    /// Puts the terminal back in the mode it was in before [pause](Self::pause), and draws
+    /// the prompt and buffer again, below whatever was printed
+    pub fn resume(&mut self) -> ReplResult<()> {
+        let Some(raw) = self.paused.take() else {
+            return Ok(());
+        };
+        if raw {
+            self.set_raw(true)?;
+        }
+        self.with_output(|rl, stdout| {
+            if raw {
+                rl.reports(stdout, true)?;
+            }
+            // whatever ran may have left the cursor anywhere on the row
+            queue!(stdout, MoveToColumn(0))?;
+            rl.ed.draw_head(stdout)?;
+            rl.ed.redraw_tail(stdout)?;
+            Ok(stdout.flush()?)
+        })
+    }
+
+    /// Runs `f` like [with_cooked_terminal](Self::with_cooked_terminal), on the terminal `w`
+    fn cooked<W: Write, T>(&mut self, w: &mut W, f: impl FnOnce(&mut Self) -> T) -> ReplResult<T> {
+        let raw = self.is_raw()?;
+        if raw {
+            self.ed.undraw(w)?;
+            queue!(w, Clear(ClearType::FromCursorDown))?;
+            self.reports(w, false)?;
+        }
+        queue!(w, Show)?;
+        w.flush()?;
+        if !raw {
+            return Ok(f(self));
+        }
+        self.set_raw(false)?;
+        let out = f(self);
+        self.set_raw(true)?;
+        self.reports(w, true)?;
+        // whatever ran may have left the cursor anywhere on the row
+        queue!(w, MoveToColumn(0))?;
+        self.ed.draw_head(w)?;
+        self.ed.redraw_tail(w)?;
+        Ok(out)
+    }
+
+    /// Reads a single key, without editing or drawing anything, for interactions like "press
+    /// any key", a y/n confirmation, or paging through output.
+    ///
+    /// Keys are decoded like while editing, so `Ctrl+C` is read as `Key::Ctrl('c')`, and
+    /// it's up to the caller what it means. Outside of [ReadMode::Interactive], characters
+    /// are read as-is, and a line break is read as [Key::Enter].
+    pub fn read_key(&mut self) -> ReplResult<Key> {
+        if self.mode != ReadMode::Interactive {
+            return Ok(match self.next_char()? {
+                '\n' => Key::Enter,
+                c => Key::Char(c),
+            });
+        }
+        self.with_output(|rl, stdout| {
+            let _make_raw = rl.raw()?;
+            stdout.flush()?;
+            loop {
+                if let Some(key) = rl.next_key(stdout)? {
+                    return Ok(key);
+                }
+            }
+        })
+    }
+
+    /// Prints `text` in `color` on lines of its own, like a banner before the first read,
+    /// or a parting message after the last. It's drawn where the prompt is, and mirrored.
+    ///
+    /// Outside of [ReadMode::Interactive], the text is printed without color.
+    pub fn print_message(&mut self, color: &str, text: impl std::fmt::Display) -> ReplResult<()> {
+        self.with_output(|rl, stdout| {
+            match rl.mode {
+                ReadMode::Interactive => writeln!(stdout, "{color}{text}\x1b[0m")?,
+                _ => writeln!(stdout, "{text}")?,
+            }
+            Ok(stdout.flush()?)
+        })
+    }
+
+    /// Writes `text` as-is where the buffer is drawn, for the prebaked prompts
+    #[cfg(feature = "prebaked")]
+    pub(crate) fn write_out(&mut self, text: impl std::fmt::Display) -> ReplResult<()> {
+        self.with_output(|_, stdout| {
+            write!(stdout, "{text}")?;
+            Ok(stdout.flush()?)
+        })
+    }
+
+    /// Returns how lines are read
+    pub fn mode(&self) -> ReadMode {
+        self.mode
+    }
+
+    /// Sets how lines are read
+    pub fn set_mode(&mut self, mode: ReadMode) {
+        self.mode = mode
+    }
+
+    /// Mirrors everything drawn on the terminal to `mirror`, such as another terminal or a
+    /// recording. Only the terminal accepts input.
+    ///
+    /// A mirror which fails to write is removed.
+    pub fn add_mirror(&mut self, mirror: impl Write + 'a) {
+        self.mirrors.0.push(Box::new(mirror))
+    }
+
+    /// Removes all mirrors
+    pub fn clear_mirrors(&mut self) {
+        self.mirrors.0.clear()
+    }
+
+    /// Set whether colors and styles are drawn, including the prompt's color, highlighting,
+    /// and error messages. Without them, only the escape sequences which move the cursor
+    /// and clear the screen are drawn.
+    ///
+    /// They're drawn unless `NO_COLOR` is set, or `TERM` is `dumb`.
+    pub fn set_colors(&mut self, colors: bool) {
+        self.colors = colors
+    }
+
+    /// Checks that the terminal's cursor is where the editor expects it after every `keys`
+    /// keys, repainting the buffer if something else has written over it. `0` disables the
+    /// check.
+    ///
+    /// The check asks the terminal for the cursor position, so keys typed while the answer
+    /// is on its way may be lost. Only the cursor's column is compared.
+    pub fn set_watchdog(&mut self, keys: usize) {
+        self.watchdog = keys
+    }
+
+    /// Draws on a background thread, waiting at most `budget` after each key for the
+    /// terminal to catch up, or draws in line with editing if `None` (the default).
+    ///
+    /// This keeps typing responsive on slow terminals, like serial consoles: whatever
+    /// is drawn while the terminal is still busy gets coalesced, and written all at once.
+    pub fn set_render_budget(&mut self, budget: Option<Duration>) {
+        self.render_budget = budget
+    }
+
+    /// Chooses whether keys are read through crossterm's event reader, instead of decoded
+    /// from the raw bytes of the input.
+    ///
+    /// Events are read by default on Windows, whose consoles don't send escape sequences for
+    /// keys. Events always come from the terminal, whatever the input is.
+    #[cfg(feature = "events")]
+    pub fn set_event_input(&mut self, events: bool) {
+        self.events = events
+    }
+
+    /// Chooses whether to draw the prompts and the buffer on stderr, instead of stdout, like
+    /// `read -p` in a shell. The lines read are still returned, not printed.
+    ///
+    /// This lets tools whose output is piped elsewhere prompt the user on the terminal.
+    pub fn set_stderr(&mut self, stderr: bool) {
+        self.stderr = stderr
+    }
+
+    /// Draws on `backend`, and puts it in raw mode and asks it for its size, rather than
+    /// the local terminal, like for a terminal connected over the network. Keys are still
+    /// read from the input.
+    ///
+    /// Drawing isn't [offloaded](Self::set_render_budget) to another thread, and
+    /// [Suspend](EditCommand::Suspend) does nothing. See [backend](crate::backend).
+    pub fn set_backend(&mut self, backend: impl Backend + 'a) {
+        self.backend = Some(backend::Shared::new(backend))
+    }
+
+    /// Chooses whether to return to the cursor using the terminal's saved cursor position
+    /// (DECSC/DECRC) after drawing the rest of the buffer, or by moving back to it.
+    ///
+    /// By default, the saved position is used unless running inside a [Multiplexer], or
+    /// `TERM` names the Linux console or a dumb terminal.
+    pub fn set_save_restore(&mut self, save_restore: bool) {
+        self.ed.save_restore = save_restore;
+        for (_, ed) in &mut self.buffers {
+            ed.save_restore = save_restore;
+        }
+    }
+
+    /// Sets 24-bit colors to be drawn as is, or approximated from the 256-color palette.
+    ///
+    /// By default, 24-bit colors are drawn if `COLORTERM` advertises them, and the
+    /// [Multiplexer], if any, can pass them on.
+    pub fn set_truecolor(&mut self, truecolor: bool) {
+        self.ed.truecolor = truecolor;
+        for (_, ed) in &mut self.buffers {
+            ed.truecolor = truecolor;
+        }
+    }
+
+    /// Sets what counts as part of a word when deleting by words, with `Ctrl+W` and `Alt+D`.
+    /// By default, it's anything but whitespace, like readline.
+    ///
+    /// ```no_run
+    /// # use repline::{Repline, repline::WordChars};
+    /// let mut rl = Repline::new("", "> ", ". ");
+    /// // `Ctrl+W` after `foo.bar_baz` leaves `foo.`
+    /// rl.set_word_chars(WordChars::With("_"));
+    /// ```
+    pub fn set_word_chars(&mut self, words: WordChars<'a>) {
+        self.ed.words = words;
+        for (_, ed) in &mut self.buffers {
+            ed.words = words;
+        }
+    }
+
+    /// Sets whether the shorter of the begin and again prompts is padded on the left to the
+    /// width of the other, so that the text of every line starts in the same column
+    pub fn set_align_prompts(&mut self, align: bool) {
+        self.ed.align = align;
+        for (_, ed) in &mut self.buffers {
+            ed.align = align;
+        }
+    }
+
+    /// Shows how long the last command took before the begin prompt, like `[1.2s] `, when it
+    /// took at least `threshold`, or never if `None` (the default).
+    ///
+    /// The loops in [prebaked](crate::prebaked) time their closures for this. Otherwise, see
+    /// [set_elapsed](Self::set_elapsed).
+    pub fn set_elapsed_threshold(&mut self, threshold: Option<Duration>) {
+        self.elapsed_threshold = threshold
+    }
+
+    /// Draws `marker` dimmed where the prompt was while a command runs, like `…`, or nothing
+    /// if `None` (the default). Output from the command draws over it, so it's best kept to
+    /// a single column.
+    ///
+    /// The loops in [prebaked](crate::prebaked) show it while their closures run. Otherwise,
+    /// see [begin_busy](Self::begin_busy).
+    pub fn set_busy_marker(&mut self, marker: Option<&'a str>) {
+        self.busy_marker = marker
+    }
+
+    /// Hides the cursor, so that it doesn't blink mid-line while a command runs, and draws
+    /// the [busy marker](Self::set_busy_marker) at the start of the line, if any. Call
+    /// [end_busy](Self::end_busy) once the command is done.
+    ///
+    /// Does nothing outside of [ReadMode::Interactive].
+    pub fn begin_busy(&mut self) -> ReplResult<()> {
+        if self.mode != ReadMode::Interactive {
+            return Ok(());
+        }
+        self.with_output(|rl, stdout| {
+            queue!(stdout, Hide)?;
+            if let Some(marker) = rl.busy_marker {
+                let marker = PrintStyledContent(rl.ed.theme.hint.apply(marker));
+                queue!(stdout, MoveToColumn(0), marker, MoveToColumn(0))?;
+            }
+            Ok(stdout.flush()?)
+        })
+    }
+
+    /// Shows the cursor again after [begin_busy](Self::begin_busy). The next read draws over
+    /// the busy marker.
+    pub fn end_busy(&mut self) -> ReplResult<()> {
+        if self.mode != ReadMode::Interactive {
+            return Ok(());
+        }
+        self.with_output(|_, stdout| {
+            queue!(stdout, Show)?;
+            Ok(stdout.flush()?)
+        })
+    }
+
+    /// Records how long the last command took, to show before the begin prompt if it took
+    /// long enough. See [set_elapsed_threshold](Self::set_elapsed_threshold).
+    pub fn set_elapsed(&mut self, elapsed: Duration) {
+        let segment = match self.elapsed_threshold {
+            Some(threshold) if elapsed >= threshold => format!("[{}] ", Elapsed(elapsed)),
+            _ => String::new(),
+        };
+        for (_, ed) in &mut self.buffers {
+            ed.segment = segment.clone();
+        }
+        self.ed.segment = segment;
+    }
+
+    /// Overrides the detected [Multiplexer] repline is running inside of
+    pub fn set_multiplexer(&mut self, multiplexer: Option<Multiplexer>) {
+        self.multiplexer = multiplexer
+    }
+
+    /// Returns the [Multiplexer] repline is running inside of, if any
+    pub fn multiplexer(&self) -> Option<Multiplexer> {
+        self.multiplexer
+    }
+
+    /// Returns the [Keymap], which maps keys to the commands they run
+    pub fn keymap(&self) -> &Keymap {
+        &self.keymap
+    }
+
+    /// Replaces the [Keymap]
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap
+    }
+
+    /// Sets what `Up` and `Down` move through: the lines of the buffer, and the history from
+    /// its edges, by default
+    pub fn set_arrow_keys(&mut self, arrows: ArrowKeys) {
+        self.arrows = arrows
+    }
+
+    /// Sets how a key which can't do anything is signaled, like `Backspace` on an empty line,
+    /// `Up` past the oldest history entry, or `Tab` when nothing completes the word: by
+    /// ringing the terminal's bell, by default
+    pub fn set_bell(&mut self, bell: Bell) {
+        self.bell = bell
+    }
+
+    /// Sets what a line feed (`\n`) read on its own does: submit like `Enter`, by default, so
+    /// that input which ends its lines with `\n`, like a script or a pipe, is read line by
+    /// line. A line feed right after a carriage return is dropped either way, so `\r\n` is
+    /// one `Enter`.
+    ///
+    /// Which command `Enter` itself runs is up to the [Keymap], like any other key.
+    pub fn set_line_feed(&mut self, line_feed: LineFeed) {
+        self.line_feed = line_feed
+    }
+
+    /// Binds `key` to `command`, like `Tab` to [EditCommand::Submit]
+    pub fn bind(&mut self, key: Key, command: EditCommand) {
+        self.keymap.bind(key, command);
+    }
+
+    /// Sets how long after `Esc` another key has to arrive to be read as part of a sequence,
+    /// like `Alt+X` or an arrow key, rather than as a key of its own. Defaults to 100ms, which
+    /// is rounded up to the next tenth of a second.
+    ///
+    /// With `None`, or outside of Unix, `Esc` waits for the next key, and is read as a key of
+    /// its own when it's pressed twice. Keys read through crossterm's event reader, like with
+    /// [set_event_input](Self::set_event_input), are told apart by crossterm instead.
+    pub fn set_escape_timeout(&mut self, timeout: Option<Duration>) {
+        self.escape_timeout = timeout
+    }
+
+    /// Calls `on_idle` whenever no key arrives for `after` while editing, with how long it's
+    /// been since the last key, so that the prompt can show fresh information, or give up.
+    ///
+    /// While an idle handler is set, keys are read from the terminal through crossterm's
+    /// event reader, as with [set_event_input](Self::set_event_input), whatever the input
+    /// is. It isn't called by [read_async](Self::read_async), whose caller can time out
+    /// instead.
+    #[cfg(feature = "events")]
+    pub fn set_idle_handler(
+        &mut self,
+        after: Duration,
+        on_idle: impl FnMut(Duration) -> Idle + 'a,
+    ) {
+        self.idle = Some(IdleHandler { on_idle: Box::new(on_idle), after, status: None })
+    }
+
+    /// Stops calling the idle handler
+    #[cfg(feature = "events")]
+    pub fn clear_idle_handler(&mut self) {
+        self.idle = None
+    }
+
+    /// Calls the idle handler until a key arrives, if there is one
+    #[cfg(feature = "events")]
+    fn wait_idle<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        let Some(IdleHandler { after, .. }) = self.idle else {
+            return Ok(());
+        };
+        w.flush()?;
+        let start = Instant::now();
+        while !crossterm::event::poll(after)? {
+            let Some(idle) = &mut self.idle else {
+                return Ok(());
+            };
+            match (idle.on_idle)(start.elapsed()) {
+                Idle::Wait => {}
+                Idle::Status(status) => {
+                    idle.status = Some(status);
+                    self.show_status();
+                    self.unflash(w)?;
+                    self.ed.redraw_tail(w)?;
+                    w.flush()?;
+                }
+                Idle::Abort => return Err(Error::TimedOut(self.ed.to_string())),
+            }
+        }
+        // the status only lasts until the next key
+        if let Some(IdleHandler { status: status @ Some(_), .. }) = &mut self.idle {
+            *status = None;
+            self.show_status();
+            self.ed.redraw_tail(w)?;
+        }
+        Ok(())
+    }
+
+    /// Enables focus reporting while reading, calling `on_focus` whenever the terminal gains
+    /// or loses focus. The prompt is redrawn afterwards, in the color `on_focus` returns, if
+    /// any, like a dim one while unfocused.
+    ///
+    /// Terminals which don't support focus reporting (`CSI ? 1004 h`) never call `on_focus`.
+    pub fn set_focus_handler(&mut self, on_focus: impl FnMut(Focus) -> Option<&'a str> + 'a) {
+        self.on_focus = Some(FocusHandler(Box::new(on_focus)))
+    }
+
+    /// Calls `on_unknown` with input which doesn't do anything, like an escape sequence which
+    /// isn't decoded, or a key with nothing bound to it, so the quirks of a terminal can be
+    /// looked into without the input being drawn over the buffer.
+    ///
+    /// ```no_run
+    /// # use repline::Repline;
+    /// let mut rl = Repline::new("", "> ", ". ");
+    /// rl.set_unknown_key_handler(|key| eprintln!("unknown key: {key:?}"));
+    /// ```
+    pub fn set_unknown_key_handler(&mut self, on_unknown: impl FnMut(Unhandled) + 'a) {
+        self.on_unknown = Some(UnknownKeyHandler(Box::new(on_unknown)))
+    }
+
+    /// Tells the [unknown key handler](Self::set_unknown_key_handler) about `key`
+    fn unknown_key(&mut self, key: Unhandled) {
+        if let Some(UnknownKeyHandler(on_unknown)) = &mut self.on_unknown {
+            on_unknown(key)
+        }
+    }
+
+    /// Disables focus reporting
+    pub fn clear_focus_handler(&mut self) {
+        self.on_focus = None
+    }
+
+    /// Calls `on_edit` after each key which inserts or deletes text, moves the cursor, or
+    /// recalls a history entry, and when a line is submitted, with the buffer and the byte
+    /// index of the cursor in it. This lets a program follow the buffer, like for a preview
+    /// pane or an input log, without handling keys itself.
+    ///
+    /// A change which doesn't shorten the buffer, like a word being capitalized, is
+    /// [Insert](EditEvent::Insert). On [Submit](EditEvent::Submit), the cursor is at the end.
+    pub fn set_edit_hook(&mut self, on_edit: impl FnMut(EditEvent, &str, usize) + 'a) {
+        self.on_edit = Some(EditHook(Box::new(on_edit)))
+    }
+
+    /// Removes the edit hook
+    pub fn clear_edit_hook(&mut self) {
+        self.on_edit = None
+    }
+
+    /// Runs the focus handler, and redraws the prompt
+    fn focus<W: Write>(&mut self, w: &mut W, focus: Focus) -> ReplResult<()> {
+        let Some(FocusHandler(on_focus)) = &mut self.on_focus else {
+            return Ok(());
+        };
+        if let Some(color) = on_focus(focus) {
+            self.ed.color_prompts(color);
+        }
+        self.ed.redraw(w)
+    }
+
+    /// Moves the cursor to the character drawn at `row` and `col` on screen, if it's in the
+    /// buffer
+    fn click<W: Write>(&mut self, w: &mut W, row: usize, col: usize) -> ReplResult<()> {
+        let Some((cursor, _)) = self.query_cursor(w)? else {
+            return Ok(());
+        };
+        // the screen row the prompt starts on, counting from 0
+        let cursor = (cursor as usize).saturating_sub(1);
+        let Some(origin) = cursor.checked_sub(self.ed.rows_above()) else {
+            return Ok(());
+        };
+        if let Some(index) = row
+            .checked_sub(origin)
+            .and_then(|row| self.ed.index_at(row, col))
+        {
+            self.ed.unmark(w)?;
+            self.ed.cursor_to(index, w)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `f` with the terminal to draw on, which also writes to the mirrors
+    fn with_output<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self, &mut Output<'a>) -> ReplResult<T>,
+    ) -> ReplResult<T> {
+        // each frame is buffered, and drawn in one go when it's flushed: the terminal isn't
+        // buffered at all, stderr neither, and stdout writes out each line as it ends
+        let primary: Box<dyn Write + 'a> = match (&self.tty, self.render_budget) {
+            _ if self.backend.is_some() => {
+                let backend = self.backend.clone().expect("checked just above");
+                Box::new(BufWriter::new(backend::Writer(backend)))
+            }
+            (Some(tty), None) => Box::new(BufWriter::new(tty.try_clone()?)),
+            (None, None) if self.stderr => Box::new(BufWriter::new(stderr().lock())),
+            (None, None) => Box::new(BufWriter::new(stdout().lock())),
+            (tty, Some(budget)) => {
+                let inner: Box<dyn Write + Send> = match tty {
+                    Some(tty) => Box::new(tty.try_clone()?),
+                    None if self.stderr => Box::new(BufWriter::new(stderr())),
+                    None => Box::new(stdout()),
+                };
+                Box::new(Renderer::new(inner, budget))
+            }
+        };
+        let uncolored = (!self.colors).then(Uncolored::default);
+        let mut output = Output { primary, mirrors: std::mem::take(&mut self.mirrors), uncolored };
+        let out = f(self, &mut output);
+        self.mirrors = output.mirrors;
+        out
+    }
+
+    /// Puts the terminal to draw on in raw mode, until the returned guard and every other
+    /// guard alive are dropped
+    fn raw(&self) -> ReplResult<RawMode<'a>> {
+        Ok(match &self.backend {
+            Some(backend) => RawMode::Backend { _guard: backend.raw().map_err(Error::Terminal)? },
+            None => RawMode::Local { _guard: raw()? },
+        })
+    }
+
+    /// Returns whether the terminal to draw on is in raw mode
+    fn is_raw(&self) -> ReplResult<bool> {
+        Ok(match &self.backend {
+            Some(backend) => backend.is_raw(),
+            None => crossterm::terminal::is_raw_mode_enabled()?,
+        })
+    }
+
+    /// Puts the terminal to draw on in raw mode, or takes it back out
+    fn set_raw(&self, raw: bool) -> ReplResult<()> {
+        match (&self.backend, raw) {
+            (Some(backend), raw) => backend.set_raw(raw)?,
+            (None, true) => crossterm::terminal::enable_raw_mode().map_err(Error::Terminal)?,
+            (None, false) => crossterm::terminal::disable_raw_mode()?,
+        }
+        Ok(())
+    }
+
+    /// Returns the width and height of the terminal to draw on, or 0 for either if it isn't
+    /// known
+    fn terminal_size(&self) -> (usize, usize) {
+        let size = match &self.backend {
+            Some(backend) => backend.size(),
+            None => crossterm::terminal::size(),
+        };
+        size.map_or((0, 0), |(width, height)| (width as usize, height as usize))
+    }
+
+    /// Prints a message (ideally an error) without moving the cursor
+    fn print_err<W: Write>(&self, w: &mut W, value: impl std::fmt::Display) -> ReplResult<()> {
+        let gutter = match self.ed.gutter_width() {
+            0 => 0,
+            n => n + 1,
+        };
+        if self.ed.at_start() {
+            let pad = Padding(gutter + self.ed.prompt_width(0));
+            self.ed.print_err(format_args!("{pad}{value}"), w)
+        } else if self.ed.at_line_start() {
+            let pad = Padding(gutter + self.ed.prompt_width(1));
+            self.ed.print_err(format_args!("{pad}{value}"), w)
+        } else {
+            self.ed.print_err(value, w)
+        }
+    }
+
+    // Prints some debug info into the editor's buffer and the provided writer
+    pub fn put<D: std::fmt::Display, W: Write>(&mut self, disp: D, w: &mut W) -> ReplResult<()> {
+        self.ed.extend(format!("{disp}").chars(), w)
+    }
+
+    /// Reads the next character of input
+    fn next_char(&mut self) -> ReplResult<char> {
+        self.next_input().ok_or(Error::EndOfInput)?
+    }
+
+    /// Takes the next character of [pushed](Self::push_input) input, then of the
+    /// [sources](Self::push_source), then of the input
+    fn next_input(&mut self) -> Option<ReplResult<char>> {
+        if let Some(c) = self.injected.pop_front() {
+            return Some(Ok(c));
+        }
+        let next = loop {
+            let Some(source) = self.sources.last_mut() else {
+                break self.input.next()?;
+            };
+            match source.0.next() {
+                Some(c) => break c,
+                None => drop(self.sources.pop()),
+            }
+        };
+        use chars::Undecodable::{BadUnicode, BadUtf8};
+        Some(match next {
+            Err(BadUtf8(_) | BadUnicode(_)) if self.lossy => Ok(char::REPLACEMENT_CHARACTER),
+            next => next.map_err(Error::from),
+        })
+    }
+
+    /// Reads and decodes the next key, or returns `None` if the input isn't understood.
+    ///
+    /// `w` is only drawn on by the idle handler, while waiting for a key.
+    #[cfg_attr(not(feature = "events"), allow(unused_variables))]
+    fn next_key<W: Write>(&mut self, w: &mut W) -> ReplResult<Option<Key>> {
+        if let Some(key) = self.unread.take() {
+            return Ok(Some(key));
+        }
+        let injected = !self.injected.is_empty() || !self.sources.is_empty();
+        #[cfg(feature = "events")]
+        if (self.events || self.idle.is_some()) && !injected {
+            self.wait_idle(w)?;
+            let key = crate::events::read_key()?;
+            self.time_key();
+            return Ok(key);
+        }
+        let c = self.next_char()?;
+        // pushed input arrives all at once, but wasn't pasted
+        if !injected {
+            self.time_key();
+        }
+        let after_cr = std::mem::replace(&mut self.after_cr, c == '\r');
+        let key = match c {
+            '\r' => Key::Enter,
+            // the rest of a \r\n line break
+            '\n' if after_cr => return Ok(None),
+            '\n' if self.line_feed == LineFeed::Enter => Key::Enter,
+            '\t' => Key::Tab,
+            '\x08' | '\x7f' => Key::Backspace,
+            // Ctrl+X: prefix for buffer commands
+            '\x18' => Key::CtrlX(self.next_char()?),
+            '\x1b' => return self.escape(injected),
+            c @ '\x01'..='\x1a' => Key::Ctrl((c as u8 - 1 + b'a') as char),
+            // Ctrl+\, Ctrl+], Ctrl+^, and Ctrl+_
+            c @ '\x1c'..='\x1f' => Key::Ctrl((c as u8 + b'@') as char),
+            c if c.is_ascii_control() => {
+                self.unknown_key(Unhandled::Undecoded(c.into()));
+                return Ok(None);
+            }
+            c => Key::Char(c),
+        };
+        Ok(Some(key))
+    }
+
+    /// Notes when a key arrived, to tell whether it's part of a paste
+    fn time_key(&mut self) {
+        if let Some(policy) = self.paste {
+            let now = Instant::now();
+            let burst = self
+                .last_key
+                .is_some_and(|last| now - last < policy.threshold);
+            self.pasting.0.set(burst);
+            self.last_key = Some(now);
+        }
+    }
+
+    /// Decodes an ANSI Escape, or a lone `Esc` if nothing follows it in time. `injected` is
+    /// whether the escape came from [pushed](Self::push_input) input or a source.
+    fn escape(&mut self, injected: bool) -> ReplResult<Option<Key>> {
+        let rest = !self.injected.is_empty() || !self.sources.is_empty();
+        let next = match self.escape_timeout {
+            // pushed input arrives all at once, so nothing more is coming
+            _ if injected && !rest => None,
+            Some(timeout) if !rest && self.backend.is_none() => {
+                match raw::with_read_timeout(timeout, || self.next_input()) {
+                    Some(next) => next,
+                    None => Some(self.next_char()),
+                }
+            }
+            _ => Some(self.next_char()),
+        };
+        let Some(next) = next else {
+            return Ok(Some(Key::Esc));
+        };
+        match next? {
+            '[' => self.csi(),
+            'O' => self.ss3(),
+            '\x1b' => Ok(Some(Key::Esc)),
+            #[cfg(feature = "clipboard")]
+            ']' if self.awaiting_clipboard => self.osc(),
+            c => Ok(Some(Key::Alt(c))),
+        }
+    }
+
+    /// Reads an Operating System Command the terminal sent, which is understood if it's the
+    /// clipboard [asked for](EditCommand::PasteClipboard), and handled like a paste
+    #[cfg(feature = "clipboard")]
+    fn osc(&mut self) -> ReplResult<Option<Key>> {
+        let mut body = String::new();
+        loop {
+            match self.next_char()? {
+                '\x07' => break,
+                // the string terminator, ESC \
+                '\x1b' => {
+                    self.next_char()?;
+                    break;
+                }
+                c => body.push(c),
+            }
+        }
+        self.awaiting_clipboard = false;
+        let Some(text) = clipboard::decode_reply(&body) else {
+            return Ok(None);
+        };
+        self.pasted = text;
+        Ok(Some(Key::Paste))
+    }
+
+    /// Decodes a Single Shift 3 sequence, which terminals send for the cursor keys in
+    /// application mode, like `OA` (Up) or `OH` (Home)
+    fn ss3(&mut self) -> ReplResult<Option<Key>> {
+        let first = self.next_char()?;
+        let mut c = first;
+        // some terminals put a modifier in between, like `O5C` (Ctrl+Right)
+        let modifiers = match c.to_digit(10) {
+            Some(param) => {
+                c = self.next_char()?;
+                Modifiers::from_param(param)
+            }
+            None => Modifiers::NONE,
+        };
+        let key = match c {
+            'A' => Key::Up(modifiers),
+            'B' => Key::Down(modifiers),
+            'C' => Key::Right(modifiers),
+            'D' => Key::Left(modifiers),
+            'H' => Key::Home(modifiers),
+            'F' => Key::End(modifiers),
+            // Enter on the keypad
+            'M' => Key::Enter,
+            c @ 'P'..='S' => Key::F(c as u8 - b'P' + 1),
+            other => {
+                let raw = match first == other {
+                    true => format!("\x1bO{other}"),
+                    false => format!("\x1bO{first}{other}"),
+                };
+                self.unknown_key(Unhandled::Undecoded(raw));
+                return Ok(None);
+            }
+        };
+        Ok(Some(key))
+    }
+
+    /// Decodes an ANSI Control Sequence Introducer, like `1;5C` (Ctrl+Right) or `3~` (Delete).
+    ///
+    /// The whole sequence is always consumed, even if it isn't understood, so that none of
+    /// it leaks into the buffer.
+    fn csi(&mut self) -> ReplResult<Option<Key>> {
+        let mut params = String::new();
+        let last = loop {
+            match self.next_char()? {
+                // rxvt ends a key held with Shift in `$`, like `7$` (Shift+Home)
+                '$' if !params.is_empty() && params.bytes().all(|b| b.is_ascii_digit()) => {
+                    break '$';
+                }
+                // parameter and intermediate bytes
+                c @ ('\x20'..='\x3f') => params.push(c),
+                // final byte
+                c @ ('\x40'..='\x7e') => break c,
+                _ => return Ok(None),
+            }
+        };
+        // a mouse report, like `<0;12;3M`: a press of the left button at column 12, row 3
+        if let Some(report) = params.strip_prefix('<') {
+            let fields: Vec<u16> = report.split(';').filter_map(|n| n.parse().ok()).collect();
+            return Ok(match (last, &fields[..]) {
+                ('M', &[0, col, row]) if col > 0 && row > 0 => Some(Key::Click(row - 1, col - 1)),
+                _ => None,
+            });
+        }
+        // the Linux console's F1 to F5, like `[A`
+        if last == '[' && params.is_empty() {
+            return Ok(match self.next_char()? {
+                c @ 'A'..='E' => Some(Key::F(c as u8 - b'A' + 1)),
+                _ => None,
+            });
+        }
+        // each parameter defaults to 1 when it's left out
+        let param = |n: usize| {
+            params
+                .split(';')
+                .nth(n)
+                .and_then(|p| p.parse().ok())
+                .unwrap_or(1)
+        };
+        // rxvt ends the `~` keys in `$`, `^`, or `@` when Shift, Ctrl, or both are held
+        let (last, modifiers) = match last {
+            '$' => ('~', Modifiers::SHIFT),
+            '^' => ('~', Modifiers::CTRL),
+            '@' => ('~', Modifiers::CTRL_SHIFT),
+            last => (last, Modifiers::from_param(param(1))),
+        };
+        let key = match (last, param(0)) {
+            ('A', _) => Key::Up(modifiers),
+            ('B', _) => Key::Down(modifiers),
+            ('C', _) => Key::Right(modifiers),
+            ('D', _) => Key::Left(modifiers),
+            ('H', _) | ('~', 1 | 7) => Key::Home(modifiers),
+            ('F', _) | ('~', 4 | 8) => Key::End(modifiers),
+            ('~', 3) => Key::Delete(modifiers),
+            ('~', 5) => Key::PageUp,
+            ('~', 6) => Key::PageDown,
+            (c @ 'P'..='S', _) => Key::F(c as u8 - b'P' + 1),
+            // F5 to F12 skip a number after F5 and F10
+            ('~', n @ 11..=15) => Key::F(n as u8 - 10),
+            ('~', n @ 17..=21) => Key::F(n as u8 - 11),
+            ('~', n @ 23..=24) => Key::F(n as u8 - 12),
+            ('~', 200) => return self.read_paste().map(Some),
+            ('I', _) if params.is_empty() => Key::Focus(Focus::Gained),
+            ('O', _) if params.is_empty() => Key::Focus(Focus::Lost),
+            _ => {
+                self.unknown_key(Unhandled::Undecoded(format!("\x1b[{params}{last}")));
+                return Ok(None);
+            }
+        };
+        Ok(Some(key))
+    }
+
+    /// Reads the text of a bracketed paste, up to the sequence which ends it
+    fn read_paste(&mut self) -> ReplResult<Key> {
+        const END: &str = "\x1b[201~";
+        let mut text = String::new();
+        while !text.ends_with(END) {
+            text.push(self.next_char()?);
+        }
+        text.truncate(text.len() - END.len());
+        self.pasted = text;
+        Ok(Key::Paste)
+    }
+
+    /// Inserts the last argument of the previous history entry, or, if `again`, swaps the one
+    /// just inserted for that of the entry before the one it came from
+    fn yank_last_arg<W: Write>(&mut self, w: &mut W, again: bool) -> ReplResult<()> {
+        let (back, len) = if again {
+            (self.last_arg.0 + 1, self.last_arg.1)
+        } else {
+            (0, 0)
+        };
+        // only entries older than the one being recalled, if any
+        let entries = (0..self.hindex)
+            .rev()
+            .filter_map(|index| self.history.get(index));
+        let mut args = entries.filter_map(|text| text.split_whitespace().next_back());
+        let Some(arg) = args.nth(back).map(str::to_string) else {
+            return Ok(());
+        };
+        self.ed.unmark(w)?;
+        let cursor = self.ed.cursor();
+        self.ed.erase(cursor.saturating_sub(len)..cursor, w)?;
+        self.ed.extend(arg.chars(), w)?;
+        self.last_arg = (back, arg.chars().count());
+        Ok(())
+    }
+
+    /// Keeps the buffer before another history entry is recalled: the entry being recalled
+    /// keeps its edits, and a new line is kept as the draft, for `Down` to return to
+    fn leave_entry(&mut self) {
+        if self.hindex < self.history.len() {
+            self.history.replace(self.hindex, self.ed.to_string())
+        } else {
+            self.draft = Some(self.ed.state());
+        }
+    }
+
+    /// Recalls the previous history entry, returning false if there isn't one
+    fn history_prev<W: Write>(&mut self, w: &mut W) -> ReplResult<bool> {
+        let Some(hindex) = self.hindex.min(self.history.len()).checked_sub(1) else {
+            return Ok(false);
+        };
+        self.leave_entry();
+        self.hindex = hindex;
+        self.restore_history(w, true)?;
+        Ok(true)
+    }
+
+    /// Recalls the next history entry, or the draft past the last one, returning false if
+    /// there isn't one
+    fn history_next<W: Write>(&mut self, w: &mut W) -> ReplResult<bool> {
+        if self.hindex >= self.history.len() {
+            return Ok(false);
+        }
+        self.leave_entry();
+        self.hindex += 1;
+        match self.hindex < self.history.len() {
+            true => self.restore_history(w, false)?,
+            false => self.restore_draft(w)?,
+        }
+        Ok(true)
+    }
+
+    /// Recalls the oldest history entry
+    fn history_first<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        if self.hindex == 0 || self.history.is_empty() {
+            return Ok(());
+        }
+        self.leave_entry();
+        self.hindex = 0;
+        self.restore_history(w, true)
+    }
+
+    /// Stops recalling history entries, going back to the draft
+    fn history_last<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        if self.hindex >= self.history.len() {
+            return Ok(());
+        }
+        self.leave_entry();
+        self.hindex = self.history.len();
+        self.restore_draft(w)
+    }
+
+    /// Goes back to the line being typed before the history was recalled, as it was left
+    fn restore_draft<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        self.ed.undraw(w)?;
+        queue!(w, Clear(ClearType::FromCursorDown))?;
+        self.ed.set_state(self.draft.take().unwrap_or_default());
+        self.ed.draw_head(w)?;
+        self.ed.redraw_tail(w)
+    }
+
+    /// Drops the selection and moves the cursor
+    fn motion<W: Write>(&mut self, w: &mut W, motion: Motion<'a, W>) -> ReplResult<()> {
+        self.ed.unmark(w)?;
+        motion(&mut self.ed, w)
+    }
+
+    /// Extends the selection by moving the cursor
+    fn select<W: Write>(&mut self, w: &mut W, motion: Motion<'a, W>) -> ReplResult<()> {
+        self.ed.mark();
+        motion(&mut self.ed, w)?;
+        self.ed.redraw(w)
+    }
+
+    /// Extends the block (column) selection by moving the cursor
+    fn select_block<W: Write>(&mut self, w: &mut W, motion: Motion<'a, W>) -> ReplResult<()> {
+        self.ed.mark_block();
+        motion(&mut self.ed, w)?;
+        self.ed.redraw(w)
+    }
+
+    /// Returns the registered [pair](Self::add_pair) around the cursor, if `last` typed its
+    /// opener, closing it
+    fn fresh_pair(&self, last: Option<EditCommand>) -> Option<(&'a str, &'a str)> {
+        let Self { pairs, ed, .. } = self;
+        if !matches!(last, Some(EditCommand::Insert(_))) {
+            return None;
+        }
+        let pairs = pairs.iter().copied();
+        pairs
+            .filter(|(open, close)| ed.ends_with(open.chars()) && ed.starts_with(close.chars()))
+            .max_by_key(|(open, _)| open.len())
+    }
+
+    /// Finds the innermost registered [pair](Self::add_pair) enclosing the cursor, returning
+    /// the indices of its delimiters, and the delimiters themselves
+    fn nearest_pair(&self) -> Option<((usize, usize), &'a str, &'a str)> {
+        let Self { pairs, ed, .. } = self;
+        pairs
+            .iter()
+            .filter_map(|&(open, close)| Some((ed.enclosing(open, close)?, open, close)))
+            .max_by_key(|&((start, _), open, _)| (start, open.len()))
+    }
+
+    /// Returns the range of the innermost registered [pair](Self::add_pair) enclosing the
+    /// cursor, including the delimiters themselves unless `inner` is set
+    fn pair_range(&self, inner: bool) -> Option<Range<usize>> {
+        let ((start, end), open, close) = self.nearest_pair()?;
+        Some(match inner {
+            true => start + open.chars().count()..end,
+            false => start..end + close.chars().count(),
+        })
+    }
+
+    /// Selects the contents of the innermost pair enclosing the cursor, or the whole pair
+    fn select_pair<W: Write>(&mut self, w: &mut W, inner: bool) -> ReplResult<()> {
+        if let Some(range) = self.pair_range(inner) {
+            self.ed.unmark(w)?;
+            self.ed.select(range, w)?;
+        }
+        Ok(())
+    }
+
+    /// Deletes the contents of the innermost pair enclosing the cursor, or the whole pair
+    fn delete_pair<W: Write>(&mut self, w: &mut W, inner: bool) -> ReplResult<()> {
+        if let Some(range) = self.pair_range(inner) {
+            self.ed.unmark(w)?;
+            self.ed.erase(range, w)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the innermost registered [pair](Self::add_pair) enclosing the cursor
+    fn unsurround<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        if let Some((_, open, close)) = self.nearest_pair() {
+            self.ed.unsurround(open, close, w)?;
+        }
+        Ok(())
+    }
+
+    /// Searches the history for lines containing what the user types, newest first,
+    /// showing the match in the buffer and the query on the status line.
+    ///
+    /// Repeating the search command finds the next older match, `Enter` keeps the match,
+    /// and `Ctrl+G` goes back to the line as it was. Any other key keeps the match, and
+    /// then does what it usually does.
+    fn incremental_search<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        let before = self.ed.state();
+        let (mut query, mut at) = (String::new(), None::<usize>);
+        let mut failed = false;
+        loop {
+            let label = if failed {
+                self.strings.search_failed
+            } else {
+                self.strings.search
+            };
+            self.ed.set_status(Some(format!("{label}: {query}")));
+            self.ed.redraw(w)?;
+            w.flush()?;
+
+            let Some(key) = self.next_key(w)? else {
+                continue;
+            };
+            // where to look for the next match, from newest to oldest
+            let from = match (key, self.keymap.get(key)) {
+                (Key::Focus(focus), _) => {
+                    self.focus(w, focus)?;
+                    continue;
+                }
+                (_, Some(EditCommand::SearchHistory)) => at.unwrap_or(self.history.len()),
+                (Key::Char(c), _) => {
+                    query.push(c);
+                    at.map_or(self.history.len(), |at| at + 1)
+                }
+                (Key::Backspace, _) => {
+                    query.pop();
+                    self.history.len()
+                }
+                (Key::Ctrl('g') | Key::Esc, _) => {
+                    self.ed.unmark(w)?;
+                    self.ed.replace_all(&before.text, w)?;
+                    self.ed.cursor_to(before.cursor, w)?;
+                    break;
+                }
+                (Key::Enter, _) => break,
+                (key, _) => {
+                    self.unread = Some(key);
+                    break;
+                }
+            };
+            let found = (0..from).rev().find_map(|i| {
+                let text = self.history.get(i)?;
+                Some((i, text.find(query.as_str())?, text))
+            });
+            failed = found.is_none();
+            if let Some((i, byte, text)) = found {
+                at = Some(i);
+                let cursor = text[..byte].chars().count();
+                let text = text.to_string();
+                self.ed.restore(&text, w)?;
+                self.ed.cursor_to(cursor, w)?;
+            }
+        }
+        self.show_status();
+        self.ed.redraw(w)
+    }
+
+    /// Reads the hex digits of a codepoint the user types, showing them on the status line,
+    /// and inserts its character.
+    ///
+    /// `Enter` or `Space` inserts the character, and `Ctrl+G` or `Esc` gives up. Any other
+    /// key inserts the character, if the digits make one, and then does what it usually does.
+    fn codepoint_entry<W: Write>(&mut self, w: &mut W) -> ReplResult<()> {
+        let mut digits = String::new();
+        let end = loop {
+            let label = self.strings.codepoint;
+            self.ed.set_status(Some(format!("{label}{digits}")));
+            self.ed.redraw(w)?;
+            w.flush()?;
+
+            let Some(key) = self.next_key(w)? else {
+                continue;
+            };
+            match key {
+                Key::Focus(focus) => self.focus(w, focus)?,
+                Key::Char(c) if c.is_ascii_hexdigit() && digits.len() < 6 => digits.push(c),
+                Key::Backspace => drop(digits.pop()),
+                Key::Ctrl('g') | Key::Esc => break None,
+                Key::Enter | Key::Char(' ') => break Some(None),
+                key => break Some(Some(key)),
+            }
+        };
+        self.show_status();
+        self.ed.redraw(w)?;
+        let Some(unread) = end else {
+            return Ok(());
+        };
+        let codepoint = u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32);
+        if let Some(c) = codepoint {
+            self.insert_filtered(c, w)?;
+        }
+        self.unread = unread;
+        Ok(())
+    }
+
+    /// Restores the currently selected history
+    fn restore_history<W: Write>(&mut self, w: &mut W, upward: bool) -> ReplResult<()> {
+        let Self { history, hindex, ed, metrics, .. } = self;
+        if let Some(history) = history.get(*hindex) {
+            if let Some(metrics) = metrics {
+                metrics.recalls += 1;
+            }
+            ed.restore(history, w)?;
+            // at the end of the first line, from where `Up` goes on to older entries
+            if upward {
+                ed.cursor_start(w)?;
+                ed.cursor_line_end(w)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Usage counters for a single [read](Repline::read), for measuring how much effort
+/// it takes users to enter a line
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Metrics {
+    /// Keys pressed, counting each escape sequence once
+    pub keystrokes: usize,
+    /// Presses of Backspace
+    pub backspaces: usize,
+    /// History entries recalled with the arrow keys
+    pub recalls: usize,
+    /// Completion candidates inserted with `Tab`
+    pub completions: usize,
+    /// Time spent in the read
+    pub elapsed: Duration,
+}
+
+/// A cursor motion on the [Editor]
+type Motion<'a, W> = fn(&mut Editor<'a>, &mut W) -> ReplResult<()>;
+
+/// How a [Repline] reads lines
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ReadMode {
+    /// Lines are edited in place on an ANSI terminal
+    #[default]
+    Interactive,
+    /// The prompt is printed without color, and lines are read as-is, for terminals which
+    /// don't understand escape sequences
+    Dumb,
+    /// Lines are read as-is, without a prompt, for reading from pipes and files
+    Plain,
+}
+
+impl ReadMode {
+    /// Picks the mode which suits stdin and stdout: [Plain](ReadMode::Plain) unless both are
+    /// terminals, and [Dumb](ReadMode::Dumb) if `TERM` is `dumb`
+    pub fn detect() -> Self {
+        Self::detect_with(stdout().is_terminal())
+    }
+
+    /// Picks the mode like [detect](Self::detect), for drawing on stderr instead of stdout
+    pub fn detect_stderr() -> Self {
+        Self::detect_with(stderr().is_terminal())
+    }
+
+    /// Picks the mode for stdin and an output which `terminal` says is a terminal or not
+    pub(crate) fn detect_with(terminal: bool) -> Self {
+        if !(stdin().is_terminal() && terminal) {
+            Self::Plain
+        } else if std::env::var("TERM").is_ok_and(|term| term == "dumb") {
+            Self::Dumb
+        } else {
+            Self::Interactive
+        }
+    }
+}
+
+/// Uppercases the first character of `word`, and lowercases the rest
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    let first = chars.next().into_iter().flat_map(char::to_uppercase);
+    first.chain(chars.flat_map(char::to_lowercase)).collect()
+}
+
+/// Where [Repline::auto_with] draws when stdin is a terminal, but stdout is redirected
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Fallback {
+    /// Draws on stderr, if it's a terminal
+    #[default]
+    Stderr,
+    /// Draws on the controlling terminal (`/dev/tty`, or `CONOUT$` on Windows), if there is one
+    Tty,
+    /// Reads lines as-is, without a prompt, as if stdin were redirected too
+    Plain,
+}
+
+/// What `Up` and `Down` ([EditCommand::Up] and [EditCommand::Down]) move through. See
+/// [Repline::set_arrow_keys].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ArrowKeys {
+    /// The lines of the buffer, and the history from the first and last lines
+    #[default]
+    Edges,
+    /// The history, wherever the cursor is, like in a calculator
+    History,
+    /// The lines of the buffer only, like in a code editor
+    Lines,
+}
+
+/// How a key which can't do anything is signaled. See [Repline::set_bell].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Bell {
+    /// Rings the terminal's bell
+    #[default]
+    Audible,
+    /// Reverses the colors of the screen until the next key, without holding it up
+    Visible,
+    /// Does nothing, leaving the key to look like it was ignored
+    None,
+}
+
+/// What a line feed read on its own is read as. See [Repline::set_line_feed].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum LineFeed {
+    /// `Enter`, like a carriage return
+    #[default]
+    Enter,
+    /// `Ctrl+J`, which is ignored unless it's bound in the [Keymap]
+    CtrlJ,
+}
+
+/// Opens the controlling terminal for drawing on
+fn open_tty() -> std::io::Result<File> {
+    #[cfg(windows)]
+    return OpenOptions::new().write(true).open("CONOUT$");
+    #[cfg(not(windows))]
+    OpenOptions::new().write(true).open("/dev/tty")
+}
+
+/// A file in the temporary directory which only the user can read, removed when dropped
+struct TempFile(std::path::PathBuf);
+
+impl TempFile {
+    /// Creates a file with an unguessable name holding `text`. A file already there, which
+    /// may have been planted or be a symlink, is never opened: another name is tried instead.
+    fn create(text: &str) -> Result<Self> {
+        use std::hash::{BuildHasher, RandomState};
+        let mut tries = 0;
+        loop {
+            let name = RandomState::new().hash_one((std::process::id(), Instant::now(), tries));
+            let path = std::env::temp_dir().join(format!("repline-{name:016x}.txt"));
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            match options.open(&path) {
+                Ok(mut file) => {
+                    let temp = Self(path);
+                    file.write_all(text.as_bytes())?;
+                    return Ok(temp);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && tries < 8 => tries += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// How keys which arrive in a burst, like a paste, are treated. See
+/// [Repline::set_paste_policy].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PastePolicy {
+    /// Keys which arrive within this long of the previous key are taken as part of a paste
+    pub threshold: Duration,
+    /// Whether pasted openers of [pairs](Repline::add_pair) are closed automatically
+    pub pairs: bool,
+    /// Whether a pasted `Tab` expands snippets and completes, rather than indenting
+    pub expand: bool,
+    /// Whether a pasted line break submits the buffer
+    pub submit: bool,
+}
+
+impl Default for PastePolicy {
+    /// Pasted text is inserted as-is, and never submitted
+    fn default() -> Self {
+        Self { threshold: Duration::from_millis(5), pairs: false, expand: false, submit: false }
+    }
+}
+
+/// Tells whether the key being handled arrived in a burst, like a paste. See
+/// [Repline::pasting].
+#[derive(Clone, Debug, Default)]
+pub struct Pasting(Rc<Cell<bool>>);
+
+impl Pasting {
+    /// Returns true if the key being handled is part of a paste
+    pub fn get(&self) -> bool {
+        self.0.get()
+    }
+}
+
+/// Iterates over the lines a [Repline] reads. See [Repline::lines].
+#[derive(Debug)]
+pub struct Lines<'r, 'a, R: Read> {
+    rl: Option<&'r mut Repline<'a, R>>, // None once the iteration has ended
+}
+
+impl<R: Read> Iterator for Lines<'_, '_, R> {
+    type Item = ReplResult<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rl = self.rl.as_mut()?;
+        match rl.read() {
+            Ok(mut line) => {
+                rl.accept();
+                if line.ends_with('\n') {
+                    line.pop();
+                }
+                Some(Ok(line))
+            }
+            Err(Error::CtrlC(_) | Error::CtrlD(_) | Error::EndOfInput) => {
+                self.rl.take()?.deny();
+                None
+            }
+            Err(e) => {
+                self.rl = None;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<'r, 'a, R: Read> IntoIterator for &'r mut Repline<'a, R> {
+    type Item = ReplResult<String>;
+    type IntoIter = Lines<'r, 'a, R>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.lines()
+    }
+}
+
+/// Where a line that was read ended on screen, relative to where the cursor was left.
+///
+/// Lets the application print beside the submitted line, like a result or an error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EndPosition {
+    /// How many lines above the cursor the line ended
+    pub up: usize,
+    /// The screen column just past the end of the line
+    pub column: usize,
+}
+
+impl EndPosition {
+    /// Moves the cursor from where the read left it to just past the end of the line
+    pub fn move_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        queue!(w, MoveUp(self.up as u16), MoveToColumn(self.column as u16))
+    }
+
+    /// Moves the cursor back from the line to where the read left it, at the start of its
+    /// line
+    pub fn move_back<W: Write>(&self, w: &mut W) -> Result<()> {
+        queue!(w, MoveDown(self.up as u16), MoveToColumn(0))
+    }
+}
+
+/// What an [idle handler](Repline::set_idle_handler) asks for, when no key has arrived for
+/// a while
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Idle {
+    /// Keep waiting
+    Wait,
+    /// Show a message on the status line until the next key, and keep waiting
+    Status(String),
+    /// End the read with [Error::TimedOut]
+    Abort,
+}
+
+/// Called while no key arrives
+#[cfg(feature = "events")]
+struct IdleHandler<'a> {
+    on_idle: Box<IdleFn<'a>>,
+    after: Duration,        // how long without a key before calling on_idle
+    status: Option<String>, // the message on_idle asked to show, until the next key
+}
+#[cfg(feature = "events")]
+type IdleFn<'a> = dyn FnMut(Duration) -> Idle + 'a;
+
+#[cfg(feature = "events")]
+impl std::fmt::Debug for IdleHandler<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { after, status, .. } = self;
+        f.debug_struct("IdleHandler")
+            .field("after", after)
+            .field("status", status)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Whether the terminal gained or lost focus
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Focus {
+    Gained,
+    Lost,
+}
+
+/// What a key did to the buffer, for the [edit hook](Repline::set_edit_hook)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EditEvent {
+    /// Text was inserted, or changed
+    Insert,
+    /// Text was deleted
+    Delete,
+    /// The cursor moved, and the text didn't change
+    Move,
+    /// A history entry was recalled
+    History,
+    /// The line was submitted
+    Submit,
+}
+
+/// A source [pushed](Repline::push_source) in front of the input
+struct Source<'a>(Chars<Bytes<BoxedRead<'a>>>);
+type BoxedRead<'a> = Box<dyn Read + 'a>;
+
+impl std::fmt::Debug for Source<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Source").finish_non_exhaustive()
+    }
+}
+
+/// Called after each key which changes the buffer, with what it did
+struct EditHook<'a>(Box<EditFn<'a>>);
+type EditFn<'a> = dyn FnMut(EditEvent, &str, usize) + 'a;
+
+impl std::fmt::Debug for EditHook<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("EditHook").finish_non_exhaustive()
+    }
+}
+
+/// Called when the terminal gains or loses focus, returning the new prompt color, if any
+struct FocusHandler<'a>(Box<dyn FnMut(Focus) -> Option<&'a str> + 'a>);
+
+impl std::fmt::Debug for FocusHandler<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("FocusHandler").finish_non_exhaustive()
+    }
+}
+
+/// Called with input which doesn't do anything
+struct UnknownKeyHandler<'a>(Box<dyn FnMut(Unhandled) + 'a>);
+
+impl std::fmt::Debug for UnknownKeyHandler<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("UnknownKeyHandler").finish_non_exhaustive()
+    }
+}
+
+/// Raw mode on the local terminal or the [backend](Repline::set_backend), for as long as
+/// it's held
+enum RawMode<'a> {
+    Local { _guard: RawGuard },
+    Backend { _guard: backend::Raw<'a> },
+}
+
+/// Shows a live result beside the line being edited
+struct Echo<'a> {
+    echo: Box<EchoFn<'a>>,
+    drawn: Option<(usize, usize)>, // the line and column the result was drawn at
+}
+type EchoFn<'a> = dyn FnMut(&str) -> Option<String> + 'a;
+
+impl std::fmt::Debug for Echo<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Echo")
+            .field("drawn", &self.drawn)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Constrains what can be typed to text the filter accepts
+struct Filter<'a> {
+    filter: Box<FilterFn<'a>>,
+    hint: &'a str,  // shown on the status line when a keystroke is rejected
+    rejected: bool, // whether the last keystroke was rejected
+}
+type FilterFn<'a> = dyn FnMut(&str) -> bool + 'a;
+
+impl std::fmt::Debug for Filter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { hint, rejected, .. } = self;
+        f.debug_struct("Filter")
+            .field("hint", hint)
+            .field("rejected", rejected)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Carries indentation onto new lines, with a function which decides whether a line opens
+/// a block, and so adds a level
+struct AutoIndent<'a>(Option<Box<OpensFn<'a>>>);
+type OpensFn<'a> = dyn FnMut(&str) -> bool + 'a;
+
+impl std::fmt::Debug for AutoIndent<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AutoIndent").finish_non_exhaustive()
+    }
+}
+
+/// Recalls history entries which a predicate accepts
+struct HistorySearch<'a>(Box<SearchFn<'a>>);
+type SearchFn<'a> = dyn FnMut(&str) -> bool + 'a;
+
+impl std::fmt::Debug for HistorySearch<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("HistorySearch").finish_non_exhaustive()
+    }
+}
+
+/// Keeps the lines a predicate accepts out of the history
+struct HistoryIgnore<'a>(Box<SearchFn<'a>>);
+
+impl std::fmt::Debug for HistoryIgnore<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("HistoryIgnore").finish_non_exhaustive()
+    }
+}
+
+/// Splits each line read into logical lines
+struct Splitter<'a>(Box<SplitFn<'a>>);
+type SplitFn<'a> = dyn FnMut(&str) -> Vec<String> + 'a;
+
+impl std::fmt::Debug for Splitter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Splitter").finish_non_exhaustive()
+    }
+}
+
+/// Decides whether Enter submits the buffer
+struct BoxedValidator<'a>(Box<dyn Validator + 'a>);
+
+impl std::fmt::Debug for BoxedValidator<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("BoxedValidator").finish_non_exhaustive()
+    }
+}
+
+/// Returns the index in characters of byte index `at` in `text`, rounding down to the start
+/// of a character
+fn char_index(text: &str, at: usize) -> usize {
+    let at = (0..=at.min(text.len()))
+        .rev()
+        .find(|&i| text.is_char_boundary(i))
+        .unwrap_or(0);
+    text[..at].chars().count()
+}
+
+/// Sees each key before the editor
+struct BoxedMiddleware<'a>(Box<dyn Middleware + 'a>);
+
+impl std::fmt::Debug for BoxedMiddleware<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("BoxedMiddleware").finish_non_exhaustive()
+    }
+}
+
+/// The [Middleware] below the one handling a key, down to the editor
+struct Layers<'r, 'a, R: Read, W> {
+    rl: &'r mut Repline<'a, R>,
+    layers: &'r mut [BoxedMiddleware<'a>], // the outermost is last
+    w: &'r mut W,
+}
+
+impl<R: Read, W: Write> Dispatch for Layers<'_, '_, R, W> {
+    fn next(&mut self, key: Key) -> ReplResult<Option<String>> {
+        let Self { rl, layers, w } = self;
+        match layers.split_last_mut() {
+            Some((BoxedMiddleware(layer), layers)) => {
+                let mut below = Layers { rl: &mut **rl, layers, w: &mut **w };
+                layer.handle(key, &mut Context(&mut below))
+            }
+            None => rl.handle_key(Some(key), *w),
+        }
+    }
+
+    fn text(&self) -> String {
+        self.rl.ed.to_string()
+    }
+
+    fn cursor(&self) -> usize {
+        self.rl.ed.byte_cursor()
+    }
+}
+
+/// Makes the prompts at draw time
+struct BoxedPrompt<'a>(Box<dyn Prompt + 'a>);
+
+impl std::fmt::Debug for BoxedPrompt<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("BoxedPrompt").finish_non_exhaustive()
+    }
+}
+
+/// Rewrites the buffer on demand
+struct BoxedFormatter<'a>(Box<dyn Formatter + 'a>);
+
+impl std::fmt::Debug for BoxedFormatter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("BoxedFormatter").finish_non_exhaustive()
+    }
+}
+
+/// Writers which mirror everything drawn on the terminal
+#[derive(Default)]
+struct Mirrors<'a>(Vec<Box<dyn Write + 'a>>);
+
+impl std::fmt::Debug for Mirrors<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Mirrors").field(&self.0.len()).finish()
+    }
+}
+
+/// The terminal being drawn on, which fans each write out to the mirrors
+struct Output<'a> {
+    primary: Box<dyn Write + 'a>,
+    mirrors: Mirrors<'a>,
+    uncolored: Option<Uncolored>, // strips colors and styles, if they're turned off
+}
+
+impl Write for Output<'_> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if let Some(uncolored) = &mut self.uncolored {
+            let stripped = uncolored.strip(buf);
+            self.primary.write_all(&stripped)?;
+            self.mirrors
+                .0
+                .retain_mut(|mirror| mirror.write_all(&stripped).is_ok());
+            return Ok(buf.len());
+        }
+        let len = self.primary.write(buf)?;
+        self.mirrors
+            .0
+            .retain_mut(|mirror| mirror.write_all(&buf[..len]).is_ok());
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.mirrors.0.retain_mut(|mirror| mirror.flush().is_ok());
+        self.primary.flush()
+    }
+}
+
+/// Strips Select Graphic Rendition sequences (`ESC [ ... m`), which set colors and styles,
+/// from what's written, keeping every other sequence
+#[derive(Debug, Default)]
+struct Uncolored {
+    pending: Vec<u8>, // an escape sequence split across writes, held until it's complete
+}
+
+impl Uncolored {
+    fn strip(&mut self, buf: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(buf.len());
+        for &b in buf {
+            let pending = &mut self.pending;
+            match (pending.len(), b) {
+                (0, b'\x1b') => pending.push(b),
+                (0, b) => out.push(b),
+                // not a control sequence
+                (1, b) if b != b'[' => out.extend(pending.drain(..).chain([b])),
+                // the final byte ends it
+                (2.., b'\x40'..=b'\x7e') => {
+                    pending.push(b);
+                    match b {
+                        b'm' => pending.clear(),
+                        _ => out.append(pending),
+                    }
+                }
+                (_, b) => pending.push(b),
+            }
+        }
+        out
+    }
+}
+
+/// Returns false if `NO_COLOR` is set, or `TERM` is `dumb`
+fn colors_wanted() -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    !no_color && !std::env::var("TERM").is_ok_and(|term| term == "dumb")
+}
+
+/// Displays a duration briefly, like `850ms`, `1.2s`, or `2m03s`
+struct Elapsed(Duration);
+impl std::fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self(elapsed) = self;
+        match elapsed.as_secs() {
+            0 => write!(f, "{}ms", elapsed.as_millis()),
+            1..60 => write!(f, "{:.1}s", elapsed.as_secs_f64()),
+            secs => write!(f, "{}m{:02}s", secs / 60, secs % 60),
+        }
+    }
+}
+
+/// Helper struct for padding inline-printed messages
+struct Padding(usize);
+impl std::fmt::Display for Padding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for _ in 0..self.0 {
+            f.write_str(" ")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "screen"))]
+mod tests {
+    use crate::testing::{Replay, Terminal, assert_cursor_at, assert_returned_line};
+    use std::io::Write;
+
+    #[test]
+    fn yank_after_a_yank_too_long_for_the_line() {
+        // "abc" is killed, then yanked before "x", which makes the line too long
+        let terminal = Terminal::new(20, 4);
+        let keys = Replay::new("abc\x15x\x01\x19\x1by\r");
+        let mut rl = terminal.repline(keys, "> ", ". ");
+        rl.set_max_len(Some(3));
+        assert_returned_line(rl.read(), "x");
+
+        rl.accept();
+        rl.add_history("echo long");
+        let mut rl = rl.swap_input(Replay::new("x\x01\x1b.\x1b.\r"));
+        assert_returned_line(rl.read(), "x");
+    }
+
+    #[test]
+    fn only_inserted_closers_are_typed_over() {
+        let terminal = Terminal::new(20, 4);
+        let mut rl = terminal.repline(Replay::new("f(a)\r"), "> ", ". ");
+        rl.set_pairs(super::BRACKETS);
+        assert_returned_line(rl.read(), "f(a)");
+
+        rl.accept();
+        // the closer was typed, not inserted
+        let mut rl = rl.swap_input(Replay::new("f)\x1b[D)\r"));
+        assert_returned_line(rl.read(), "f))");
+
+        rl.accept();
+        // the inserted closer is left behind once the cursor moves away and back
+        let mut rl = rl.swap_input(Replay::new("(\x1b[D\x1b[C)\r"));
+        assert_returned_line(rl.read(), "())");
+    }
+
+    #[test]
+    fn multibyte_text_is_redrawn_whole() {
+        // the line break redraws "é漢" below it, and recalling the entry draws it all again
+        let terminal = Terminal::new(12, 6);
+        let keys = Replay::new("hé漢\x1b[D\x1b[D\x1b\rx\r");
+        let mut rl = terminal.repline(keys, "> ", ". ");
+        assert_returned_line(rl.read(), "h\nxé漢");
+        assert_eq!(terminal.screen().lines()[..2], ["> h", ". xé漢"]);
+
+        rl.accept();
+        // the input runs out before Enter, leaving the recalled entry drawn
+        let mut rl = rl.swap_input(Replay::new("\x1b[A"));
+        assert!(rl.read().is_err());
+        assert_eq!(terminal.screen().lines()[2..4], ["> h", ". xé漢"]);
+        assert_cursor_at(&terminal.screen(), 2, 3);
+    }
+
+    #[test]
+    fn enter_mid_buffer_submits_all_of_it() {
+        // Enter is pressed in the middle of the first line
+        let terminal = Terminal::new(12, 6);
+        let keys = Replay::new("one\x1b\rtwo\x1b[A\x1b[D\r");
+        let mut rl = terminal.repline(keys, "> ", ". ");
+        assert_returned_line(rl.read(), "one\ntwo");
+        assert_eq!(terminal.screen().lines()[..2], ["> one", ". two"]);
+        // the next line is started below the last one
+        assert_cursor_at(&terminal.screen(), 2, 2);
+    }
+
+    #[test]
+    fn recalled_entry_is_laid_out_like_it_was_typed() {
+        let terminal = Terminal::new(12, 8);
+        let keys = Replay::new("first\x1b\rno\x1b\rend\r");
+        let mut rl = terminal.repline(keys, "> ", ". ");
+        assert_returned_line(rl.read(), "first\nno\nend");
+        let typed = terminal.screen().lines()[..3].to_vec();
+
+        rl.accept();
+        let mut rl = rl.swap_input(Replay::new("\x1b[A"));
+        assert!(rl.read().is_err());
+        assert_eq!(terminal.screen().lines()[3..6], typed);
+        assert_cursor_at(&terminal.screen(), 3, 7);
+    }
+
+    #[test]
+    fn pause_between_reads() {
+        let terminal = Terminal::new(20, 8);
+        let mut rl = terminal.repline(Replay::new("ls\r"), "> ", ". ");
+        assert_returned_line(rl.read(), "ls");
+
+        // like a `:!cmd` run by the closure of read_and_mut
+        rl.pause().unwrap();
+        write!(terminal.clone(), "a.txt\r\n").unwrap();
+        rl.resume().unwrap();
+        assert_eq!(
+            terminal.screen().lines()[..5],
+            ["> ls", ".", "a.txt", "> ls", "."]
+        );
+        assert_cursor_at(&terminal.screen(), 4, 2);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn undo_after_loading_a_session() {
+        let path = std::env::temp_dir().join(format!("repline-test-{}.json", std::process::id()));
+        let terminal = Terminal::new(20, 6);
+        // the input runs out mid-line, which is left in the buffer
+        let mut rl = terminal.repline(Replay::new("one two"), "> ", ". ");
+        assert!(rl.read().is_err());
+        rl.save_session(&path).unwrap();
+
+        let mut rl = terminal.repline(Replay::new("\x1f\r"), "> ", ". ");
+        rl.load_session(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_returned_line(rl.read(), "one");
     }
 }
@@ -0,0 +1,271 @@
+//! An in-memory terminal, which reads the escape sequences repline draws with into a grid of
+//! characters, so what an [Editor](crate::editor::Editor) draws can be checked without a TTY.
+//! It's also a [Backend], so a whole [Repline](crate::Repline) can draw on it.
+//!
+//! It follows the sequences repline writes: cursor movement, saving and restoring the cursor,
+//! and clearing lines and the screen. Styles are dropped, along with any sequence it doesn't
+//! know. Text wraps at the last column, and scrolls up off the bottom row, as on a terminal.
+//!
+//! ```
+//! use repline::{editor::Editor, screen::Screen};
+//!
+//! let (mut ed, mut screen) = (Editor::new("", "> ", ". "), Screen::new(8, 4));
+//! ed.cols = 8;
+//! ed.draw_head(&mut screen)?;
+//! ed.extend("some text".chars(), &mut screen)?;
+//! assert_eq!(screen.lines(), ["> some t", "ext", "", ""]);
+//! assert_eq!(screen.cursor(), (1, 3));
+//! # Ok::<(), repline::Error>(())
+//! ```
+
+use crate::backend::Backend;
+use std::{fmt::Display, io::Write};
+use unicode_width::UnicodeWidthChar;
+
+/// A grid of characters, written to like a terminal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Screen {
+    cells: Vec<Vec<char>>,
+    cols: usize,
+    row: usize,
+    /// Equal to `cols` after writing to the last column, until the next character wraps
+    col: usize,
+    saved: (usize, usize),
+    /// Bytes of a character or sequence which haven't all been written yet
+    pending: Vec<u8>,
+}
+
+/// Fills the cell after a wide character
+const WIDE: char = '\0';
+
+impl Screen {
+    /// Constructs a blank Screen, `cols` wide and `rows` tall, with the cursor at the top left.
+    pub fn new(cols: usize, rows: usize) -> Self {
+        let (cols, rows) = (cols.max(1), rows.max(1));
+        Self {
+            cells: vec![vec![' '; cols]; rows],
+            cols,
+            row: 0,
+            col: 0,
+            saved: (0, 0),
+            pending: vec![],
+        }
+    }
+
+    /// Gets each row's text, without trailing spaces.
+    pub fn lines(&self) -> Vec<String> {
+        let line = |row: &Vec<char>| -> String {
+            row.iter()
+                .filter(|&&c| c != WIDE)
+                .collect::<String>()
+                .trim_end()
+                .to_string()
+        };
+        self.cells.iter().map(line).collect()
+    }
+
+    /// Gets the text of one row, without trailing spaces.
+    pub fn line(&self, row: usize) -> String {
+        self.lines().swap_remove(row)
+    }
+
+    /// Gets the row and column of the cursor, counting from 0.
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.row, self.col.min(self.cols - 1))
+    }
+
+    /// Gets the width and height of the Screen.
+    pub fn size(&self) -> (usize, usize) {
+        (self.cols, self.cells.len())
+    }
+
+    fn line_feed(&mut self) {
+        if self.row + 1 < self.cells.len() {
+            self.row += 1;
+        } else {
+            self.cells.remove(0);
+            self.cells.push(vec![' '; self.cols]);
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        let width = c.width().unwrap_or(0);
+        if width == 0 {
+            return;
+        }
+        if self.col + width > self.cols {
+            self.col = 0;
+            self.line_feed();
+        }
+        let row = &mut self.cells[self.row];
+        row[self.col] = c;
+        if width == 2 && self.col + 1 < self.cols {
+            row[self.col + 1] = WIDE;
+        }
+        self.col += width;
+    }
+
+    fn clear(&mut self, row: usize, cols: std::ops::Range<usize>) {
+        self.cells[row][cols].fill(' ');
+    }
+
+    fn move_to(&mut self, row: usize, col: usize) {
+        self.row = row.min(self.cells.len() - 1);
+        self.col = col.min(self.cols - 1);
+    }
+
+    /// Runs a control sequence, `ESC [ params final`
+    fn csi(&mut self, params: &str, end: char) {
+        if params.starts_with(['?', '<', '>', '=']) {
+            return; // private modes, like hiding the cursor or bracketed paste
+        }
+        let mut nums = params.split(';').map(|n| n.parse::<usize>().unwrap_or(0));
+        let first = nums.next().unwrap_or(0);
+        let n = first.max(1);
+        let (row, col) = (self.row, self.col.min(self.cols - 1));
+        match end {
+            'A' => self.move_to(row.saturating_sub(n), col),
+            'B' => self.move_to(row + n, col),
+            'C' => self.move_to(row, col + n),
+            'D' => self.move_to(row, col.saturating_sub(n)),
+            'E' => self.move_to(row + n, 0),
+            'F' => self.move_to(row.saturating_sub(n), 0),
+            'G' => self.move_to(row, n - 1),
+            'H' | 'f' => self.move_to(n - 1, nums.next().unwrap_or(0).max(1) - 1),
+            'J' => {
+                let rows = match first {
+                    0 => {
+                        self.clear(row, col..self.cols);
+                        row + 1..self.cells.len()
+                    }
+                    1 => {
+                        self.clear(row, 0..col + 1);
+                        0..row
+                    }
+                    _ => 0..self.cells.len(),
+                };
+                rows.for_each(|row| self.clear(row, 0..self.cols));
+            }
+            'K' => match first {
+                0 => self.clear(row, col..self.cols),
+                1 => self.clear(row, 0..col + 1),
+                _ => self.clear(row, 0..self.cols),
+            },
+            's' => self.saved = self.cursor(),
+            'u' => self.move_to(self.saved.0, self.saved.1),
+            _ => {} // styles, and sequences which don't change the text
+        }
+    }
+
+    /// Runs the character or sequence at the front of `bytes`, returning its length, or 0 if
+    /// it isn't all there yet
+    fn step(&mut self, bytes: &[u8]) -> usize {
+        match bytes.first() {
+            None => 0,
+            Some(b'\x1b') => self.escape(bytes),
+            Some(_) => {
+                let len = utf8_len(bytes[0]).min(4);
+                match bytes.get(..len).map(std::str::from_utf8) {
+                    None => 0,
+                    Some(Ok(s)) => {
+                        s.chars().for_each(|c| self.control(c));
+                        len
+                    }
+                    Some(Err(_)) => 1, // not UTF-8, so skipped
+                }
+            }
+        }
+    }
+
+    fn control(&mut self, c: char) {
+        match c {
+            '\r' => self.col = 0,
+            '\n' => {
+                // moving the cursor leaves the last column, rather than wrapping from it
+                self.col = self.col.min(self.cols - 1);
+                self.line_feed()
+            }
+            '\x08' => self.col = self.col.min(self.cols - 1).saturating_sub(1),
+            '\t' => self.col = (self.col / 8 * 8 + 8).min(self.cols - 1),
+            c if c.is_control() => {}
+            c => self.print(c),
+        }
+    }
+
+    /// Runs the escape sequence at the front of `bytes`, returning its length, or 0 if it
+    /// isn't all there yet
+    fn escape(&mut self, bytes: &[u8]) -> usize {
+        match bytes.get(1) {
+            None => 0,
+            Some(b'[') => {
+                let Some(end) = bytes[2..].iter().position(|b| (0x40..0x7f).contains(b)) else {
+                    return 0;
+                };
+                let params = String::from_utf8_lossy(&bytes[2..2 + end]).into_owned();
+                self.csi(&params, bytes[2 + end] as char);
+                end + 3
+            }
+            // strings, like setting the clipboard or passing through a multiplexer
+            Some(b']' | b'P' | b'_' | b'^') => {
+                let body = &bytes[2..];
+                let bel = body.iter().position(|&b| b == b'\x07').map(|i| i + 3);
+                let st = body.windows(2).position(|w| w == b"\x1b\\").map(|i| i + 4);
+                match (bel, st) {
+                    (Some(bel), Some(st)) => bel.min(st),
+                    (end, None) | (None, end) => end.unwrap_or(0),
+                }
+            }
+            Some(b'7') => {
+                self.saved = self.cursor();
+                2
+            }
+            Some(b'8') => {
+                self.move_to(self.saved.0, self.saved.1);
+                2
+            }
+            Some(_) => 2,
+        }
+    }
+}
+
+fn utf8_len(first: u8) -> usize {
+    match first {
+        0xf0.. => 4,
+        0xe0.. => 3,
+        0xc0.. => 2,
+        _ => 1,
+    }
+}
+
+impl Write for Screen {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(buf);
+        let mut at = 0;
+        while at < bytes.len() {
+            match self.step(&bytes[at..]) {
+                0 => break,
+                used => at += used,
+            }
+        }
+        self.pending = bytes.split_off(at);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Backend for Screen {
+    fn size(&mut self) -> std::io::Result<(u16, u16)> {
+        let (cols, rows) = Screen::size(self);
+        Ok((cols as u16, rows as u16))
+    }
+}
+
+impl Display for Screen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.lines().join("\n"))
+    }
+}
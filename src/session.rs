@@ -0,0 +1,32 @@
+//! Saved sessions bundle the history, the scratch buffers, and the options a user may have
+//! changed, so that a program can pick up where the user left off after a restart.
+//!
+//! Sessions are stored as JSON, tagged with a [VERSION] which is bumped whenever the format
+//! changes. Sessions saved by older versions still load, without what they didn't save.
+
+use crate::{
+    editor::EditorState,
+    history::{Entry, Ranking},
+};
+use serde::{Deserialize, Serialize};
+
+/// The version of the session format written by this version of repline
+pub const VERSION: u32 = 2;
+
+/// Everything a saved session holds
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub version: u32,
+    /// History entries, from least to most relevant
+    pub history: Vec<Entry>,
+    pub ranking: Ranking,
+    pub context: Option<String>,
+    pub context_recall: bool,
+    /// The named scratch buffers, if any were added, with what can be undone in each
+    pub buffers: Vec<(String, EditorState)>,
+    /// The index of the active scratch buffer
+    pub buffer: usize,
+    /// The contents of the active buffer, and what can be undone in it
+    pub state: EditorState,
+    pub align_prompts: bool,
+}
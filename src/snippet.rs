@@ -0,0 +1,51 @@
+//! Snippets are abbreviations which expand into text with numbered placeholders, like
+//! `for ${1:item} in ${2} {${0}}`, which `Tab` visits in order before resuming its usual duty.
+//!
+//! Placeholders are visited from `${1}` upward, with `${0}` (if present) visited last.
+
+use std::ops::Range;
+
+/// The text of an expanded snippet, and the character ranges of its placeholders
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Expansion {
+    pub text: Vec<char>,
+    /// Placeholder ranges, in the order they're visited
+    pub stops: Vec<Range<usize>>,
+}
+
+/// Expands the `${n}` and `${n:default}` placeholders in `body`.
+///
+/// Anything which doesn't parse as a placeholder is left as-is.
+pub fn expand(body: &str) -> Expansion {
+    let mut out = Expansion::default();
+    let mut stops = vec![];
+    let mut rest = body;
+    while let Some(at) = rest.find("${") {
+        out.text.extend(rest[..at].chars());
+        rest = &rest[at..];
+        let Some((number, default, len)) = placeholder(rest) else {
+            out.text.extend("${".chars());
+            rest = &rest[2..];
+            continue;
+        };
+        let start = out.text.len();
+        out.text.extend(default.chars());
+        stops.push((number, start..out.text.len()));
+        rest = &rest[len..];
+    }
+    out.text.extend(rest.chars());
+
+    // ${0} goes last, and equal numbers keep their order
+    stops.sort_by_key(|&(number, _)| (number == 0, number));
+    out.stops = stops.into_iter().map(|(_, range)| range).collect();
+    out
+}
+
+/// Parses a placeholder at the start of `text`, returning its number, its default text,
+/// and its length in bytes
+fn placeholder(text: &str) -> Option<(usize, &str, usize)> {
+    let inner = text.strip_prefix("${")?;
+    let end = inner.find('}')?;
+    let (number, default) = inner[..end].split_once(':').unwrap_or((&inner[..end], ""));
+    Some((number.parse().ok()?, default, end + 3))
+}
@@ -0,0 +1,71 @@
+//! Text which repline draws by itself, which can be replaced to translate the interface.
+
+use crate::diagnostics::Severity;
+
+/// Text which repline draws by itself.
+///
+/// Defaults to English.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Strings<'a> {
+    /// Label for [Severity::Hint] diagnostics on the status line
+    pub hint: &'a str,
+    /// Label for [Severity::Info] diagnostics on the status line
+    pub info: &'a str,
+    /// Label for [Severity::Warning] diagnostics on the status line
+    pub warning: &'a str,
+    /// Label for [Severity::Error] diagnostics on the status line
+    pub error: &'a str,
+    /// Label for the active scratch buffer on the status line
+    pub buffer: &'a str,
+    /// Name of the buffer in use before any scratch buffers are added
+    pub main_buffer: &'a str,
+    /// Label for the query on the status line while searching the history
+    pub search: &'a str,
+    /// Label for the query on the status line while searching the history finds nothing
+    pub search_failed: &'a str,
+    /// Label for the digits on the status line while a codepoint is being typed
+    pub codepoint: &'a str,
+    /// Label for the cursor's line, like `line 12/40`, while the buffer is too tall to see
+    /// all at once
+    pub line: &'a str,
+    /// Message shown when the buffer can't be opened in an external editor, because
+    /// neither `$VISUAL` nor `$EDITOR` is set
+    pub no_editor: &'a str,
+    /// Question shown after a preview of pasted text with line breaks or control characters,
+    /// before inserting it
+    pub paste_confirm: &'a str,
+}
+
+impl<'a> Strings<'a> {
+    /// English text
+    pub const ENGLISH: Strings<'static> = Strings {
+        hint: "hint",
+        info: "info",
+        warning: "warning",
+        error: "error",
+        buffer: "buffer",
+        main_buffer: "main",
+        search: "search",
+        search_failed: "failing search",
+        codepoint: "U+",
+        line: "line",
+        no_editor: "set $VISUAL or $EDITOR to edit in an external editor",
+        paste_confirm: "insert this paste? [y/n]",
+    };
+
+    /// Returns the label for diagnostics of the given [Severity]
+    pub fn severity(&self, severity: Severity) -> &'a str {
+        match severity {
+            Severity::Hint => self.hint,
+            Severity::Info => self.info,
+            Severity::Warning => self.warning,
+            Severity::Error => self.error,
+        }
+    }
+}
+
+impl Default for Strings<'_> {
+    fn default() -> Self {
+        Strings::ENGLISH
+    }
+}
@@ -0,0 +1,153 @@
+//! End-to-end tests of a REPL's editing: recorded keys are replayed into a [Repline] which
+//! draws on an in-memory [Screen], and what it drew and returned is checked.
+//!
+//! ```
+//! use repline::{
+//!     keymap::script,
+//!     testing::{Replay, Terminal, assert_cursor_at, assert_returned_line, assert_screen_contains},
+//! };
+//!
+//! let terminal = Terminal::new(20, 4);
+//! let mut rl = terminal.repline(Replay::new(&script("ab<Left>c<Enter>")?), "> ", ". ");
+//! assert_returned_line(rl.read(), "acb");
+//! assert_screen_contains(&terminal.screen(), "> acb");
+//! assert_cursor_at(&terminal.screen(), 1, 2);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::{Repline, backend::Backend, error::ReplResult, screen::Screen};
+use std::{
+    cell::{Ref, RefCell},
+    io::{Read, Result, Write},
+    rc::Rc,
+    thread,
+    time::Duration,
+};
+
+/// How fast a [Replay] types its keys
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Speed {
+    /// All at once, like for CI
+    #[default]
+    Instant,
+    /// One character at a time, waiting this long before each after the first, like to watch
+    /// a session play out, or to test what depends on the timing of keys
+    PerKey(Duration),
+}
+
+/// Keys recorded from a terminal, or written with [script](crate::keymap::script), typed
+/// into whatever reads them at a chosen [Speed]
+#[derive(Clone, Debug)]
+pub struct Replay {
+    keys: String,
+    at: usize, // the byte of `keys` to be read next
+    speed: Speed,
+}
+
+impl Replay {
+    /// Constructs a Replay of `keys`, typed [instantly](Speed::Instant)
+    pub fn new(keys: &str) -> Self {
+        Self { keys: keys.to_string(), at: 0, speed: Speed::Instant }
+    }
+
+    /// Types the keys at `speed`
+    pub fn with_speed(self, speed: Speed) -> Self {
+        Self { speed, ..self }
+    }
+}
+
+impl Read for Replay {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let Self { keys, at, speed } = self;
+        if *at == keys.len() || buf.is_empty() {
+            return Ok(0);
+        }
+        let end = match speed {
+            Speed::Instant => keys.len(),
+            Speed::PerKey(delay) => {
+                if *at > 0 && keys.is_char_boundary(*at) {
+                    thread::sleep(*delay);
+                }
+                // the rest of the character, which may be read a byte at a time
+                (*at + 1..=keys.len())
+                    .find(|&i| keys.is_char_boundary(i))
+                    .unwrap_or(keys.len())
+            }
+        };
+        let len = (end - *at).min(buf.len());
+        buf[..len].copy_from_slice(&keys.as_bytes()[*at..*at + len]);
+        *at += len;
+        Ok(len)
+    }
+}
+
+/// A [Screen] which a [Repline] draws on as its [Backend], and which can be looked at while
+/// the Repline is still in use
+#[derive(Clone, Debug)]
+pub struct Terminal(Rc<RefCell<Screen>>);
+
+impl Terminal {
+    /// Constructs a blank Terminal, `cols` wide and `rows` tall
+    pub fn new(cols: usize, rows: usize) -> Self {
+        Self(Rc::new(RefCell::new(Screen::new(cols, rows))))
+    }
+
+    /// Constructs a [Repline] which reads `input`, and draws on this Terminal
+    pub fn repline<'a, R: Read>(&self, input: R, begin: &'a str, again: &'a str) -> Repline<'a, R> {
+        let mut rl = Repline::with_input(input, "", begin, again);
+        rl.set_backend(self.clone());
+        rl
+    }
+
+    /// Gets what's been drawn so far
+    pub fn screen(&self) -> Ref<'_, Screen> {
+        self.0.borrow()
+    }
+}
+
+impl Write for Terminal {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Backend for Terminal {
+    fn size(&mut self) -> Result<(u16, u16)> {
+        Backend::size(&mut *self.0.borrow_mut())
+    }
+}
+
+/// Panics, showing the screen, unless `text` was drawn on it. Text spanning several rows is
+/// written with a `\n` between them, without their trailing spaces.
+#[track_caller]
+pub fn assert_screen_contains(screen: &Screen, text: &str) {
+    let drawn = screen.to_string();
+    assert!(
+        drawn.contains(text),
+        "{text:?} isn't on the screen:\n{drawn}"
+    );
+}
+
+/// Panics, showing the screen, unless the cursor is at `row` and `col`, counting from 0
+#[track_caller]
+pub fn assert_cursor_at(screen: &Screen, row: usize, col: usize) {
+    let cursor = screen.cursor();
+    assert_eq!(
+        cursor,
+        (row, col),
+        "the cursor is at {cursor:?}, not ({row}, {col}):\n{screen}"
+    );
+}
+
+/// Panics unless `read` returned `line`, which is written without its line break
+#[track_caller]
+pub fn assert_returned_line(read: ReplResult<String>, line: &str) {
+    match read {
+        Ok(read) => assert_eq!(read.strip_suffix('\n').unwrap_or(&read), line),
+        Err(e) => panic!("expected {line:?}, but the read failed: {e}"),
+    }
+}
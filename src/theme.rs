@@ -0,0 +1,44 @@
+//! Styles for what repline draws by itself, which can be replaced to suit a program's colors
+//! or the terminal's.
+//!
+//! ```no_run
+//! # use repline::{Repline, theme::Theme};
+//! use crossterm::style::{Attribute, Color, ContentStyle};
+//!
+//! let mut prompt = ContentStyle::new();
+//! prompt.foreground_color = Some(Color::Yellow);
+//! prompt.attributes.set(Attribute::Bold);
+//!
+//! let mut rl = Repline::new("", "> ", ". ");
+//! rl.set_theme(Theme { prompt, again: prompt, ..Theme::default() });
+//! ```
+
+use crossterm::style::{Color, ContentStyle, Stylize};
+
+/// Styles for what repline draws by itself.
+///
+/// The prompt color a [Repline](crate::Repline) is constructed with becomes its prompt
+/// styles, which replacing the Theme replaces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Theme {
+    /// Style of the begin prompt, and the banner above it
+    pub prompt: ContentStyle,
+    /// Style of the again prompt, before each line after the first
+    pub again: ContentStyle,
+    /// Style of secondary text, like the [echo](crate::Repline::set_echo) result, how long
+    /// the last command took, or the cursor's line while the buffer is scrolled
+    pub hint: ContentStyle,
+    /// Style of error messages, like a failed [format](crate::keymap::EditCommand::Format)
+    pub error: ContentStyle,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            prompt: ContentStyle::new(),
+            again: ContentStyle::new(),
+            hint: ContentStyle::new().dim(),
+            error: ContentStyle::new().with(Color::Red),
+        }
+    }
+}
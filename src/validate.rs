@@ -0,0 +1,53 @@
+//! Validation on Enter: a [Validator] decides whether the buffer is a complete entry, or
+//! whether Enter should start another line.
+
+/// Whether the buffer is ready to be submitted
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Validation {
+    /// Enter submits the buffer
+    Complete,
+    /// Enter inserts a line break, and editing goes on
+    Incomplete,
+}
+
+/// Decides whether Enter submits the buffer, like a REPL which keeps gathering lines until
+/// the braces balance.
+///
+/// Implemented for all `FnMut(&str) -> Validation`.
+pub trait Validator {
+    /// Checks `text`, the buffer as it would be submitted, ending with a line break
+    fn validate(&mut self, text: &str) -> Validation;
+}
+
+impl<F: FnMut(&str) -> Validation> Validator for F {
+    fn validate(&mut self, text: &str) -> Validation {
+        self(text)
+    }
+}
+
+/// Keeps gathering lines until every `(`, `[`, and `{` is closed, ignoring those in
+/// double-quoted strings
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Balanced;
+
+impl Validator for Balanced {
+    fn validate(&mut self, text: &str) -> Validation {
+        let (mut depth, mut quoted, mut escaped) = (0isize, false, false);
+        for c in text.chars() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if quoted => escaped = true,
+                '"' => quoted = !quoted,
+                _ if quoted => {}
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        // too many closers can't be fixed by typing more, so let the user see the error
+        match depth > 0 || quoted {
+            true => Validation::Incomplete,
+            false => Validation::Complete,
+        }
+    }
+}